@@ -1,24 +1,36 @@
+use sha2::{Digest, Sha256};
 use std::env;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Text formats worth compressing, images and fonts are already compressed
+const COMPRESSIBLE_EXTENSIONS: [&str; 10] = ["html", "js", "mjs", "css", "json", "svg", "txt", "xml", "map", "webmanifest"];
+/// Below this size the compressed variant saves less than the extra header costs
+const MIN_COMPRESS_SIZE: u64 = 1024;
 
 fn main() -> std::io::Result<()> {
     let metadata: String = env::var("CARGO_MANIFEST_DIR").unwrap();
     let cargo_toml: String = std::fs::read_to_string(format!("{}/Cargo.toml", metadata)).unwrap();
     
     if let Some(env_section) = cargo_toml.split("[package.metadata.env]").nth(1) {
-      for line in env_section.lines().filter(|line| line.contains('=')) {
-          let parts: Vec<_> = line.split('=').collect();
-          let key: &str = parts[0].trim();
-          let value: &str = parts[1].trim().trim_matches('"');
-          println!("cargo:rustc-env={}={}", key, value);
+      // Stop at the next table, its keys are not environment variables
+      for line in env_section.lines().take_while(|line| !line.trim_start().starts_with('[')) {
+          let Some((key, value)) = line.split_once('=') else { continue };
+          println!("cargo:rustc-env={}={}", key.trim(), value.trim().trim_matches('"'));
       }
     }
     // build client as static files
-    println!("cargo:rerun-if-changed=build.rs");
-    println!("cargo:rerun-if-changed=client/src/**");
-    println!("cargo:rerun-if-changed=client/static/**");
-    println!("cargo:rerun-if-changed=client/svelte.config.js");
-    println!("cargo:rerun-if-changed=.env");
+    // Only inputs are watched, folders are scanned recursively. Paths that don't exist are skipped,
+    // cargo would otherwise rerun the script on every build.
+    for input in ["build.rs", "client/src", "client/static", "client/svelte.config.js", ".env", "client/build"] {
+        if Path::new(input).exists() {
+            println!("cargo:rerun-if-changed={}", input);
+        }
+    }
+    // Read by svelte.config.js, the client has to be rebuilt for another base path
+    println!("cargo:rerun-if-env-changed=BASE_PATH");
 
     if !check_program_installed("pnpm") {
         panic!("pnpm is not installed! install it first.");
@@ -29,9 +41,200 @@ fn main() -> std::io::Result<()> {
         let _ = build_client();
     }
 
+    // Write `.br` and `.gz` variants of the client build to OUT_DIR, embedded as `PrecompressedFiles`.
+    // Writing them into `client/build` would change an input of this script and rerun it on every build.
+    let client_build = Path::new("client/build");
+    let precompressed: PathBuf = Path::new(&env::var("OUT_DIR").unwrap()).join("precompressed");
+    std::fs::create_dir_all(&precompressed)?;
+    if client_build.exists() {
+        precompress_dir(client_build, client_build, &precompressed)?;
+    }
+    prune_variants(client_build, &precompressed, &precompressed)?;
+
+    // Identifies the exact build at runtime, see `src/server/build_info.rs`
+    emit_build_info(client_build)?;
+
+    Ok(())
+}
+
+fn emit_build_info(client_build: &Path) -> std::io::Result<()> {
+    let git_commit: String = git(&["rev-parse", "HEAD"]).unwrap_or_else(|| "unknown".to_string());
+    // Untracked files don't count, `client/build` and `.env` are never committed
+    let git_dirty: bool = git(&["status", "--porcelain", "--untracked-files=no"]).is_some_and(|status| !status.is_empty());
+    // SOURCE_DATE_EPOCH pins the timestamp for reproducible builds
+    let timestamp: u64 = match env::var("SOURCE_DATE_EPOCH").ok().and_then(|epoch| epoch.parse().ok()) {
+        Some(epoch) => epoch,
+        None => SystemTime::now().duration_since(UNIX_EPOCH).map(|since_epoch| since_epoch.as_secs()).unwrap_or_default(),
+    };
+    let rustc_version: String = Command::new(env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string()))
+        .arg("--version")
+        .output()
+        .ok()
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|version| version.trim().to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+    let mut features: Vec<String> = env::vars()
+        .filter_map(|(key, _)| key.strip_prefix("CARGO_FEATURE_").map(str::to_lowercase))
+        .filter(|feature| feature != "default")
+        .collect();
+    features.sort();
+    let client_hash: String = if client_build.exists() {
+        hash_client_build(client_build)?
+    } else {
+        "unknown".to_string()
+    };
+
+    println!("cargo:rustc-env=BUILD_GIT_COMMIT={}", git_commit);
+    println!("cargo:rustc-env=BUILD_GIT_DIRTY={}", git_dirty);
+    println!("cargo:rustc-env=BUILD_TIMESTAMP={}", timestamp);
+    println!("cargo:rustc-env=BUILD_RUSTC_VERSION={}", rustc_version);
+    println!("cargo:rustc-env=BUILD_PROFILE={}", env::var("PROFILE").unwrap_or_default());
+    println!("cargo:rustc-env=BUILD_FEATURES={}", features.join(","));
+    println!("cargo:rustc-env=BUILD_CLIENT_HASH={}", client_hash);
+
+    // A missing path would rerun the build script on every build, source tarballs have no .git
+    if Path::new(".git/HEAD").exists() {
+        println!("cargo:rerun-if-changed=.git/HEAD");
+        println!("cargo:rerun-if-changed=.git/index");
+        if let Some(head_ref) = git(&["symbolic-ref", "-q", "HEAD"]) {
+            println!("cargo:rerun-if-changed=.git/{}", head_ref);
+        }
+    }
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+
+    Ok(())
+}
+
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    String::from_utf8(output.stdout).ok().map(|stdout| stdout.trim().to_string())
+}
+
+/// SHA-256 over the path and contents of every file in the client build, in path order
+fn hash_client_build(client_build: &Path) -> std::io::Result<String> {
+    let mut files: Vec<PathBuf> = Vec::new();
+    collect_files(client_build, &mut files)?;
+    files.sort();
+
+    let mut hasher = Sha256::new();
+    for path in files {
+        let relative: PathBuf = path.strip_prefix(client_build).unwrap_or(&path).to_path_buf();
+        hasher.update(relative.to_string_lossy().replace('\\', "/").as_bytes());
+        let data: Vec<u8> = std::fs::read(&path)?;
+        // Length prefixed, so bytes can't move between a file and the next path unnoticed
+        hasher.update([0]);
+        hasher.update((data.len() as u64).to_le_bytes());
+        hasher.update(&data);
+    }
+
+    Ok(hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Mirrors `dir` into `out`, with the variants of each file under the same relative path
+fn precompress_dir(root: &Path, dir: &Path, out: &Path) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            precompress_dir(root, &path, out)?;
+            continue;
+        }
+        let target: PathBuf = out.join(path.strip_prefix(root).unwrap_or(&path));
+        let compressible: bool = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(|extension| COMPRESSIBLE_EXTENSIONS.contains(&extension));
+        if compressible && path.metadata()?.len() >= MIN_COMPRESS_SIZE {
+            precompress_file(&path, &target)?;
+        } else {
+            // The file shrank or changed type since the last build
+            for variant in variants(&target) {
+                if variant.exists() {
+                    std::fs::remove_file(&variant)?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn precompress_file(path: &Path, target: &Path) -> std::io::Result<()> {
+    let modified = path.metadata()?.modified()?;
+    let data: Vec<u8> = std::fs::read(path)?;
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    for (variant, compress) in variants(target).into_iter().zip([compress_brotli as fn(&[u8]) -> std::io::Result<Vec<u8>>, compress_gzip]) {
+        // Only recompress when the source is newer than the variant
+        if variant.metadata().and_then(|m| m.modified()).is_ok_and(|variant_modified| variant_modified >= modified) {
+            continue;
+        }
+        let compressed: Vec<u8> = compress(&data)?;
+        if compressed.len() < data.len() {
+            std::fs::write(&variant, compressed)?;
+        } else if variant.exists() {
+            std::fs::remove_file(&variant)?;
+        }
+    }
+
     Ok(())
 }
 
+/// The `.br` and `.gz` paths for `target`, in the order `precompress_file` compresses them
+fn variants(target: &Path) -> [PathBuf; 2] {
+    let file_name = target.file_name().unwrap_or_default().to_string_lossy();
+    ["br", "gz"].map(|suffix| target.with_file_name(format!("{}.{}", file_name, suffix)))
+}
+
+/// Removes variants whose source file is gone from the client build, they would be embedded for nothing
+fn prune_variants(client_build: &Path, out: &Path, dir: &Path) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            prune_variants(client_build, out, &path)?;
+            continue;
+        }
+        let source: PathBuf = client_build.join(path.strip_prefix(out).unwrap_or(&path)).with_extension("");
+        if !source.is_file() {
+            std::fs::remove_file(&path)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn compress_brotli(data: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut writer = brotli::CompressorWriter::new(Vec::new(), 4096, 11, 22);
+    writer.write_all(data)?;
+
+    Ok(writer.into_inner())
+}
+
+fn compress_gzip(data: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::best());
+    encoder.write_all(data)?;
+
+    encoder.finish()
+}
+
 #[cfg(not(debug_assertions))]
 fn build_client() -> std::io::Result<()> {
     let node_modules = std::path::Path::new("client/node_modules");
@@ -0,0 +1,39 @@
+// What `/api/version` returns, see `src/server/build_info.rs`
+export interface BuildInfo {
+	version: string;
+	git_commit: string;
+	git_dirty: boolean;
+	build_timestamp: string;
+	rustc_version: string;
+	profile: string;
+	features: string[];
+	client_bundle_hash: string;
+}
+
+// Data the server injects into index.html, see `src/server/bootstrap.rs`
+export interface Bootstrap {
+	state: {
+		app_name: string;
+		app_version: string;
+		counter: number;
+		global_counter: number;
+		build: BuildInfo;
+	};
+	user: { username: string; expiration: string } | null;
+	features: Record<string, boolean>;
+	nonce: string;
+}
+
+// Returns null when the page wasn't served by the backend (e.g. the Vite dev server on its own)
+export function readBootstrap(): Bootstrap | null {
+	const element = document.getElementById('app-bootstrap');
+	if (!element?.textContent) {
+		return null;
+	}
+	try {
+		return JSON.parse(element.textContent) as Bootstrap;
+	} catch (error) {
+		console.error("Invalid bootstrap data:", error);
+		return null;
+	}
+}
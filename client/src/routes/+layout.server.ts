@@ -1,11 +1,12 @@
 import type { ServerLoadEvent } from "@sveltejs/kit";
+import { base } from "$app/paths";
 export const load = async({ fetch }: ServerLoadEvent) => {
   console.log("Loading data from server");
   const environment = import.meta.env.MODE;
   console.log("Environment: ", environment);
   
   try {
-    const response = await fetch(`/api/state`);
+    const response = await fetch(`${base}/api/state`);
     if (!response.ok) {
       throw new Error(`Failed to fetch /api/state: ${response.statusText}`);
     }
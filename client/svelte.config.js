@@ -1,6 +1,9 @@
 import adapter from '@sveltejs/adapter-static';
 import { vitePreprocess } from '@sveltejs/vite-plugin-svelte';
 
+// Same value as the backend's BASE_PATH, e.g. `/cleanroom`. SvelteKit wants a leading slash and no trailing one.
+const basePath = (process.env.BASE_PATH ?? '').trim().replace(/^\/+|\/+$/g, '');
+
 /** @type {import('@sveltejs/kit').Config} */
 const config = {
 	// Consult https://svelte.dev/docs/kit/integrations
@@ -18,7 +21,10 @@ const config = {
 			fallback: 'index.html',
       precompress: false,
 			strict: true
-    })
+    }),
+		paths: {
+			base: basePath ? `/${basePath}` : ''
+		}
 	}
 };
 
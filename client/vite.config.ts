@@ -8,13 +8,16 @@ export default ({ mode }: { mode: string }) => {
   console.log("Vite env: ", env);
   console.log("Vite env mode: ", mode);
   console.log("Vite env PORT: ", env.VITE_PORT);
+  // API requests live below the same BASE_PATH as the pages, see svelte.config.js
+  const basePath = (process.env.BASE_PATH ?? '').trim().replace(/^\/+|\/+$/g, '');
+  const apiPrefix = basePath ? `/${basePath}/api` : '/api';
 	let envSettings = {};
 	if (mode === 'development') {
 		envSettings = {
 			server: {
 				port: 3000,
 				proxy: {
-					'/api': `http://127.0.0.1:${env.VITE_PORT}`,
+					[apiPrefix]: `http://127.0.0.1:${env.VITE_PORT}`,
 				}
 			},
 		};
@@ -23,7 +26,7 @@ export default ({ mode }: { mode: string }) => {
       server: {
         port: 3000,
         proxy: {
-          '/api': `http://127.0.0.1:${env.VITE_PORT}`,
+          [apiPrefix]: `http://127.0.0.1:${env.VITE_PORT}`,
         }
       }
     }
@@ -1,4 +1,4 @@
-use std::sync::atomic::{AtomicBool, AtomicUsize};
+use std::sync::atomic::AtomicBool;
 
 use actix_web::{web, HttpRequest};
 use chrono::NaiveDateTime;
@@ -10,14 +10,19 @@ use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use utoipa::ToSchema;
 
-// Global flag for shutdown coordination
-pub static HEALTH_CHECK_HITS: AtomicUsize = AtomicUsize::new(0);
+pub mod otel;
+pub mod secrets;
+
+use secrets::Secret;
+
+// Maintenance mode, only changed through `server::maintenance` which also keeps its message and end time
 pub static PAUSED: AtomicBool = AtomicBool::new(false);
 
 #[derive(Clone, Debug, Deserialize, FromRow, Serialize, ToSchema)]
 pub struct User {
     pub id: i64,
     pub username: String,
+    #[serde(skip_serializing)]
     pub password_hash: String,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
@@ -26,13 +31,15 @@ pub struct User {
 #[derive(Debug, Deserialize, ToSchema)]
 pub struct CreateUser {
     pub username: String,
-    pub password: String
+    #[schema(value_type = String)]
+    pub password: Secret<String>
 }
 
 #[derive(Debug, Deserialize, ToSchema)]
 pub struct LoginUser {
     pub username: String,
-    pub password: String,
+    #[schema(value_type = String)]
+    pub password: Secret<String>,
     // pub token: Option<String>, // Optional token for authentication
 }
 
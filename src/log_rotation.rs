@@ -0,0 +1,409 @@
+use chrono::Utc;
+use flate2::{write::GzEncoder, Compression};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use crate::server::settings::{LogInterval, LoggingSettings};
+
+/// Which files an appender owns and how long it keeps them
+#[derive(Clone)]
+struct Retention {
+    directory: PathBuf,
+    /// Start of every file name, empty for the main log of a dated rotation
+    prefix: String,
+    compress: bool,
+    max_files: Option<usize>,
+    max_age: Option<Duration>,
+    /// Rotations can follow each other faster than the clean up of the previous one
+    cleaning: Arc<Mutex<()>>,
+}
+
+/// #### Rolling Log File
+/// Writer for `tracing_appender::non_blocking` that starts a new file every interval and whenever
+/// the current one would grow past the size limit. Rotated files are gzipped and pruned by count
+/// and age on a separate thread, so logging never waits for it.
+///
+/// Names are `[prefix.]<period>[.<n>].log`, e.g. `2026-10-19.log`, `2026-10-19.1.log.gz` or `access.2026-10-19-14.log`.
+pub struct RollingLogFile {
+    retention: Retention,
+    interval: LogInterval,
+    max_bytes: Option<u64>,
+    period: String,
+    index: u32,
+    file: File,
+    written: u64,
+}
+
+impl RollingLogFile {
+    pub fn new(settings: &LoggingSettings, prefix: &str) -> io::Result<Self> {
+        let retention: Retention = Retention::new(settings, prefix);
+        let period: String = period(settings.rotation);
+        // Continue the newest file of the current period, a restart shouldn't start a new one
+        let mut index: u32 = 0;
+        while retention.taken(&period, index + 1) {
+            index += 1;
+        }
+        if !retention.path(&period, index).exists() && retention.taken(&period, index) {
+            index += 1;
+        }
+        let (file, written) = open(&retention.path(&period, index))?;
+
+        let rolling: Self = Self {
+            retention,
+            interval: settings.rotation,
+            max_bytes: settings.max_file_bytes,
+            period,
+            index,
+            file,
+            written,
+        };
+        // Files left by earlier runs, e.g. before compression or a lower limit was configured
+        rolling.clean_up();
+
+        Ok(rolling)
+    }
+
+    fn rotate(&mut self, period: String, index: u32) -> io::Result<()> {
+        self.file.flush()?;
+        let (file, written) = open(&self.retention.path(&period, index))?;
+        self.file = file;
+        self.written = written;
+        self.period = period;
+        self.index = index;
+        self.clean_up();
+
+        Ok(())
+    }
+
+    fn clean_up(&self) {
+        let retention: Retention = self.retention.clone();
+        let current: PathBuf = self.retention.path(&self.period, self.index);
+        std::thread::spawn(move || {
+            let _guard = retention.cleaning.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            if let Err(e) = retention.clean_up(&current) {
+                tracing::event!(target: "backend", tracing::Level::WARN, "Failed to clean up the log files in {}: {}", retention.directory.display(), e);
+            }
+        });
+    }
+}
+
+impl Write for RollingLogFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let period: String = period(self.interval);
+        if period != self.period {
+            self.rotate(period, 0)?;
+        } else if self.max_bytes.is_some_and(|max_bytes| self.written > 0 && self.written + buf.len() as u64 > max_bytes) {
+            let mut index: u32 = self.index + 1;
+            while self.retention.taken(&self.period, index) {
+                index += 1;
+            }
+            self.rotate(self.period.clone(), index)?;
+        }
+
+        // One call per event, so events are never split across files
+        self.file.write_all(buf)?;
+        self.written += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// The files of the log written with `prefix`, plain and gzipped, newest first. An empty prefix
+/// is the main log, otherwise the name of a target file.
+pub fn log_files(settings: &LoggingSettings, prefix: &str) -> io::Result<Vec<(PathBuf, SystemTime)>> {
+    Retention::new(settings, prefix).owned_files()
+}
+
+impl Retention {
+    fn new(settings: &LoggingSettings, prefix: &str) -> Self {
+        // Without a period in the name the files still need a stem
+        let prefix: String = match (prefix, settings.rotation) {
+            ("", LogInterval::Never) => "app".to_string(),
+            (prefix, _) => prefix.to_string(),
+        };
+
+        Self {
+            directory: settings.directory.clone(),
+            prefix,
+            compress: settings.compress,
+            max_files: settings.max_files,
+            max_age: settings.max_age,
+            cleaning: Arc::new(Mutex::new(())),
+        }
+    }
+
+    fn path(&self, period: &str, index: u32) -> PathBuf {
+        let index: String = index.to_string();
+        let parts: Vec<&str> = [self.prefix.as_str(), period, if index == "0" { "" } else { &index }]
+            .into_iter()
+            .filter(|part| !part.is_empty())
+            .collect();
+        self.directory.join(format!("{}.log", parts.join(".")))
+    }
+
+    /// Already used, in plain or gzipped form
+    fn taken(&self, period: &str, index: u32) -> bool {
+        let path: PathBuf = self.path(period, index);
+        path.exists() || gzipped(&path).exists()
+    }
+
+    /// Whether the file was written by an appender with this prefix, the main log and the target
+    /// files share the folder
+    fn owns(&self, name: &str) -> bool {
+        let rest: &str = match self.prefix.as_str() {
+            "" => name,
+            prefix => match name.strip_prefix(prefix) {
+                Some(rest) if rest.starts_with('.') => rest,
+                _ => return false,
+            },
+        };
+        let rest: &str = rest.strip_suffix(".gz").unwrap_or(rest);
+        let Some(stem) = rest.strip_suffix(".log") else {
+            return false;
+        };
+        let stem: &str = stem.strip_prefix('.').unwrap_or(stem);
+        // `app.log` of a rotation without periods, otherwise a period and an optional index
+        stem.is_empty() || (stem.starts_with(|c: char| c.is_ascii_digit()) && stem.chars().all(|c| c.is_ascii_digit() || c == '-' || c == '.'))
+    }
+
+    fn owned_files(&self) -> io::Result<Vec<(PathBuf, SystemTime)>> {
+        let mut files: Vec<(PathBuf, SystemTime)> = Vec::new();
+        for entry in fs::read_dir(&self.directory)? {
+            let entry: fs::DirEntry = entry?;
+            let owned: bool = entry.file_name().to_str().is_some_and(|name| self.owns(name));
+            if owned && entry.file_type()?.is_file() {
+                files.push((entry.path(), entry.metadata()?.modified()?));
+            }
+        }
+        // Newest first
+        files.sort_by_key(|(_, modified)| std::cmp::Reverse(*modified));
+
+        Ok(files)
+    }
+
+    fn clean_up(&self, current: &Path) -> io::Result<()> {
+        if self.compress {
+            for (path, _) in self.owned_files()? {
+                if path != current && path.extension().is_some_and(|extension| extension == "log") {
+                    compress(&path)?;
+                }
+            }
+        }
+
+        let now: SystemTime = SystemTime::now();
+        let rotated = self.owned_files()?.into_iter().filter(|(path, _)| path != current);
+        for (rank, (path, modified)) in rotated.enumerate() {
+            // The current file counts towards the maximum
+            let too_many: bool = self.max_files.is_some_and(|max_files| rank + 1 >= max_files);
+            let too_old: bool = self.max_age.is_some_and(|max_age| now.duration_since(modified).unwrap_or_default() > max_age);
+            if too_many || too_old {
+                fs::remove_file(&path)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn period(interval: LogInterval) -> String {
+    let format: &str = match interval {
+        LogInterval::Minutely => "%Y-%m-%d-%H-%M",
+        LogInterval::Hourly => "%Y-%m-%d-%H",
+        LogInterval::Daily => "%Y-%m-%d",
+        LogInterval::Never => return String::new(),
+    };
+
+    Utc::now().format(format).to_string()
+}
+
+fn open(path: &Path) -> io::Result<(File, u64)> {
+    let file: File = OpenOptions::new().create(true).append(true).open(path)?;
+    let written: u64 = file.metadata()?.len();
+
+    Ok((file, written))
+}
+
+fn gzipped(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(".gz");
+    PathBuf::from(name)
+}
+
+/// Replaces `path` with `path.gz`, the original is only removed once the archive is complete
+fn compress(path: &Path) -> io::Result<()> {
+    let target: PathBuf = gzipped(path);
+    let mut source: File = File::open(path)?;
+    // Pruning goes by age, the archive keeps the time of the last write to the log
+    let modified: SystemTime = source.metadata()?.modified()?;
+    let mut encoder: GzEncoder<File> = GzEncoder::new(File::create(&target)?, Compression::default());
+    io::copy(&mut source, &mut encoder)?;
+    let archive: File = encoder.finish()?;
+    archive.set_modified(modified)?;
+    archive.sync_all()?;
+    fs::remove_file(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Log folder for one test, removed when dropped
+    struct LogDir(PathBuf);
+
+    impl LogDir {
+        fn new() -> Self {
+            let dir: PathBuf = std::env::temp_dir().join(format!("log-rotation-test-{}", uuid::Uuid::new_v4().simple()));
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+
+        /// Without periods in the names, so the tests don't depend on the clock
+        fn settings(&self) -> LoggingSettings {
+            LoggingSettings {
+                directory: self.0.clone(),
+                rotation: LogInterval::Never,
+                max_file_bytes: None,
+                max_files: None,
+                max_age: None,
+                compress: false,
+                ..LoggingSettings::from_env()
+            }
+        }
+
+        /// Writes a file last modified `age` ago
+        fn file(&self, name: &str, age: Duration) -> PathBuf {
+            let path: PathBuf = self.0.join(name);
+            let file: File = File::create(&path).unwrap();
+            file.set_modified(SystemTime::now() - age).unwrap();
+            path
+        }
+
+        fn names(&self) -> Vec<String> {
+            let mut names: Vec<String> = fs::read_dir(&self.0)
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+                .collect();
+            names.sort();
+            names
+        }
+
+        fn read(&self, name: &str) -> String {
+            fs::read_to_string(self.0.join(name)).unwrap()
+        }
+    }
+
+    impl Drop for LogDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn rotates_before_passing_the_size_limit() {
+        let dir: LogDir = LogDir::new();
+        let mut log: RollingLogFile = RollingLogFile::new(&LoggingSettings { max_file_bytes: Some(11), ..dir.settings() }, "").unwrap();
+        log.write_all(b"first\n").unwrap();
+        log.write_all(b"four\n").unwrap();
+        log.write_all(b"second\n").unwrap();
+        // An event larger than the limit still goes into a file of its own
+        log.write_all(b"a much longer event\n").unwrap();
+        log.flush().unwrap();
+
+        assert_eq!(dir.names(), ["app.1.log", "app.2.log", "app.log"]);
+        assert_eq!(dir.read("app.log"), "first\nfour\n");
+        assert_eq!(dir.read("app.1.log"), "second\n");
+        assert_eq!(dir.read("app.2.log"), "a much longer event\n");
+    }
+
+    #[test]
+    fn continues_the_newest_file() {
+        let dir: LogDir = LogDir::new();
+        dir.file("app.log", Duration::ZERO);
+        fs::write(dir.0.join("app.1.log"), "earlier\n").unwrap();
+        let mut log: RollingLogFile = RollingLogFile::new(&dir.settings(), "").unwrap();
+        log.write_all(b"later\n").unwrap();
+        log.flush().unwrap();
+
+        assert_eq!(dir.read("app.1.log"), "earlier\nlater\n");
+    }
+
+    #[test]
+    fn skips_a_compressed_newest_file() {
+        let dir: LogDir = LogDir::new();
+        dir.file("app.log", Duration::ZERO);
+        dir.file("app.1.log.gz", Duration::ZERO);
+        dir.file("app.2.log.gz", Duration::ZERO);
+        let mut log: RollingLogFile = RollingLogFile::new(&dir.settings(), "").unwrap();
+        log.write_all(b"next\n").unwrap();
+        log.flush().unwrap();
+
+        assert_eq!(dir.read("app.3.log"), "next\n");
+    }
+
+    #[test]
+    fn owns_only_its_own_files() {
+        let dir: LogDir = LogDir::new();
+        let main: Retention = Retention::new(&LoggingSettings { rotation: LogInterval::Daily, ..dir.settings() }, "");
+        assert!(main.owns("2026-10-19.log"));
+        assert!(main.owns("2026-10-19.1.log"));
+        assert!(main.owns("2026-10-19.1.log.gz"));
+        assert!(!main.owns("access.2026-10-19.log"));
+        assert!(!main.owns("2026-10-19.txt"));
+        assert!(!main.owns("notes.log"));
+
+        let access: Retention = Retention::new(&LoggingSettings { rotation: LogInterval::Hourly, ..dir.settings() }, "access");
+        assert!(access.owns("access.2026-10-19-14.log"));
+        assert!(access.owns("access.2026-10-19-14.2.log.gz"));
+        assert!(!access.owns("accesses.2026-10-19-14.log"));
+        assert!(!access.owns("2026-10-19-14.log"));
+        assert!(!access.owns("access.notes.log"));
+    }
+
+    #[test]
+    fn prunes_by_count() {
+        let dir: LogDir = LogDir::new();
+        let current: PathBuf = dir.file("app.3.log", Duration::ZERO);
+        dir.file("app.2.log", Duration::from_secs(60));
+        dir.file("app.1.log.gz", Duration::from_secs(120));
+        dir.file("app.log", Duration::from_secs(180));
+        dir.file("access.log", Duration::from_secs(240));
+        let retention: Retention = Retention::new(&LoggingSettings { max_files: Some(2), ..dir.settings() }, "");
+        retention.clean_up(&current).unwrap();
+
+        assert_eq!(dir.names(), ["access.log", "app.2.log", "app.3.log"]);
+    }
+
+    #[test]
+    fn prunes_by_age() {
+        let dir: LogDir = LogDir::new();
+        // Even an old current file is kept, it is still being written
+        let current: PathBuf = dir.file("app.2.log", Duration::from_secs(7200));
+        dir.file("app.1.log", Duration::from_secs(600));
+        dir.file("app.log", Duration::from_secs(7200));
+        let retention: Retention = Retention::new(&LoggingSettings { max_age: Some(Duration::from_secs(3600)), ..dir.settings() }, "");
+        retention.clean_up(&current).unwrap();
+
+        assert_eq!(dir.names(), ["app.1.log", "app.2.log"]);
+    }
+
+    #[test]
+    fn compresses_rotated_files() {
+        let dir: LogDir = LogDir::new();
+        let current: PathBuf = dir.file("app.1.log", Duration::ZERO);
+        let rotated: PathBuf = dir.file("app.log", Duration::from_secs(600));
+        let modified: SystemTime = fs::metadata(&rotated).unwrap().modified().unwrap();
+        let retention: Retention = Retention::new(&LoggingSettings { compress: true, ..dir.settings() }, "");
+        retention.clean_up(&current).unwrap();
+
+        assert_eq!(dir.names(), ["app.1.log", "app.log.gz"]);
+        assert_eq!(fs::metadata(dir.0.join("app.log.gz")).unwrap().modified().unwrap(), modified);
+    }
+}
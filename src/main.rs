@@ -1,30 +1,50 @@
+pub mod log_rotation;
 pub mod server;
 pub mod telemetry;
 
 use std::sync::atomic::{AtomicBool, AtomicUsize};
 
-use tracing_appender::non_blocking::WorkerGuard;
+use telemetry::LogGuards;
 
 
 #[tokio::main]
 async fn main() -> std::io::Result<()> {
+    // Before .env fills the environment, so reloads can tell its values from real ones
+    server::reload::capture_process_env();
     // Load the .env file
     dotenvy::dotenv().expect("Error loading env file");
+
+    // `actix-svelte seal-secrets <plain .env file> <output file>` writes an encrypted SECRETS_FILE
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("seal-secrets") {
+        return seal_secrets(&args[2..]);
+    }
+
     // Initialize the logging system first thing
-    let _log_guard: WorkerGuard = telemetry::setup_logging().expect("Failed to set up logging");
+    let logging: server::settings::LoggingSettings = server::settings::LoggingSettings::from_env();
+    let _log_guards: LogGuards = telemetry::setup_logging(&logging).expect("Failed to set up logging");
     tracing::info!("Logging initialized successfully");
 
-    let hostname: String = dotenvy::var("HOST").expect("HOST must be set");
-    let port: u16 = dotenvy::var("ASPNETCORE_PORT")
-        .or_else(|_| dotenvy::var("PORT"))
-        .unwrap_or_else(|_| "5000".to_string()) // Default to 5000 if nothing is set
-        .parse::<u16>()
-        .expect("PORT must be a number");
+    let settings: server::settings::Settings = server::settings::Settings::from_env();
 
-    let application: server::Application = server::Application::build(hostname, port, None).await?;
-    tracing::event!(target: "backend", tracing::Level::INFO, "Listening on http://127.0.0.1:{}/", application.port());
+    let application: server::Application = server::Application::build(settings, None).await?;
+    tracing::event!(target: "backend", tracing::Level::INFO, "Listening on {}://{}:{}/", application.scheme(), application.hostname(), application.port());
 
     application.run_until_stopped().await?;
 
     Ok(())
-}
\ No newline at end of file
+}
+
+fn seal_secrets(args: &[String]) -> std::io::Result<()> {
+    let [input, output] = args else {
+        eprintln!("Usage: actix-svelte seal-secrets <plain .env file> <output file>");
+        std::process::exit(2);
+    };
+    let key = actix_svelte::secrets::secret_var("SECRETS_KEY").expect("SECRETS_KEY or SECRETS_KEY_FILE must be set");
+    let plaintext: Vec<u8> = std::fs::read(input)?;
+    let sealed: Vec<u8> = actix_svelte::secrets::seal_secrets(&plaintext, &key).map_err(std::io::Error::other)?;
+    std::fs::write(output, sealed)?;
+    println!("Encrypted secrets written to {}, point SECRETS_FILE at it", output);
+
+    Ok(())
+}
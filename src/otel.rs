@@ -0,0 +1,103 @@
+use actix_web::http::header::HeaderMap;
+use opentelemetry::{
+    propagation::{Extractor, TextMapPropagator},
+    trace::TracerProvider,
+    Context, KeyValue,
+};
+use opentelemetry_otlp::{ExporterBuildError, SpanExporter as OtlpSpanExporter, WithExportConfig};
+use opentelemetry_sdk::{
+    propagation::TraceContextPropagator,
+    trace::{SdkTracer, SdkTracerProvider, SpanExporter},
+    Resource,
+};
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetryLayer;
+use tracing_subscriber::registry::LookupSpan;
+
+/// Name of the instrumentation scope on every exported span
+const TRACER_NAME: &str = "actix-svelte";
+
+/// `service.name` and `service.version` of the exported spans, anything in `OTEL_RESOURCE_ATTRIBUTES`
+/// is added, e.g. `deployment.environment=plant-2,host.name=line-3`
+pub fn resource(service_name: &str, service_version: &str) -> Resource {
+    Resource::builder()
+        .with_service_name(service_name.to_string())
+        .with_attribute(KeyValue::new("service.version", service_version.to_string()))
+        .build()
+}
+
+/// OTLP over HTTP with protobuf, `endpoint` is the full traces URL, e.g. `http://localhost:4318/v1/traces`.
+/// Headers and timeouts come from the usual `OTEL_EXPORTER_OTLP_*` variables.
+pub fn otlp_exporter(endpoint: &str) -> Result<OtlpSpanExporter, ExporterBuildError> {
+    OtlpSpanExporter::builder().with_http().with_endpoint(endpoint).build()
+}
+
+/// Spans are exported in batches from a background thread, so requests never wait for the collector.
+/// Tests pass an in-memory exporter instead of `otlp_exporter`.
+pub fn tracer_provider(exporter: impl SpanExporter + 'static, resource: Resource) -> SdkTracerProvider {
+    SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(resource)
+        .build()
+}
+
+/// Layer that turns `tracing` spans into OpenTelemetry spans for `provider`
+pub fn layer<S>(provider: &SdkTracerProvider) -> OpenTelemetryLayer<S, SdkTracer>
+where
+    S: tracing::Subscriber + for<'span> LookupSpan<'span>,
+{
+    tracing_opentelemetry::layer().with_tracer(provider.tracer(TRACER_NAME))
+}
+
+/// Flushes the spans still waiting for export when dropped, keep it alive until the process exits
+pub struct TracerGuard(SdkTracerProvider);
+
+impl TracerGuard {
+    pub fn new(provider: SdkTracerProvider) -> Self {
+        Self(provider)
+    }
+}
+
+impl Drop for TracerGuard {
+    fn drop(&mut self) {
+        if let Err(e) = self.0.shutdown() {
+            eprintln!("Failed to export the last spans: {}", e);
+        }
+    }
+}
+
+/// Reads the W3C `traceparent` and `tracestate` headers
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|name| name.as_str()).collect()
+    }
+}
+
+/// The trace a request belongs to according to its `traceparent` header, an empty context
+/// (so a new trace) without one or when it doesn't parse
+pub fn parent_context(headers: &HeaderMap) -> Context {
+    TraceContextPropagator::new().extract(&HeaderExtractor(headers))
+}
+
+/// Child span for a database query, named like `SELECT users` as the semantic conventions suggest
+pub fn db_span(operation: &str, table: &str) -> Span {
+    tracing::info_span!(
+        "db.query",
+        otel.name = %format!("{} {}", operation, table),
+        otel.kind = "client",
+        db.system.name = "sqlite",
+        db.operation.name = operation,
+        db.collection.name = table,
+    )
+}
+
+/// Child span for hashing or verifying a password, a deliberately slow step worth seeing in a trace
+pub fn password_hash_span(operation: &str) -> Span {
+    tracing::info_span!("argon2", otel.name = %format!("argon2 {}", operation), operation)
+}
@@ -0,0 +1,129 @@
+use chacha20poly1305::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{collections::HashMap, fmt, path::Path, sync::OnceLock};
+
+const REDACTED: &str = "[REDACTED]";
+const NONCE_LEN: usize = 12;
+
+/// #### Secret
+/// Wraps a sensitive value so it can't end up in logs or responses by accident: `Debug`,
+/// `Display` and `Serialize` all print `[REDACTED]`. Call `expose` where the real value is needed.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Secret<T>(T);
+
+impl<T> Secret<T> {
+    pub fn new(value: T) -> Self {
+        Self(value)
+    }
+
+    pub fn expose(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> From<T> for Secret<T> {
+    fn from(value: T) -> Self {
+        Self(value)
+    }
+}
+
+impl<T> fmt::Debug for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl<T> fmt::Display for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl<T> Serialize for Secret<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(REDACTED)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Secret<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        T::deserialize(deserializer).map(Secret)
+    }
+}
+
+/// Reads a secret, looking in order at:
+/// 1. `{KEY}_FILE`, a file holding the value (Docker and Kubernetes secrets)
+/// 2. `{KEY}` in the environment or `.env`
+/// 3. the encrypted secrets file pointed to by `SECRETS_FILE`
+///
+/// Panics when a configured file can't be read, same as a missing required variable.
+pub fn secret_var(key: &str) -> Option<Secret<String>> {
+    if let Ok(path) = dotenvy::var(format!("{}_FILE", key)) {
+        let value: String = std::fs::read_to_string(&path)
+            .unwrap_or_else(|e| panic!("{}_FILE points to an unreadable file ({}): {}", key, path, e));
+        return Some(Secret::new(value.trim_end_matches(['\r', '\n']).to_string()));
+    }
+    if let Ok(value) = dotenvy::var(key) {
+        return Some(Secret::new(value));
+    }
+
+    encrypted_secrets().get(key).cloned()
+}
+
+/// Entries of the encrypted secrets file, decrypted once on first use
+fn encrypted_secrets() -> &'static HashMap<String, Secret<String>> {
+    static SECRETS: OnceLock<HashMap<String, Secret<String>>> = OnceLock::new();
+    SECRETS.get_or_init(|| {
+        let Ok(path) = dotenvy::var("SECRETS_FILE") else {
+            return HashMap::new();
+        };
+        let key: Secret<String> = secret_var("SECRETS_KEY").expect("SECRETS_KEY or SECRETS_KEY_FILE must be set when SECRETS_FILE is used");
+
+        open_secrets_file(Path::new(&path), &key).unwrap_or_else(|e| panic!("Failed to open SECRETS_FILE {}: {}", path, e))
+    })
+}
+
+/// Decrypts a file written by `seal_secrets`. The plaintext uses the `.env` format.
+pub fn open_secrets_file(path: &Path, key: &Secret<String>) -> Result<HashMap<String, Secret<String>>, String> {
+    let sealed: Vec<u8> = std::fs::read(path).map_err(|e| e.to_string())?;
+    let plaintext: Vec<u8> = open_secrets(&sealed, key)?;
+
+    dotenvy::from_read_iter(plaintext.as_slice())
+        .map(|entry| entry.map(|(k, v)| (k, Secret::new(v))).map_err(|e| e.to_string()))
+        .collect()
+}
+
+/// Encrypts `.env` formatted secrets with ChaCha20-Poly1305. The output is the random nonce
+/// followed by the ciphertext. The key is 32 bytes written as 64 hex characters.
+pub fn seal_secrets(plaintext: &[u8], key: &Secret<String>) -> Result<Vec<u8>, String> {
+    let cipher: ChaCha20Poly1305 = cipher(key)?;
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext: Vec<u8> = cipher.encrypt(&nonce, plaintext).map_err(|e| e.to_string())?;
+
+    let mut sealed: Vec<u8> = nonce.to_vec();
+    sealed.extend_from_slice(&ciphertext);
+    Ok(sealed)
+}
+
+pub fn open_secrets(sealed: &[u8], key: &Secret<String>) -> Result<Vec<u8>, String> {
+    if sealed.len() <= NONCE_LEN {
+        return Err("secrets file is too short".to_string());
+    }
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+
+    cipher(key)?
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| "wrong key or corrupted secrets file".to_string())
+}
+
+fn cipher(key: &Secret<String>) -> Result<ChaCha20Poly1305, String> {
+    let key_bytes: Vec<u8> = hex::decode(key.expose().trim()).map_err(|_| "SECRETS_KEY must be hex encoded".to_string())?;
+    if key_bytes.len() != 32 {
+        return Err("SECRETS_KEY must be 32 bytes (64 hex characters)".to_string());
+    }
+
+    Ok(ChaCha20Poly1305::new(Key::from_slice(&key_bytes)))
+}
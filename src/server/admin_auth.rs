@@ -0,0 +1,193 @@
+use actix_web::{
+    body::{EitherBody, MessageBody},
+    dev::{Payload, ServiceRequest, ServiceResponse},
+    http::header,
+    middleware::Next,
+    web::Data,
+    FromRequest, HttpMessage, HttpRequest, HttpResponse,
+};
+use futures::future::{ready, Ready};
+use std::fmt;
+
+use super::{listeners, settings::AdminSettings, AuthenticatedUser};
+
+/// Who got through `admin_only`, kept in the request extensions for the audit logs of the handlers
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AdminIdentity {
+    /// Came in on an admin listener without credentials, anyone who can reach it
+    Listener,
+    /// Sent `ADMIN_TOKEN` as a bearer token
+    Token,
+    /// Signed in as one of `ADMIN_USERS`
+    User(String),
+}
+
+impl AdminIdentity {
+    /// `false` for the bare admin listener, which doesn't tell who is on the other end
+    pub fn is_authenticated(&self) -> bool {
+        !matches!(self, Self::Listener)
+    }
+}
+
+impl fmt::Display for AdminIdentity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Listener => f.write_str("admin listener"),
+            Self::Token => f.write_str("admin token"),
+            Self::User(username) => f.write_str(username),
+        }
+    }
+}
+
+/// Only available behind `admin_only`, anywhere else the extractor fails with a 401
+impl FromRequest for AdminIdentity {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(
+            req.extensions()
+                .get::<AdminIdentity>()
+                .cloned()
+                .ok_or_else(|| actix_web::error::ErrorUnauthorized("Admin credentials required")),
+        )
+    }
+}
+
+/// Guards the admin routes. Lets a request through when it carries `ADMIN_TOKEN`, the session of
+/// one of `ADMIN_USERS` or arrived on an admin listener. Everything else gets a 401 when
+/// credentials are configured and a 404 otherwise, so the routes don't exist for the public.
+pub async fn admin_only(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, actix_web::Error> {
+    if let Some(identity) = identify(req.request()) {
+        req.extensions_mut().insert(identity);
+        return next.call(req).await.map(ServiceResponse::map_into_left_body);
+    }
+
+    tracing::event!(target: "backend", tracing::Level::WARN, "Rejected admin route {} without admin credentials.", req.path());
+    let has_credentials: bool = req.app_data::<Data<AdminSettings>>().is_some_and(|settings| settings.has_credentials());
+    let response: HttpResponse = if has_credentials {
+        HttpResponse::Unauthorized()
+            .insert_header((header::WWW_AUTHENTICATE, r#"Bearer realm="admin""#))
+            .finish()
+    } else {
+        HttpResponse::NotFound().finish()
+    };
+
+    Ok(req.into_response(response).map_into_right_body())
+}
+
+/// Credentials win over the listener, so changes made on an admin listener can still be attributed
+pub fn identify(req: &HttpRequest) -> Option<AdminIdentity> {
+    if let Some(settings) = req.app_data::<Data<AdminSettings>>() {
+        if let Some(token) = &settings.token {
+            if bearer_token(req).is_some_and(|bearer| constant_time_eq(bearer.as_bytes(), token.expose().as_bytes())) {
+                return Some(AdminIdentity::Token);
+            }
+        }
+        // Only looked at when sent, the extractor warns about a missing cookie
+        if !settings.users.is_empty() && req.cookie("auth_token").is_some() {
+            if let Ok(user) = AuthenticatedUser::extract(req).into_inner() {
+                if settings.users.contains(&user.username) {
+                    return Some(AdminIdentity::User(user.username));
+                }
+            }
+        }
+    }
+
+    listeners::on_admin_listener(req).then_some(AdminIdentity::Listener)
+}
+
+pub fn bearer_token(req: &HttpRequest) -> Option<&str> {
+    req.headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim)
+}
+
+/// Compares without returning early so the time taken doesn't reveal how much of the token matched
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |difference, (x, y)| difference | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::listeners::AdminListeners;
+    use actix_web::{
+        http::StatusCode,
+        middleware::from_fn,
+        test::{call_service, init_service, TestRequest},
+        web, App,
+    };
+    use actix_svelte::secrets::Secret;
+
+    /// Address `TestRequest` reports as the local address of the connection
+    const TEST_LOCAL_ADDR: &str = "127.0.0.1:8080";
+
+    async fn status(admin: AdminSettings, admin_addresses: Vec<&str>, request: TestRequest) -> StatusCode {
+        let listeners: AdminListeners = AdminListeners {
+            addresses: admin_addresses.into_iter().map(|address| address.parse().unwrap()).collect(),
+        };
+        let app = init_service(
+            App::new()
+                .app_data(Data::new(admin))
+                .app_data(Data::new(listeners))
+                .service(
+                    web::scope("/admin")
+                        .wrap(from_fn(admin_only))
+                        .route("/ping", web::get().to(|identity: AdminIdentity| async move { identity.to_string() })),
+                ),
+        )
+        .await;
+
+        call_service(&app, request.uri("/admin/ping").to_request()).await.status()
+    }
+
+    fn with_token(token: &str) -> AdminSettings {
+        AdminSettings { token: Some(Secret::new(token.to_string())), users: Vec::new() }
+    }
+
+    #[actix_web::test]
+    async fn hidden_without_admin_listener_or_credentials() {
+        assert_eq!(status(AdminSettings::default(), vec![], TestRequest::get()).await, StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn hidden_on_a_public_listener() {
+        let status: StatusCode = status(AdminSettings::default(), vec!["127.0.0.1:9999"], TestRequest::get()).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn open_on_the_admin_listener() {
+        assert_eq!(status(AdminSettings::default(), vec![TEST_LOCAL_ADDR], TestRequest::get()).await, StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn token_required_when_configured() {
+        assert_eq!(status(with_token("s3cret"), vec![], TestRequest::get()).await, StatusCode::UNAUTHORIZED);
+        let wrong = TestRequest::get().insert_header((header::AUTHORIZATION, "Bearer s3cre"));
+        assert_eq!(status(with_token("s3cret"), vec![], wrong).await, StatusCode::UNAUTHORIZED);
+        let right = TestRequest::get().insert_header((header::AUTHORIZATION, "Bearer s3cret"));
+        assert_eq!(status(with_token("s3cret"), vec![], right).await, StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn unknown_session_is_not_an_admin() {
+        let admin: AdminSettings = AdminSettings { token: None, users: vec!["admin".to_string()] };
+        let request = TestRequest::get().cookie(actix_web::cookie::Cookie::new("auth_token", "v4.public.forged"));
+        assert_eq!(status(admin, vec![], request).await, StatusCode::UNAUTHORIZED);
+    }
+
+    #[test]
+    fn constant_time_eq_compares_whole_values() {
+        assert!(constant_time_eq(b"token", b"token"));
+        assert!(!constant_time_eq(b"token", b"tokem"));
+        assert!(!constant_time_eq(b"token", b"token2"));
+        assert!(!constant_time_eq(b"", b"token"));
+    }
+}
@@ -0,0 +1,172 @@
+use actix_web::{delete, get, post, put, web::{self, Data, Json}, HttpRequest, HttpResponse, Responder};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::server::{
+	admin_auth::AdminIdentity,
+	assets::{self, AssetEntry},
+	log_level::{self, LogFilterChange, LogFilterStatus},
+	reload::{ConfigReloader, ReloadReport},
+	settings::FrontendSettings,
+};
+
+#[utoipa::path(
+	post,
+	path = "/api/admin/reload",
+	responses(
+		(status = 200, description="Settings file re-read, lists applied and restart-required variables", body = ReloadReport),
+		(status = 500, description="Settings could not be reloaded", body = ReloadReport)
+	),
+	tag = "admin",
+)]
+#[post("/reload")]
+pub async fn reload_settings(reloader: Data<ConfigReloader>) -> impl Responder {
+		tracing::event!(target: "backend", tracing::Level::INFO, "Accessing settings reload endpoint.");
+		let report: ReloadReport = reloader.reload("admin endpoint");
+
+		if report.errors.is_empty() {
+				HttpResponse::Ok().json(report)
+		} else {
+				HttpResponse::InternalServerError().json(report)
+		}
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct StaticFilesReport {
+		/// `STATIC_DIR` when a disk override is configured
+		pub static_dir: Option<String>,
+		pub files: Vec<AssetEntry>,
+}
+
+#[utoipa::path(
+	get,
+	path = "/api/admin/static-files",
+	responses(
+		(status = 200, description="Every frontend file and whether it is served from disk or embedded", body = StaticFilesReport),
+	),
+	tag = "admin",
+)]
+#[get("/static-files")]
+pub async fn static_files(frontend: Data<FrontendSettings>) -> actix_web::Result<HttpResponse> {
+		tracing::event!(target: "backend", tracing::Level::INFO, "Accessing static files endpoint.");
+		let settings: Data<FrontendSettings> = frontend.clone();
+		let files: Vec<AssetEntry> = web::block(move || assets::list_assets(&settings)).await?;
+
+		Ok(HttpResponse::Ok().json(StaticFilesReport {
+				static_dir: frontend.static_dir.as_ref().map(|dir| dir.display().to_string()),
+				files,
+		}))
+}
+
+#[utoipa::path(
+	get,
+	path = "/api/admin/log-filter",
+	responses(
+		(status = 200, description="Log filter in effect, `RUST_LOG` and the override set through this API", body = LogFilterStatus),
+	),
+	tag = "admin",
+)]
+#[get("/log-filter")]
+pub async fn get_log_filter() -> impl Responder {
+		HttpResponse::Ok().json(log_level::status())
+}
+
+#[utoipa::path(
+	put,
+	path = "/api/admin/log-filter",
+	request_body = LogFilterChange,
+	responses(
+		(status = 200, description="Directives in effect, until reverted or expired", body = LogFilterStatus),
+		(status = 400, description="The directives don't parse"),
+		(status = 401, description="No ADMIN_TOKEN or session of one of ADMIN_USERS, the admin listener alone doesn't say who made the change"),
+	),
+	security(("bearerAuth" = [])),
+	tag = "admin",
+)]
+#[put("/log-filter")]
+pub async fn set_log_filter(req: HttpRequest, admin: AdminIdentity, change: Json<LogFilterChange>) -> impl Responder {
+		let Some(set_by) = requester(&req, &admin) else {
+				return unattributed();
+		};
+		match log_level::set_override(&change, &set_by) {
+				Ok(status) => HttpResponse::Ok().json(status),
+				Err(e) => HttpResponse::BadRequest().body(format!("Invalid log filter: {}", e)),
+		}
+}
+
+#[utoipa::path(
+	delete,
+	path = "/api/admin/log-filter",
+	responses(
+		(status = 200, description="`RUST_LOG` is in effect again", body = LogFilterStatus),
+		(status = 401, description="No ADMIN_TOKEN or session of one of ADMIN_USERS, the admin listener alone doesn't say who made the change"),
+	),
+	security(("bearerAuth" = [])),
+	tag = "admin",
+)]
+#[delete("/log-filter")]
+pub async fn revert_log_filter(req: HttpRequest, admin: AdminIdentity) -> impl Responder {
+		match requester(&req, &admin) {
+				Some(reverted_by) => HttpResponse::Ok().json(log_level::revert(&reverted_by)),
+				None => unattributed(),
+		}
+}
+
+/// Who to name in the logs: the admin user or token with the client address, `None` when only
+/// the admin listener let the request in
+fn requester(req: &HttpRequest, admin: &AdminIdentity) -> Option<String> {
+		let address: String = req.connection_info().realip_remote_addr().unwrap_or("unknown").to_string();
+		admin.is_authenticated().then(|| format!("{} ({})", admin, address))
+}
+
+fn unattributed() -> HttpResponse {
+		HttpResponse::Unauthorized().body("Changing the log filter needs ADMIN_TOKEN or the session of one of ADMIN_USERS")
+}
+
+#[cfg(test)]
+mod tests {
+		use super::*;
+		use crate::server::{admin_auth, listeners::AdminListeners, settings::AdminSettings};
+		use actix_svelte::secrets::Secret;
+		use actix_web::{
+				http::{header, StatusCode},
+				middleware::from_fn,
+				test::{call_service, init_service, TestRequest},
+				App,
+		};
+
+		/// Reached through the admin listener, `TestRequest` connects to 127.0.0.1:8080
+		async fn status(request: TestRequest) -> StatusCode {
+				let app = init_service(
+						App::new()
+								.app_data(Data::new(AdminSettings { token: Some(Secret::new("t0ken".to_string())), users: Vec::new() }))
+								.app_data(Data::new(AdminListeners { addresses: vec!["127.0.0.1:8080".parse().unwrap()] }))
+								.service(
+										web::scope("/admin")
+												.wrap(from_fn(admin_auth::admin_only))
+												.service(set_log_filter)
+												.service(revert_log_filter),
+								),
+				)
+				.await;
+
+				call_service(&app, request.to_request()).await.status()
+		}
+
+		#[actix_web::test]
+		async fn admin_listener_alone_cannot_change_the_filter() {
+				let put = TestRequest::put().uri("/admin/log-filter").set_json(serde_json::json!({ "directives": "debug" }));
+				assert_eq!(status(put).await, StatusCode::UNAUTHORIZED);
+				assert_eq!(status(TestRequest::delete().uri("/admin/log-filter")).await, StatusCode::UNAUTHORIZED);
+		}
+
+		#[actix_web::test]
+		async fn admin_token_is_named_as_the_requester() {
+				let delete = TestRequest::delete().uri("/admin/log-filter").insert_header((header::AUTHORIZATION, "Bearer t0ken"));
+				assert_eq!(status(delete).await, StatusCode::OK);
+				let req = TestRequest::default().peer_addr("10.0.0.7:51000".parse().unwrap()).to_http_request();
+				assert_eq!(requester(&req, &AdminIdentity::Token).as_deref(), Some("admin token (10.0.0.7)"));
+				assert_eq!(requester(&req, &AdminIdentity::User("ada".to_string())).as_deref(), Some("ada (10.0.0.7)"));
+				assert_eq!(requester(&req, &AdminIdentity::Listener), None);
+		}
+}
@@ -1,6 +1,6 @@
 use actix_web::{cookie::{Cookie, SameSite}, get, post, web::{Data, Json}, HttpResponse, Responder};
 
-use actix_svelte::{CreateUser, LoginUser, User};
+use actix_svelte::{otel, secrets::Secret, CreateUser, LoginUser, User};
 use argon2::{
 		password_hash::{
 				rand_core::OsRng,
@@ -11,8 +11,9 @@ use argon2::{
 use chrono::{DateTime, Duration, Utc};
 use rusty_paseto::prelude::*;
 use rusty_paseto::prelude::PasetoBuilder;
+use tracing::Instrument;
 
-use crate::server::{DatabaseState, SharedState, AuthenticatedUser};
+use crate::server::{metrics::{metrics, LoginResult}, settings::AdminSettings, DatabaseState, SharedState, AuthenticatedUser};
 
 
 #[utoipa::path(
@@ -26,8 +27,16 @@ use crate::server::{DatabaseState, SharedState, AuthenticatedUser};
 	tag = "auth",
 )]
 #[post("register")]
-pub async fn register_user(data: Data<SharedState>, db_pool: Data<DatabaseState>, user: Json<CreateUser>) -> impl Responder {
+pub async fn register_user(data: Data<SharedState>, db_pool: Data<DatabaseState>, admin: Option<Data<AdminSettings>>, user: Json<CreateUser>) -> impl Responder {
 		tracing::event!(target: "backend", tracing::Level::INFO, "Accessing register endpoint.");
+		// ADMIN_USERS grants admin rights by name, a listed name nobody registered yet must not be up for grabs.
+		// Answered like a taken name so the response doesn't reveal who the admins are.
+		if admin.is_some_and(|admin| admin.users.contains(&user.username)) {
+				tracing::event!(target: "backend", tracing::Level::WARN, "Refused to register {}, the name is listed in ADMIN_USERS", user.username);
+				return HttpResponse::InternalServerError()
+						.content_type("application/json")
+						.body(r#"{"error": "Failed to register user"}"#);
+		}
 		// Generate a random salt for hashing the password using today's datetime, username, and a random number
 		// let salt = format!("{}-{}-{}", chrono::Utc::now().timestamp(), user.username, rand::random::<u32>()).as_bytes();
 		let salt: SaltString = SaltString::generate(&mut OsRng);
@@ -35,19 +44,27 @@ pub async fn register_user(data: Data<SharedState>, db_pool: Data<DatabaseState>
 		let argon2: Argon2<'_> = Argon2::default();
 
 		// Hash the password using Argon2
-		let password_hash: String = argon2.hash_password(user.password.as_bytes(), &salt).expect("Failed to hash password").to_string();
+		let password_hash: String = otel::password_hash_span("hash")
+				.in_scope(|| argon2.hash_password(user.password.expose().as_bytes(), &salt))
+				.expect("Failed to hash password")
+				.to_string();
 		// Parse the hashed password into a PasswordHash object
 		let parsed_hash: PasswordHash<'_> = PasswordHash::new(&password_hash).expect("Failed to parse password hash");
-		let result: Result<(), argon2::password_hash::Error> = argon2.verify_password(user.password.as_bytes(), &parsed_hash);
+		let result: Result<(), argon2::password_hash::Error> = otel::password_hash_span("verify")
+				.in_scope(|| argon2.verify_password(user.password.expose().as_bytes(), &parsed_hash));
 		println!("Password verification result: {:?}", result);
 		// Lets insert it into our user table
-		let query_result = sqlx::query!(
-			"INSERT INTO users (username, password_hash) VALUES (?, ?)",
-			user.username,
-			password_hash
-		)
-		.execute(&db_pool.pool)
-		.await;
+		let query_result = match db_pool.acquire().await {
+				Ok(mut connection) => sqlx::query!(
+					"INSERT INTO users (username, password_hash) VALUES (?, ?)",
+					user.username,
+					password_hash
+				)
+				.execute(&mut *connection)
+				.instrument(otel::db_span("INSERT", "users"))
+				.await,
+				Err(e) => Err(e),
+		};
 
 		match query_result {
 				Ok(_) => {
@@ -83,8 +100,8 @@ pub async fn register_user(data: Data<SharedState>, db_pool: Data<DatabaseState>
 						let cookie = Cookie::build("auth_token", token.clone()) // Choose a name for your cookie
 								.http_only(true)
 								.same_site(SameSite::Strict) // Recommended for security against CSRF
-								.path("/") // Adjust the path as needed
-								// .secure(true) // Uncomment if your site is served over HTTPS
+								.path(data.cookie_path().to_string()) // BASE_PATH, or / when served from the root
+								.secure(data.secure_cookies) // Only sent over HTTPS when the server terminates TLS
 								.finish();
 
 						// Then allow the user to login with this token
@@ -93,7 +110,8 @@ pub async fn register_user(data: Data<SharedState>, db_pool: Data<DatabaseState>
 								.cookie(cookie)
 								.content_type("application/json")
 								.json(AuthenticatedUser {
-										token: token.to_string(),
+										token: Secret::new(token),
+										username: user.username.clone(),
 										expiration: expiration,
 								})
 				},
@@ -125,20 +143,25 @@ pub async fn login(data: Data<SharedState>, db_pool: Data<DatabaseState>, user:
 		// Check if the user has a token in their session
 
 		// Fetch the user from the database
-		let query_result = sqlx::query_as!(User, "SELECT * FROM users WHERE username = ?", user.username)
-				.fetch_one(&db_pool.pool)
-				.await;
+		let query_result = match db_pool.acquire().await {
+				Ok(mut connection) => sqlx::query_as!(User, "SELECT * FROM users WHERE username = ?", user.username)
+						.fetch_one(&mut *connection)
+						.instrument(otel::db_span("SELECT", "users"))
+						.await,
+				Err(e) => Err(e),
+		};
 
 		match query_result {
 				Ok(fetched_user) => {
-						let entered_password: &String = &user.password; // Assuming your login input has a password field
+						let entered_password: &String = user.password.expose(); // Assuming your login input has a password field
 						let stored_password_hash: &String = &fetched_user.password_hash;
 
 						// Parse the stored password hash string
 						match PasswordHash::new(stored_password_hash) {
 								Ok(parsed_hash) => {
 										// Verify the entered password against the parsed hash
-										let verification_result: Result<(), argon2::password_hash::Error> = Argon2::default().verify_password(entered_password.as_bytes(), &parsed_hash);
+										let verification_result: Result<(), argon2::password_hash::Error> = otel::password_hash_span("verify")
+												.in_scope(|| Argon2::default().verify_password(entered_password.as_bytes(), &parsed_hash));
 
 										match verification_result {
 												Ok(_) => {
@@ -167,22 +190,25 @@ pub async fn login(data: Data<SharedState>, db_pool: Data<DatabaseState>, user:
 														let cookie = Cookie::build("auth_token", token.clone()) // Choose a name for your cookie
 																.http_only(true)
 																.same_site(SameSite::Strict) // Recommended for security against CSRF
-																.path("/") // Adjust the path as needed
-																// .secure(true) // Uncomment if your site is served over HTTPS
+																.path(data.cookie_path().to_string()) // BASE_PATH, or / when served from the root
+																.secure(data.secure_cookies) // Only sent over HTTPS when the server terminates TLS
 																.finish();
 														
 														// Log the successful login
 														tracing::event!(target: "backend", tracing::Level::INFO, "User logged in successfully: {}", fetched_user.username);
+														metrics().record_login(LoginResult::Success);
 														HttpResponse::Ok()
 																.cookie(cookie)
 																.content_type("application/json")
 																.json(AuthenticatedUser {
-																		token: token.to_string(),
+																		token: Secret::new(token),
+																		username: fetched_user.username.clone(),
 																		expiration: expiration,
 																})
 												},
 												Err(_) => {
 														// Password is incorrect
+														metrics().record_login(LoginResult::Failure);
 														HttpResponse::Unauthorized()
 																.content_type("application/json")
 																.body(r#"{"error": "Invalid username or password"}"#)
@@ -191,6 +217,7 @@ pub async fn login(data: Data<SharedState>, db_pool: Data<DatabaseState>, user:
 								},
 								Err(_) => {
 										// Password is incorrect
+										metrics().record_login(LoginResult::Failure);
 										HttpResponse::Unauthorized()
 												.content_type("application/json")
 												.body(r#"{"error": "Invalid username or password"}"#)
@@ -200,6 +227,10 @@ pub async fn login(data: Data<SharedState>, db_pool: Data<DatabaseState>, user:
 				Err(e) => {
 						// User not found or other error
 						tracing::event!(target: "backend", tracing::Level::ERROR, "Failed to login user: {}", e);
+						metrics().record_login(match e {
+								sqlx::Error::RowNotFound => LoginResult::Failure,
+								_ => LoginResult::Error,
+						});
 						HttpResponse::InternalServerError()
 								.content_type("application/json")
 								.body(r#"{"error": "Failed to login user"}"#)
@@ -240,7 +271,178 @@ pub async fn login(data: Data<SharedState>, db_pool: Data<DatabaseState>, user:
 )]
 #[get("/protected")]
 pub async fn protected(user: AuthenticatedUser) -> impl Responder {
-    tracing::event!(target: "backend", tracing::Level::INFO, "Accessing protected endpoint by user: {}", user.token);
-		println!("Authenticated user token: {:?}", user);
-    HttpResponse::Ok().json(serde_json::json!({ "message": format!("Hello, token: {}!", user.token) }))
-}
\ No newline at end of file
+    tracing::event!(target: "backend", tracing::Level::INFO, "Accessing protected endpoint by user: {}", user.username);
+    HttpResponse::Ok().json(serde_json::json!({ "message": format!("Hello, {}!", user.username) }))
+}
+
+#[cfg(test)]
+mod tests {
+		use crate::server::{api::routes, settings::AdminSettings, AppState, DatabaseState};
+		use actix_web::{
+				body::to_bytes,
+				cookie::Cookie,
+				dev::ServiceResponse,
+				http::StatusCode,
+				test::{call_service, init_service, TestRequest},
+				web::{self, Data},
+				App,
+		};
+		use std::{
+				io::Write,
+				sync::{Arc, Mutex},
+		};
+		use tracing_subscriber::fmt::MakeWriter;
+
+		const PASSWORD: &str = "c0rrect-h0rse-battery-staple";
+
+		/// Collects everything the fmt layer writes so the test can search it
+		#[derive(Clone, Default)]
+		struct CapturedLogs(Arc<Mutex<Vec<u8>>>);
+
+		impl CapturedLogs {
+				fn contents(&self) -> String {
+						String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+				}
+		}
+
+		impl Write for CapturedLogs {
+				fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+						self.0.lock().unwrap().extend_from_slice(buf);
+						Ok(buf.len())
+				}
+
+				fn flush(&mut self) -> std::io::Result<()> {
+						Ok(())
+				}
+		}
+
+		impl<'a> MakeWriter<'a> for CapturedLogs {
+				type Writer = CapturedLogs;
+
+				fn make_writer(&'a self) -> Self::Writer {
+						self.clone()
+				}
+		}
+
+		fn credentials() -> serde_json::Value {
+				serde_json::json!({ "username": "operator", "password": PASSWORD })
+		}
+
+		fn auth_token(response: &ServiceResponse) -> String {
+				response
+						.response()
+						.cookies()
+						.find(|cookie| cookie.name() == "auth_token")
+						.map(|cookie| cookie.value().to_string())
+						.expect("auth_token cookie")
+		}
+
+		async fn body(response: ServiceResponse) -> String {
+				String::from_utf8(to_bytes(response.into_body()).await.unwrap().to_vec()).unwrap()
+		}
+
+		#[actix_web::test]
+		async fn credentials_stay_out_of_responses_and_logs() {
+				let logs: CapturedLogs = CapturedLogs::default();
+				let subscriber = tracing_subscriber::fmt()
+						.with_writer(logs.clone())
+						.with_max_level(tracing::Level::TRACE)
+						.with_ansi(false)
+						.finish();
+				let _guard = tracing::subscriber::set_default(subscriber);
+				let app = init_service(
+						App::new()
+								.app_data(Data::new(AppState::for_tests()))
+								.app_data(Data::new(DatabaseState::in_memory().await))
+								.service(web::scope("/api").configure(routes::auth_services)),
+				)
+				.await;
+
+				let registered: ServiceResponse = call_service(&app, TestRequest::post().uri("/api/auth/register").set_json(credentials()).to_request()).await;
+				assert_eq!(registered.status(), StatusCode::OK);
+				let registered_token: String = auth_token(&registered);
+				let registered_body: String = body(registered).await;
+
+				let logged_in: ServiceResponse = call_service(&app, TestRequest::post().uri("/api/auth/login").set_json(credentials()).to_request()).await;
+				assert_eq!(logged_in.status(), StatusCode::OK);
+				let token: String = auth_token(&logged_in);
+				let login_body: String = body(logged_in).await;
+
+				let protected: ServiceResponse = call_service(
+						&app,
+						TestRequest::get().uri("/api/auth/protected").cookie(Cookie::new("auth_token", token.clone())).to_request(),
+				)
+				.await;
+				assert_eq!(protected.status(), StatusCode::OK);
+				let protected_body: String = body(protected).await;
+
+				assert!(login_body.contains("operator"));
+				for (name, body) in [("register", &registered_body), ("login", &login_body), ("protected", &protected_body)] {
+						assert!(!body.contains(PASSWORD), "{} response leaked the password: {}", name, body);
+						assert!(!body.contains(&registered_token) && !body.contains(&token), "{} response leaked the token: {}", name, body);
+				}
+
+				let logs: String = logs.contents();
+				assert!(logs.contains("Token for user operator"), "the extractor did not log: {}", logs);
+				assert!(!logs.contains(PASSWORD), "logs leaked the password: {}", logs);
+				assert!(!logs.contains(&registered_token) && !logs.contains(&token), "logs leaked the token: {}", logs);
+		}
+
+		#[actix_web::test]
+		async fn rejected_token_stays_out_of_logs() {
+				let logs: CapturedLogs = CapturedLogs::default();
+				let subscriber = tracing_subscriber::fmt()
+						.with_writer(logs.clone())
+						.with_max_level(tracing::Level::TRACE)
+						.with_ansi(false)
+						.finish();
+				let _guard = tracing::subscriber::set_default(subscriber);
+				let app = init_service(
+						App::new()
+								.app_data(Data::new(AppState::for_tests()))
+								.app_data(Data::new(DatabaseState::in_memory().await))
+								.service(web::scope("/api").configure(routes::auth_services)),
+				)
+				.await;
+				let registered: ServiceResponse = call_service(&app, TestRequest::post().uri("/api/auth/register").set_json(credentials()).to_request()).await;
+				// Same claims under a broken signature
+				let mut forged: String = auth_token(&registered);
+				let position: usize = forged.len() - 10;
+				let replacement: &str = if &forged[position..position + 1] == "A" { "B" } else { "A" };
+				forged.replace_range(position..position + 1, replacement);
+
+				let response: ServiceResponse = call_service(
+						&app,
+						TestRequest::get().uri("/api/auth/protected").cookie(Cookie::new("auth_token", forged.clone())).to_request(),
+				)
+				.await;
+				assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+				assert!(!body(response).await.contains(&forged));
+
+				let logs: String = logs.contents();
+				assert!(logs.contains("Rejected auth_token cookie"), "the extractor did not log: {}", logs);
+				assert!(!logs.contains(&forged), "logs leaked the token: {}", logs);
+		}
+
+		#[actix_web::test]
+		async fn admin_names_cannot_be_registered() {
+				let app = init_service(
+						App::new()
+								.app_data(Data::new(AppState::for_tests()))
+								.app_data(Data::new(DatabaseState::in_memory().await))
+								.app_data(Data::new(AdminSettings { token: None, users: vec!["operator".to_string()] }))
+								.service(web::scope("/api").configure(routes::auth_services)),
+				)
+				.await;
+
+				let registered: ServiceResponse = call_service(&app, TestRequest::post().uri("/api/auth/register").set_json(credentials()).to_request()).await;
+				assert_eq!(registered.status(), StatusCode::INTERNAL_SERVER_ERROR);
+				assert!(registered.response().cookies().next().is_none());
+				let logged_in: ServiceResponse = call_service(&app, TestRequest::post().uri("/api/auth/login").set_json(credentials()).to_request()).await;
+				assert_ne!(logged_in.status(), StatusCode::OK);
+
+				let other = serde_json::json!({ "username": "visitor", "password": PASSWORD });
+				let registered: ServiceResponse = call_service(&app, TestRequest::post().uri("/api/auth/register").set_json(other).to_request()).await;
+				assert_eq!(registered.status(), StatusCode::OK);
+		}
+}
@@ -0,0 +1,88 @@
+use actix_web::{delete, get, post, web::{Data, Json, Path}, HttpResponse, Responder};
+
+use crate::server::chaos::{ChaosRules, FaultRule};
+
+#[utoipa::path(
+	get,
+	path = "/api/admin/chaos/rules",
+	responses(
+		(status = 200, description="Fault injection rules in evaluation order, with their hit counts", body = Vec<FaultRule>),
+	),
+	tag = "admin",
+)]
+#[get("/rules")]
+pub async fn list_rules(chaos: Data<ChaosRules>) -> impl Responder {
+		HttpResponse::Ok().json(chaos.list())
+}
+
+#[utoipa::path(
+	post,
+	path = "/api/admin/chaos/rules",
+	request_body = FaultRule,
+	responses(
+		(status = 201, description="Rule added after the existing ones", body = FaultRule),
+		(status = 400, description="The rule could never fire or has an invalid status"),
+	),
+	tag = "admin",
+)]
+#[post("/rules")]
+pub async fn add_rule(chaos: Data<ChaosRules>, rule: Json<FaultRule>) -> impl Responder {
+		if let Err(e) = rule.validate() {
+				return HttpResponse::BadRequest().body(e);
+		}
+		let rule: FaultRule = chaos.add(rule.into_inner());
+		tracing::event!(target: "chaos", tracing::Level::WARN, "Added fault injection rule {}: {:?}", rule.id, rule);
+
+		HttpResponse::Created().json(rule)
+}
+
+#[utoipa::path(
+	delete,
+	path = "/api/admin/chaos/rules/{id}",
+	params(("id" = u64, Path, description = "Rule id")),
+	responses(
+		(status = 204, description="Rule removed"),
+		(status = 404, description="No rule with this id"),
+	),
+	tag = "admin",
+)]
+#[delete("/rules/{id}")]
+pub async fn remove_rule(chaos: Data<ChaosRules>, id: Path<u64>) -> impl Responder {
+		if chaos.remove(*id) {
+				tracing::event!(target: "chaos", tracing::Level::INFO, "Removed fault injection rule {}", id);
+				HttpResponse::NoContent().finish()
+		} else {
+				HttpResponse::NotFound().finish()
+		}
+}
+
+#[utoipa::path(
+	delete,
+	path = "/api/admin/chaos/rules",
+	responses(
+		(status = 204, description="Every rule removed"),
+	),
+	tag = "admin",
+)]
+#[delete("/rules")]
+pub async fn clear_rules(chaos: Data<ChaosRules>) -> impl Responder {
+		chaos.clear();
+		tracing::event!(target: "chaos", tracing::Level::INFO, "Removed every fault injection rule");
+
+		HttpResponse::NoContent().finish()
+}
+
+#[utoipa::path(
+	post,
+	path = "/api/admin/chaos/reset",
+	responses(
+		(status = 204, description="Hit counters of every rule set back to zero"),
+	),
+	tag = "admin",
+)]
+#[post("/reset")]
+pub async fn reset_hits(chaos: Data<ChaosRules>) -> impl Responder {
+		chaos.reset_hits();
+
+		HttpResponse::NoContent().finish()
+}
@@ -0,0 +1,86 @@
+use actix_web::{get, web::Data, HttpResponse, Responder};
+
+use crate::server::{
+	health::{CheckStatus, HealthChecks, LivenessReport, ReadinessReport},
+	DatabaseState, SharedState,
+};
+
+#[utoipa::path(
+	get,
+	path = "/api/health/live",
+	responses(
+		(status = 200, description="The process is up", body = LivenessReport),
+	),
+	tag = "health",
+)]
+#[get("/health/live")]
+pub async fn live(data: Data<SharedState>) -> impl Responder {
+		HttpResponse::Ok().json(LivenessReport {
+				status: CheckStatus::Pass,
+				uptime_seconds: data.started_at.elapsed().as_secs(),
+		})
+}
+
+#[utoipa::path(
+	get,
+	path = "/api/health/ready",
+	responses(
+		(status = 200, description="Every check passed, the app can take traffic", body = ReadinessReport),
+		(status = 503, description="At least one check failed, see its detail", body = ReadinessReport),
+	),
+	tag = "health",
+)]
+#[get("/health/ready")]
+pub async fn ready(db: Data<DatabaseState>, checks: Data<HealthChecks>) -> impl Responder {
+		let report: ReadinessReport = checks.readiness(&db.pool).await;
+
+		if report.status == CheckStatus::Pass {
+				HttpResponse::Ok().json(report)
+		} else {
+				let failed: Vec<&str> = report
+						.checks
+						.iter()
+						.filter(|(_, check)| check.status == CheckStatus::Fail)
+						.map(|(name, _)| *name)
+						.collect();
+				tracing::event!(target: "backend", tracing::Level::WARN, "Readiness check failed: {}", failed.join(", "));
+				HttpResponse::ServiceUnavailable().json(report)
+		}
+}
+
+#[cfg(test)]
+mod tests {
+		use super::*;
+		use crate::server::{maintenance::{self, MaintenanceNotice}, settings::HealthSettings};
+		use actix_web::{
+				http::StatusCode,
+				test::{call_service, init_service, read_body_json, TestRequest},
+				App,
+		};
+		use std::time::Duration;
+
+		#[actix_web::test]
+		async fn not_ready_while_paused() {
+				let settings: HealthSettings = HealthSettings { db_timeout: Duration::from_secs(5), min_free_disk_bytes: 0 };
+				let app = init_service(
+						App::new()
+								.app_data(Data::new(DatabaseState::in_memory().await))
+								.app_data(Data::new(HealthChecks::new(&settings, "sqlite::memory:", std::env::temp_dir())))
+								.service(ready),
+				)
+				.await;
+
+				let _paused = maintenance::TEST_LOCK.lock().await;
+				let running = call_service(&app, TestRequest::get().uri("/health/ready").to_request()).await;
+				assert_eq!(running.status(), StatusCode::OK);
+
+				maintenance::pause(MaintenanceNotice::default(), "test");
+				let paused = call_service(&app, TestRequest::get().uri("/health/ready").to_request()).await;
+				maintenance::resume("test");
+				assert_eq!(paused.status(), StatusCode::SERVICE_UNAVAILABLE);
+				let report: serde_json::Value = read_body_json(paused).await;
+				assert_eq!(report["status"], "fail");
+				assert_eq!(report["checks"]["paused"]["status"], "fail");
+				assert_eq!(report["checks"]["database"]["status"], "pass");
+		}
+}
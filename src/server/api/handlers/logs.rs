@@ -0,0 +1,279 @@
+use actix_web::{
+	get,
+	http::header,
+	web::{self, Bytes, Data, Query},
+	HttpRequest, HttpResponse, Responder,
+};
+use chrono::{DateTime, Utc};
+use futures::{stream, StreamExt};
+use serde::Deserialize;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use utoipa::IntoParams;
+
+use crate::server::{
+	log_buffer::{log_buffer, LogRecord, RecordFilter},
+	log_search::{self, LogFileEntry, LogSearch, LogSearchResult},
+	settings::LoggingSettings,
+};
+
+/// Lines returned when a request doesn't ask for a number
+const DEFAULT_LIMIT: usize = 200;
+const MAX_LIMIT: usize = 5_000;
+/// Proxies close idle connections, a comment line every so often keeps the stream open
+const KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct LogQuery {
+		/// Minimum level, `warn` also returns errors
+		level: Option<String>,
+		/// Target prefix, e.g. `actix_svelte::server`
+		target: Option<String>,
+		/// Case insensitive text anywhere in the line
+		q: Option<String>,
+		/// Most recent matching lines returned, 200 by default and at most 5000
+		limit: Option<usize>,
+}
+
+impl LogQuery {
+		fn filter(&self) -> Result<RecordFilter, HttpResponse> {
+				let level: Option<tracing::Level> = match self.level.as_deref().filter(|level| !level.is_empty()) {
+						Some(level) => Some(level.parse().map_err(|_| {
+								HttpResponse::BadRequest().body(format!("Unknown level '{}', expected trace, debug, info, warn or error", level))
+						})?),
+						None => None,
+				};
+
+				Ok(RecordFilter {
+						level,
+						target: self.target.clone().filter(|target| !target.is_empty()),
+						text: self.q.clone().filter(|text| !text.is_empty()),
+				})
+		}
+
+		fn limit(&self) -> usize {
+				self.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT)
+		}
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct LogFileQuery {
+		/// `main` (default) or the name of a target file from `LOG_TARGET_FILES`
+		log: Option<String>,
+		/// RFC 3339, e.g. `2026-10-19T06:00:00Z`
+		from: Option<DateTime<Utc>>,
+		/// RFC 3339, e.g. `2026-10-19T07:00:00Z`
+		to: Option<DateTime<Utc>>,
+}
+
+#[utoipa::path(
+	get,
+	path = "/api/admin/logs/files",
+	responses(
+		(status = 200, description="Files of the main log and the target files, newest first", body = Vec<LogFileEntry>),
+		(status = 401, description="ADMIN_TOKEN or ADMIN_USERS is set and the request carries neither"),
+		(status = 404, description="Not reached through an admin listener and no admin credentials are configured"),
+	),
+	security(("bearerAuth" = [])),
+	tag = "admin",
+)]
+#[get("/logs/files")]
+pub async fn list_log_files(settings: Data<LoggingSettings>) -> actix_web::Result<HttpResponse> {
+		let files: Vec<LogFileEntry> = web::block(move || log_search::list_files(&settings))
+				.await?
+				.map_err(actix_web::error::ErrorInternalServerError)?;
+
+		Ok(HttpResponse::Ok().json(files))
+}
+
+#[utoipa::path(
+	get,
+	path = "/api/admin/logs/search",
+	params(LogQuery, LogFileQuery),
+	responses(
+		(status = 200, description="Matching lines of the log files, oldest first", body = LogSearchResult),
+		(status = 400, description="Unknown level or log"),
+		(status = 401, description="ADMIN_TOKEN or ADMIN_USERS is set and the request carries neither"),
+		(status = 404, description="Not reached through an admin listener and no admin credentials are configured"),
+	),
+	security(("bearerAuth" = [])),
+	tag = "admin",
+)]
+#[get("/logs/search")]
+pub async fn search_logs(settings: Data<LoggingSettings>, query: Query<LogQuery>, files: Query<LogFileQuery>) -> actix_web::Result<HttpResponse> {
+		let filter: RecordFilter = match query.filter() {
+				Ok(filter) => filter,
+				Err(response) => return Ok(response),
+		};
+		let files: LogFileQuery = files.into_inner();
+		let search: LogSearch = LogSearch {
+				log: files.log.filter(|log| !log.is_empty()),
+				from: files.from,
+				to: files.to,
+				filter,
+				limit: query.limit(),
+		};
+
+		Ok(match web::block(move || log_search::search(&settings, &search)).await? {
+				Ok(result) => HttpResponse::Ok().json(result),
+				Err(e) => HttpResponse::BadRequest().body(e),
+		})
+}
+
+#[utoipa::path(
+	get,
+	path = "/api/admin/logs/recent",
+	params(LogQuery),
+	responses(
+		(status = 200, description="Matching lines still in memory, oldest first", body = Vec<LogRecord>),
+		(status = 400, description="Unknown level"),
+		(status = 401, description="ADMIN_TOKEN or ADMIN_USERS is set and the request carries neither"),
+		(status = 404, description="Not reached through an admin listener and no admin credentials are configured"),
+	),
+	security(("bearerAuth" = [])),
+	tag = "admin",
+)]
+#[get("/logs/recent")]
+pub async fn recent_logs(query: Query<LogQuery>) -> impl Responder {
+		let filter: RecordFilter = match query.filter() {
+				Ok(filter) => filter,
+				Err(response) => return response,
+		};
+		let records: Vec<LogRecord> = log_buffer().map(|buffer| buffer.recent(&filter, None, query.limit())).unwrap_or_default();
+
+		HttpResponse::Ok().json(records)
+}
+
+#[utoipa::path(
+	get,
+	path = "/api/admin/logs/stream",
+	params(LogQuery),
+	responses(
+		(status = 200, description="Server-sent events: the latest `limit` matching lines, then every new one as it is logged. Each `log` event carries a LogRecord, `lagged` events the number of lines skipped because the client fell behind.", content_type = "text/event-stream"),
+		(status = 400, description="Unknown level"),
+		(status = 503, description="Logging runs without the in-memory buffer"),
+		(status = 401, description="ADMIN_TOKEN or ADMIN_USERS is set and the request carries neither"),
+		(status = 404, description="Not reached through an admin listener and no admin credentials are configured"),
+	),
+	security(("bearerAuth" = [])),
+	tag = "admin",
+)]
+#[get("/logs/stream")]
+pub async fn stream_logs(req: HttpRequest, query: Query<LogQuery>) -> impl Responder {
+		let filter: RecordFilter = match query.filter() {
+				Ok(filter) => filter,
+				Err(response) => return response,
+		};
+		let Some(buffer) = log_buffer() else {
+				return HttpResponse::ServiceUnavailable().finish();
+		};
+		// Subscribed first, so nothing is logged unseen between the backlog and the live lines
+		let receiver = buffer.subscribe();
+		// A reconnecting EventSource sends the id of the last event it got and continues from there
+		let last_event_id: Option<u64> = req
+				.headers()
+				.get("Last-Event-ID")
+				.and_then(|value| value.to_str().ok())
+				.and_then(|value| value.parse().ok());
+		let backlog: Vec<LogRecord> = buffer.recent(&filter, last_event_id, query.limit());
+		let last_sent: u64 = backlog.last().map(|record| record.seq).or(last_event_id).unwrap_or(0);
+
+		let backlog = stream::iter(backlog).map(|record| Ok::<Bytes, actix_web::Error>(log_event(&record)));
+		let live = stream::unfold((receiver, filter, last_sent), |(mut receiver, filter, mut last_sent)| async move {
+				loop {
+						let event: Bytes = match tokio::time::timeout(KEEP_ALIVE, receiver.recv()).await {
+								Ok(Ok(record)) if record.seq > last_sent && filter.matches(&record) => {
+										last_sent = record.seq;
+										log_event(&record)
+								}
+								Ok(Ok(_)) => continue,
+								Ok(Err(RecvError::Lagged(skipped))) => Bytes::from(format!("event: lagged\ndata: {}\n\n", skipped)),
+								Ok(Err(RecvError::Closed)) => return None,
+								Err(_) => Bytes::from_static(b": keep-alive\n\n"),
+						};
+						return Some((Ok::<Bytes, actix_web::Error>(event), (receiver, filter, last_sent)));
+				}
+		});
+
+		HttpResponse::Ok()
+				.content_type("text/event-stream")
+				.insert_header((header::CACHE_CONTROL, "no-cache"))
+				// Compression would hold lines back until a block is full
+				.insert_header((header::CONTENT_ENCODING, "identity"))
+				// Same for a buffering nginx in front of the app
+				.insert_header(("X-Accel-Buffering", "no"))
+				.streaming(backlog.chain(live))
+}
+
+fn log_event(record: &LogRecord) -> Bytes {
+		let data: String = serde_json::to_string(record).unwrap_or_default();
+		Bytes::from(format!("id: {}\nevent: log\ndata: {}\n\n", record.seq, data))
+}
+
+#[cfg(test)]
+mod tests {
+		use crate::server::{api::routes, listeners::AdminListeners, settings::{AdminSettings, LoggingSettings}};
+		use actix_svelte::secrets::Secret;
+		use actix_web::{
+				http::{header, StatusCode},
+				test::{call_service, init_service, TestRequest},
+				web::{self, Data},
+				App,
+		};
+
+		const LOG_ROUTES: [&str; 4] = ["/api/admin/logs/files", "/api/admin/logs/search", "/api/admin/logs/recent", "/api/admin/logs/stream"];
+
+		/// Status of every log route, the routes are registered like the app does
+		async fn statuses(admin: AdminSettings, admin_addresses: Vec<&str>, authorization: Option<&str>) -> Vec<StatusCode> {
+				let listeners: AdminListeners = AdminListeners {
+						addresses: admin_addresses.into_iter().map(|address| address.parse().unwrap()).collect(),
+				};
+				let app = init_service(
+						App::new()
+								.app_data(Data::new(admin))
+								.app_data(Data::new(listeners))
+								.app_data(Data::new(LoggingSettings { directory: std::env::temp_dir(), ..LoggingSettings::from_env() }))
+								.service(web::scope("/api").configure(routes::admin_services)),
+				)
+				.await;
+
+				let mut statuses: Vec<StatusCode> = Vec::new();
+				for route in LOG_ROUTES {
+						let mut request: TestRequest = TestRequest::get().uri(route);
+						if let Some(authorization) = authorization {
+								request = request.insert_header((header::AUTHORIZATION, authorization));
+						}
+						statuses.push(call_service(&app, request.to_request()).await.status());
+				}
+				statuses
+		}
+
+		fn with_token() -> AdminSettings {
+				AdminSettings { token: Some(Secret::new("t0ken".to_string())), users: Vec::new() }
+		}
+
+		#[actix_web::test]
+		async fn hidden_without_admin_listener_or_credentials() {
+				assert_eq!(statuses(AdminSettings::default(), vec![], None).await, [StatusCode::NOT_FOUND; 4]);
+		}
+
+		#[actix_web::test]
+		async fn hidden_from_public_listeners() {
+				assert_eq!(statuses(AdminSettings::default(), vec!["127.0.0.1:9999"], None).await, [StatusCode::NOT_FOUND; 4]);
+		}
+
+		#[actix_web::test]
+		async fn token_required_when_configured() {
+				assert_eq!(statuses(with_token(), vec![], None).await, [StatusCode::UNAUTHORIZED; 4]);
+				assert_eq!(statuses(with_token(), vec![], Some("Bearer wrong")).await, [StatusCode::UNAUTHORIZED; 4]);
+		}
+
+		#[actix_web::test]
+		async fn served_with_the_token() {
+				let statuses: Vec<StatusCode> = statuses(with_token(), vec![], Some("Bearer t0ken")).await;
+				// The stream needs the buffer of `telemetry::setup_logging`, which tests don't install
+				assert_eq!(statuses, [StatusCode::OK, StatusCode::OK, StatusCode::OK, StatusCode::SERVICE_UNAVAILABLE]);
+		}
+}
@@ -0,0 +1,112 @@
+use actix_web::{get, http::header, web::Data, HttpRequest, HttpResponse, Responder};
+
+use crate::server::{
+	admin_auth::{bearer_token, constant_time_eq, identify},
+	metrics::{metrics, PoolGauges},
+	settings::MetricsSettings,
+	DatabaseState, SharedState,
+};
+
+/// Content type of the Prometheus text exposition format
+const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+#[utoipa::path(
+	get,
+	path = "/metrics",
+	responses(
+		(status = 200, description="Metrics in the Prometheus text format", content_type = "text/plain"),
+		(status = 401, description="METRICS_TOKEN is set and the request carries no matching bearer token"),
+		(status = 404, description="METRICS_TOKEN is not set and the request is neither from an admin nor on an admin listener"),
+	),
+	security(("bearerAuth" = [])),
+	tag = "admin",
+)]
+#[get("/metrics")]
+pub async fn metrics_endpoint(req: HttpRequest, settings: Data<MetricsSettings>, db: Data<DatabaseState>, data: Data<SharedState>) -> impl Responder {
+		if !scrape_allowed(&req, &settings) {
+				return match settings.token {
+						Some(_) => HttpResponse::Unauthorized()
+								.insert_header((header::WWW_AUTHENTICATE, r#"Bearer realm="metrics""#))
+								.finish(),
+						None => HttpResponse::NotFound().finish(),
+				};
+		}
+
+		let pool: PoolGauges = PoolGauges {
+				size: db.pool.size(),
+				idle: db.pool.num_idle(),
+				max: db.pool.options().get_max_connections(),
+		};
+		let counter: i32 = *data.counter.read().await;
+
+		HttpResponse::Ok()
+				.insert_header((header::CACHE_CONTROL, "no-store"))
+				.content_type(PROMETHEUS_CONTENT_TYPE)
+				.body(metrics().render(&pool, counter))
+}
+
+/// Admins, including anything on an admin listener, may always scrape, everyone else needs the
+/// metrics token. Without one the endpoint stays hidden, it is never public by default.
+fn scrape_allowed(req: &HttpRequest, settings: &MetricsSettings) -> bool {
+		identify(req).is_some()
+				|| settings
+						.token
+						.as_ref()
+						.is_some_and(|token| bearer_token(req).is_some_and(|bearer| constant_time_eq(bearer.as_bytes(), token.expose().as_bytes())))
+}
+
+#[cfg(test)]
+mod tests {
+		use super::*;
+		use crate::server::{listeners::AdminListeners, settings::AdminSettings, AppState};
+		use actix_svelte::secrets::Secret;
+		use actix_web::{
+				http::StatusCode,
+				test::{call_service, init_service, TestRequest},
+				App,
+		};
+
+		/// `TestRequest` connects to 127.0.0.1:8080, which is never an admin listener unless listed
+		async fn status(metrics_token: Option<&str>, admin: AdminSettings, admin_addresses: &[&str], request: TestRequest) -> StatusCode {
+				let app = init_service(
+						App::new()
+								.app_data(Data::new(MetricsSettings { token: metrics_token.map(|token| Secret::new(token.to_string())) }))
+								.app_data(Data::new(admin))
+								.app_data(Data::new(AdminListeners { addresses: admin_addresses.iter().map(|address| address.parse().unwrap()).collect() }))
+								.app_data(Data::new(DatabaseState::in_memory().await))
+								.app_data(Data::new(AppState::for_tests()))
+								.service(metrics_endpoint),
+				)
+				.await;
+
+				call_service(&app, request.uri("/metrics").to_request()).await.status()
+		}
+
+		fn bearer(token: &str) -> TestRequest {
+				TestRequest::get().insert_header((header::AUTHORIZATION, format!("Bearer {token}")))
+		}
+
+		#[actix_web::test]
+		async fn hidden_when_nothing_is_configured() {
+				assert_eq!(status(None, AdminSettings::default(), &[], TestRequest::get()).await, StatusCode::NOT_FOUND);
+				assert_eq!(status(None, AdminSettings::default(), &["127.0.0.1:9999"], TestRequest::get()).await, StatusCode::NOT_FOUND);
+		}
+
+		#[actix_web::test]
+		async fn admin_listener_can_scrape() {
+				assert_eq!(status(None, AdminSettings::default(), &["127.0.0.1:8080"], TestRequest::get()).await, StatusCode::OK);
+		}
+
+		#[actix_web::test]
+		async fn admin_token_can_scrape() {
+				let admin: AdminSettings = AdminSettings { token: Some(Secret::new("adm1n".to_string())), users: Vec::new() };
+				assert_eq!(status(None, admin, &[], bearer("adm1n")).await, StatusCode::OK);
+		}
+
+		#[actix_web::test]
+		async fn metrics_token_is_required_when_set() {
+				assert_eq!(status(Some("scr4pe"), AdminSettings::default(), &[], TestRequest::get()).await, StatusCode::UNAUTHORIZED);
+				assert_eq!(status(Some("scr4pe"), AdminSettings::default(), &[], bearer("scr4p")).await, StatusCode::UNAUTHORIZED);
+				assert_eq!(status(Some("scr4pe"), AdminSettings::default(), &[], bearer("scr4pe")).await, StatusCode::OK);
+		}
+}
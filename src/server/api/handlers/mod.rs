@@ -1,20 +1,28 @@
 
 use actix_web::{
-  get, post, web::{Data, Json, Path, Payload}, HttpRequest, HttpResponse, Responder, Result
+  get, post, route, web::{Data, Json, Path, Payload}, HttpRequest, HttpResponse, Responder, Result
 };
 use anyhow::Error;
-use mime_guess;
-use tokio::time::sleep;
-use std::{sync::atomic::Ordering, time::Duration};
 
-use crate::{server::{SerializableAppState, SharedState, StaticFiles}};
-use actix_svelte::{HEALTH_CHECK_HITS, PAUSED};
+use crate::{server::{assets, bootstrap, build_info::{self, BuildInfo}, maintenance::{self, MaintenanceNotice}, settings::FrontendSettings, SerializableAppState, SharedState}};
 
+pub mod admin;
 pub mod auth;
-
-#[tracing::instrument]
-#[get("/{filename:.*}")]
-pub async fn serve_static_files(path: Path<String>) -> Result<HttpResponse> {
+#[cfg(feature = "chaos")]
+pub mod chaos;
+pub mod health;
+pub mod logs;
+pub mod metrics;
+pub mod security;
+
+#[tracing::instrument(skip(req, data, frontend))]
+#[route("/{filename:.*}", method = "GET", method = "HEAD")]
+pub async fn serve_static_files(
+    req: HttpRequest,
+    path: Path<String>,
+    data: Data<SharedState>,
+    frontend: Data<FrontendSettings>,
+) -> Result<HttpResponse> {
     let filename: String = path.into_inner();
     
     // Skip if path starts with 'api'
@@ -29,25 +37,24 @@ pub async fn serve_static_files(path: Path<String>) -> Result<HttpResponse> {
         filename
     };
 
-    match StaticFiles::get(&requested_path) {
-        Some(content) => {
-            let mime_type = mime_guess::from_path(&requested_path)
-                .first_or_octet_stream();
-
-            Ok(HttpResponse::Ok()
-                .content_type(mime_type.as_ref())
-                .body(content.data.into_owned()))
+    // HTML pages get the bootstrap data injected, everything else is served as is
+    if requested_path.ends_with(".html") {
+        if let Some(page) = assets::find(&frontend, &requested_path).await {
+            return Ok(bootstrap::document_response(&req, page, &data, &frontend).await);
         }
-        None => {
-            // Serve index.html for non-existent paths (SPA routing)
-            match StaticFiles::get("index.html") {
-                Some(content) => Ok(HttpResponse::Ok()
-                    .content_type("text/html")
-                    .body(content.data.into_owned())),
-                None => Ok(HttpResponse::NotFound().finish()),
-            }
+    } else if let Some(response) = assets::asset_response(&req, &frontend, &requested_path).await {
+        return Ok(response);
+    }
+
+    // Serve index.html for client side routes (SPA routing), a missing asset is a real 404
+    if assets::is_navigation(&req, &requested_path) {
+        if let Some(page) = assets::find(&frontend, "index.html").await {
+            return Ok(bootstrap::document_response(&req, page, &data, &frontend).await);
         }
     }
+    tracing::event!(target: "backend", tracing::Level::DEBUG, "Static file not found: {}", requested_path);
+
+    Ok(assets::not_found(&frontend).await)
 }
 
 // #[utoipa::path(
@@ -74,16 +81,15 @@ pub async fn serve_static_files(path: Path<String>) -> Result<HttpResponse> {
 #[utoipa::path(
     post,
     path = "/api/pause",
+    request_body(content = Option<MaintenanceNotice>, description = "Message and scheduled end shown to visitors, both optional"),
     responses(
-        (status = 200, description="Service paused successfully"),
+        (status = 200, description="Service paused successfully", body = MaintenanceNotice),
     )
 )]
-#[post("/pause")]
-pub async fn pause_service() -> impl Responder {
-    println!("Pausing service...");
-    PAUSED.store(true, Ordering::SeqCst);
-    tracing::event!(target: "backend", tracing::Level::INFO, "Service has been PAUSED.");
-    HttpResponse::Ok().body("Service paused")
+pub async fn pause_service(notice: Option<Json<MaintenanceNotice>>) -> impl Responder {
+    let notice: MaintenanceNotice = notice.map(Json::into_inner).unwrap_or_default();
+    maintenance::pause(notice.clone(), "the pause endpoint");
+    HttpResponse::Ok().json(notice)
 }
 
 #[utoipa::path(
@@ -93,13 +99,8 @@ pub async fn pause_service() -> impl Responder {
         (status = 200, description="Service unpaused successfully"),
     )
 )]
-#[post("/unpause")]
 pub async fn unpause_service() -> impl Responder {
-    println!("Unpausing service...");
-    PAUSED.store(false, Ordering::SeqCst);
-    // Reset the health check hits counter when unpausing, so you can re-test the retry logic
-    HEALTH_CHECK_HITS.store(0, Ordering::SeqCst);
-    tracing::event!(target: "backend", tracing::Level::INFO, "Service has been UNPAUSED and health check counter reset.");
+    maintenance::resume("the unpause endpoint");
     HttpResponse::Ok().body("Service unpaused")
 }
 
@@ -108,66 +109,19 @@ pub async fn unpause_service() -> impl Responder {
     path = "/api/health",
     responses(
         (status = 200, description="Returns the health status of the application"),
-        (status = 503, description="Service is paused or temporarily unavailable"),
-        (status = 500, description="Internal Server Error for testing retries"),
+        (status = 503, description="Service is paused"),
     )
 )]
 #[get("/health")]
-pub async fn health_check(_data: Data<SharedState>) -> impl Responder {
-    println!("Health check endpoint hit");
+pub async fn health_check() -> impl Responder {
     tracing::event!(target: "backend", tracing::Level::INFO, "Accessing health-check endpoint.");
-    println!("Health check hit count: {}", HEALTH_CHECK_HITS.load(Ordering::SeqCst));
-    // Increment the global health check hit counter
-    let hits: usize = HEALTH_CHECK_HITS.fetch_add(1, Ordering::SeqCst);
-    println!("Health check hit count after increment: {}", hits);
-
-    // Scenario 1: Service is paused
-    if PAUSED.load(Ordering::SeqCst) {
+    // Failures and slow answers for testing client retries come from fault injection rules, see `server::chaos`
+    if maintenance::is_active() {
         tracing::event!(target: "backend", tracing::Level::WARN, "Service is paused, returning 503.");
         return HttpResponse::ServiceUnavailable().body("Service is paused");
     }
 
-    // Scenario 2: Simulate transient errors for the first few requests
-    match hits {
-        0 => { // First hit
-            tracing::event!(target: "backend", tracing::Level::WARN, "First hit: Simulating 503 Service Unavailable.");
-            // Simulate a delay to mimic a slow service
-            println!("Simulating a delay for the first hit...");
-            sleep(Duration::from_secs(1)).await; // Add a small delay to simulate network latency
-            HttpResponse::ServiceUnavailable().body("Service is temporarily unavailable (first hit)")
-        }
-        1 => { // Second hit
-            tracing::event!(target: "backend", tracing::Level::WARN, "Second hit: Simulating 500 Internal Server Error.");
-            // Simulate a delay to mimic a slow service
-            println!("Simulating a delay for the second hit...");
-            sleep(Duration::from_secs(1)).await; // Add a small delay
-            HttpResponse::InternalServerError().body("Internal Server Error (second hit)")
-        }
-        _ => { // Subsequent hits
-            // Scenario 3: Simulate a long-running but successful health check
-            tracing::event!(target: "backend", tracing::Level::INFO, "Simulating long-running health check. Sleeping for 5 secs.");
-            println!("Simulating a long-running health check for subsequent hits...");
-            // Sleep for 5 seconds to simulate a long-running health check
-            sleep(Duration::from_secs(3)).await; // This will trigger the Python timeout if less than 5s
-            HttpResponse::Ok().body("Service is running (after retries)")
-        }
-    }
-}
-
-#[utoipa::path(
-    post,
-    path = "/api/reset-health-hits",
-    responses(
-        (status = 200, description="Health check hits counter reset successfully"),
-    )
-)]
-#[post("/reset-health-hits")]
-pub async fn reset_health_check_hits() -> impl Responder {
-    println!("Resetting health check hits counter...");
-    // Reset the health check hits counter
-    HEALTH_CHECK_HITS.store(0, Ordering::SeqCst);
-    tracing::event!(target: "backend", tracing::Level::INFO, "Health check hits counter reset.");
-    HttpResponse::Ok().body("Health check hits counter reset")
+    HttpResponse::Ok().body("Service is running")
 }
 
 #[utoipa::path(
@@ -182,15 +136,7 @@ pub async fn get_app_state(data: Data<SharedState>) -> impl Responder {
     tracing::event!(target: "backend", tracing::Level::INFO, "Accessing application state endpoint.");
     let json: SerializableAppState<'_> = data.to_serializable().await;
 
-    let pkey: &[u8] = data.private_key.as_ref();
-    let public_key: &[u8] = data.public_key.as_ref();
-    println!("Lets see the app's private key: {}", hex::encode(pkey));
-    println!("Lets see the app's public key: {}", hex::encode(public_key));
-
-    // Print out the HEALTH_CHECK_HITS
-    println!("Health check hits: {}", HEALTH_CHECK_HITS.load(Ordering::SeqCst));
-    // Print out the PAUSED state
-    println!("Service paused state: {}", PAUSED.load(Ordering::SeqCst));
+    tracing::event!(target: "backend", tracing::Level::DEBUG, "Service paused state: {}", maintenance::is_active());
     tracing::event!(target: "backend", tracing::Level::INFO, "Returning application state.");
 
     HttpResponse::Ok()
@@ -198,6 +144,18 @@ pub async fn get_app_state(data: Data<SharedState>) -> impl Responder {
         .body(serde_json::to_string(&json).unwrap())
 }
 
+#[utoipa::path(
+	get,
+	path = "/api/version",
+	responses(
+		(status = 200, description="Git commit, build time, toolchain, features and client bundle of the running binary", body = BuildInfo),
+	)
+)]
+#[get("version")]
+pub async fn version() -> impl Responder {
+    HttpResponse::Ok().json(build_info::build_info())
+}
+
 #[utoipa::path(
 	post,
 	path = "/api/counter",
@@ -277,10 +235,7 @@ pub async fn counter(data: Data<SharedState>) -> impl Responder {
 )]
 #[post("/test_value")]
 pub async fn test_value(body: Json<serde_json::Value>) -> impl Responder {
-    // Simulate some processing
-    sleep(Duration::from_secs(5)).await;
-
-    // Return a simple response
+    // Echoes the body, add a latency rule in `server::chaos` to make it slow
     HttpResponse::Ok()
         .content_type("application/json")
         .body(serde_json::to_string(&body).unwrap())
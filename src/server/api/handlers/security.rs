@@ -0,0 +1,42 @@
+use actix_web::{post, web::Bytes, HttpResponse, Responder};
+
+#[utoipa::path(
+	post,
+	path = "/api/csp-report",
+	request_body(content = serde_json::Value, content_type = "application/csp-report"),
+	responses(
+		(status = 204, description="Violation report received"),
+		(status = 400, description="The body is not a JSON violation report"),
+	)
+)]
+#[post("/csp-report")]
+pub async fn csp_report(body: Bytes) -> impl Responder {
+	// Browsers send `application/csp-report` (report-uri) or `application/reports+json` (Reporting API)
+	let report: serde_json::Value = match serde_json::from_slice(&body) {
+		Ok(report) => report,
+		Err(_) => return HttpResponse::BadRequest().finish(),
+	};
+	let reports: Vec<&serde_json::Value> = match &report {
+		serde_json::Value::Array(reports) => reports.iter().filter_map(|report| report.get("body")).collect(),
+		report => vec![report.get("csp-report").unwrap_or(report)],
+	};
+	for violation in reports {
+		let field = |names: [&str; 2]| {
+			names
+				.iter()
+				.find_map(|name| violation.get(*name).and_then(|value| value.as_str()))
+				.unwrap_or_default()
+				.to_string()
+		};
+		tracing::event!(
+			target: "csp",
+			tracing::Level::WARN,
+			"CSP violation on {}: {} blocked {}",
+			field(["document-uri", "documentURL"]),
+			field(["violated-directive", "effectiveDirective"]),
+			field(["blocked-uri", "blockedURL"])
+		);
+	}
+
+	HttpResponse::NoContent().finish()
+}
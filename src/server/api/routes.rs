@@ -1,17 +1,63 @@
-use actix_web::web::{self, ServiceConfig};
+use actix_web::{middleware::from_fn, web::{self, ServiceConfig}};
 
 use super::handlers;
+use crate::server::admin_auth;
 
 /// #### Base App Services
 /// These services are used for the base application
 pub fn app_services(cfg: &mut ServiceConfig) {
   cfg.service(handlers::get_app_state);
+  cfg.service(handlers::version);
   cfg.service(handlers::health_check);
+  cfg.service(handlers::health::live);
+  cfg.service(handlers::health::ready);
   cfg.service(handlers::counter);
-  cfg.service(handlers::pause_service);
-  cfg.service(handlers::unpause_service);
-  cfg.service(handlers::reset_health_check_hits);
   cfg.service(handlers::test_value);
+  cfg.service(handlers::security::csp_report);
+}
+
+/// #### Admin Services
+/// These services need the admin listener or admin credentials, without either they don't exist (404).
+/// Pause and unpause keep their original paths, everything else lives below `/admin`.
+pub fn admin_services(cfg: &mut ServiceConfig) {
+  cfg.service(
+    web::resource("/pause")
+      .wrap(from_fn(admin_auth::admin_only))
+      .route(web::post().to(handlers::pause_service))
+  );
+  cfg.service(
+    web::resource("/unpause")
+      .wrap(from_fn(admin_auth::admin_only))
+      .route(web::post().to(handlers::unpause_service))
+  );
+  cfg.service(
+    web::scope("/admin")
+      .wrap(from_fn(admin_auth::admin_only))
+      .service(handlers::admin::reload_settings)
+      .service(handlers::admin::static_files)
+      .service(handlers::admin::get_log_filter)
+      .service(handlers::admin::set_log_filter)
+      .service(handlers::admin::revert_log_filter)
+      .service(handlers::logs::list_log_files)
+      .service(handlers::logs::search_logs)
+      .service(handlers::logs::recent_logs)
+      .service(handlers::logs::stream_logs)
+  );
+}
+
+/// #### Fault Injection Services
+/// Only registered when an admin listener or admin credentials can guard them
+#[cfg(feature = "chaos")]
+pub fn chaos_services(cfg: &mut ServiceConfig) {
+  cfg.service(
+    web::scope("/admin/chaos")
+      .wrap(from_fn(admin_auth::admin_only))
+      .service(handlers::chaos::list_rules)
+      .service(handlers::chaos::add_rule)
+      .service(handlers::chaos::clear_rules)
+      .service(handlers::chaos::remove_rule)
+      .service(handlers::chaos::reset_hits)
+  );
 }
 
 /// #### Authentication Services
@@ -25,4 +71,41 @@ pub fn auth_services(cfg: &mut ServiceConfig) {
       // .service(handlers::login_user)
       // .service(handlers::logout_user),
   );
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::server::{listeners::AdminListeners, settings::AdminSettings};
+  use actix_svelte::secrets::Secret;
+  use actix_web::{
+    http::{Method, StatusCode},
+    test::{call_service, init_service, TestRequest},
+    web::Data,
+    App,
+  };
+
+  #[actix_web::test]
+  async fn unknown_api_paths_are_not_found() {
+    let app = init_service(
+      App::new()
+        .app_data(Data::new(AdminSettings { token: Some(Secret::new("t0ken".to_string())), users: Vec::new() }))
+        .app_data(Data::new(AdminListeners { addresses: Vec::new() }))
+        .service(web::scope("/api").configure(auth_services).configure(admin_services)),
+    )
+    .await;
+
+    for (method, path, expected) in [
+      (Method::GET, "/api/nope", StatusCode::NOT_FOUND),
+      (Method::POST, "/api/pausex", StatusCode::NOT_FOUND),
+      (Method::GET, "/api/administrator", StatusCode::NOT_FOUND),
+      (Method::GET, "/api/admin/static-files", StatusCode::UNAUTHORIZED),
+      (Method::GET, "/api/admin/nope", StatusCode::UNAUTHORIZED),
+      (Method::POST, "/api/pause", StatusCode::UNAUTHORIZED),
+      (Method::POST, "/api/unpause", StatusCode::UNAUTHORIZED),
+    ] {
+      let status: StatusCode = call_service(&app, TestRequest::default().method(method.clone()).uri(path).to_request()).await.status();
+      assert_eq!(status, expected, "{} {}", method, path);
+    }
+  }
+}
@@ -1,7 +1,22 @@
 use utoipa::{openapi::security::{ApiKey, ApiKeyValue, SecurityScheme}, Modify, OpenApi};
 use super::handlers::{
-		__path_counter, __path_get_app_state, __path_health_check, __path_pause_service, __path_unpause_service, __path_reset_health_check_hits,
-		auth::{__path_register_user, __path_login, __path_protected}
+		__path_counter, __path_get_app_state, __path_version, __path_health_check, __path_pause_service, __path_unpause_service,
+		admin::{__path_reload_settings, __path_static_files, __path_get_log_filter, __path_set_log_filter, __path_revert_log_filter, StaticFilesReport},
+		auth::{__path_register_user, __path_login, __path_protected},
+		health::{__path_live, __path_ready},
+		logs::{__path_list_log_files, __path_search_logs, __path_recent_logs, __path_stream_logs},
+		metrics::__path_metrics_endpoint,
+		security::__path_csp_report,
+};
+use crate::server::{
+		assets::{AssetEntry, AssetSource},
+		build_info::BuildInfo,
+		health::{CheckResult, CheckStatus, LivenessReport, ReadinessReport},
+		log_buffer::LogRecord,
+		log_level::{LogFilterChange, LogFilterOverride, LogFilterStatus},
+		log_search::{LogFileEntry, LogLine, LogSearchResult},
+		maintenance::MaintenanceNotice,
+		reload::ReloadReport,
 };
 
 #[derive(OpenApi)]
@@ -13,26 +28,80 @@ use super::handlers::{
 		),
 		paths(
 			get_app_state,
+			version,
 			counter,
 			health_check,
+			live,
+			ready,
 			pause_service,
 			unpause_service,
 			register_user,
 			login,
 			protected,
-			reset_health_check_hits,
+			reload_settings,
+			static_files,
+			get_log_filter,
+			set_log_filter,
+			revert_log_filter,
+			list_log_files,
+			search_logs,
+			recent_logs,
+			stream_logs,
+			metrics_endpoint,
+			csp_report,
 		),
 		components(
 			schemas(
+				ReloadReport,
+				BuildInfo,
+				LivenessReport,
+				ReadinessReport,
+				CheckResult,
+				CheckStatus,
+				MaintenanceNotice,
+				StaticFilesReport,
+				AssetEntry,
+				AssetSource,
+				LogFilterStatus,
+				LogFilterOverride,
+				LogFilterChange,
+				LogRecord,
+				LogFileEntry,
+				LogLine,
+				LogSearchResult,
 			)
 		),
 		modifiers(&SecurityAddon),
 		tags(
 			(name="core", description="Operations about core functionality"),
+			(name="admin", description="Operations only served on the admin listeners"),
+			(name="health", description="Liveness and readiness probes"),
 		),
 )]
 pub struct ApiDocumentation;
 
+/// Merged into `ApiDocumentation` when the `chaos` feature is compiled in
+#[cfg(feature = "chaos")]
+#[derive(OpenApi)]
+#[openapi(
+		paths(
+			super::handlers::chaos::list_rules,
+			super::handlers::chaos::add_rule,
+			super::handlers::chaos::remove_rule,
+			super::handlers::chaos::clear_rules,
+			super::handlers::chaos::reset_hits,
+		),
+		components(
+			schemas(
+				crate::server::chaos::FaultRule,
+				crate::server::chaos::Trigger,
+				crate::server::chaos::Fault,
+				crate::server::chaos::LatencyDistribution,
+			)
+		),
+)]
+pub struct ChaosDocumentation;
+
 
 
 struct SecurityAddon;
@@ -0,0 +1,785 @@
+use actix_web::{
+    body::SizedStream,
+    http::{
+        header::{
+            self, Accept, AcceptEncoding, ContentEncoding, ContentRange, ContentRangeSpec, Encoding, EntityTag, HttpDate, IfModifiedSince,
+            IfNoneMatch, IfRange, LastModified,
+        },
+        StatusCode,
+    },
+    mime,
+    web::Bytes,
+    HttpMessage, HttpRequest, HttpResponse, HttpResponseBuilder,
+};
+use futures_util::{
+    future,
+    stream::{self, BoxStream},
+    StreamExt, TryStreamExt,
+};
+use rust_embed::EmbeddedFile;
+use serde::Serialize;
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    fs::Metadata,
+    io::SeekFrom,
+    ops::Range,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio_util::io::ReaderStream;
+use utoipa::ToSchema;
+
+use super::{build_info::build_info, settings::FrontendSettings, PrecompressedFiles, StaticFiles};
+
+/// Vite puts content-hashed build output here, a changed file always gets a new name
+const IMMUTABLE_PREFIX: &str = "_app/immutable/";
+const IMMUTABLE_CACHE_CONTROL: &str = "public, max-age=31536000, immutable";
+/// Everything else may change between deploys, browsers keep it but revalidate with the ETag
+const REVALIDATE_CACHE_CONTROL: &str = "no-cache";
+/// More ranges than this are answered with the whole file, tiny ranges cost more than they save
+const MAX_RANGES: usize = 16;
+
+/// Where a frontend file was found, files in `STATIC_DIR` override the embedded ones
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum AssetSource {
+    Disk,
+    Embedded,
+}
+
+/// Contents of a frontend file. Embedded files are sliced without copying, disk files are only read while the body is sent.
+pub enum AssetData {
+    Memory(Bytes),
+    File(PathBuf),
+}
+
+/// A frontend file ready to be served
+pub struct Asset {
+    pub source: AssetSource,
+    pub data: AssetData,
+    pub len: u64,
+    pub etag: EntityTag,
+    /// Whole seconds like the HTTP date it is sent as, `None` when unknown
+    pub last_modified: Option<SystemTime>,
+}
+
+impl Asset {
+    fn embedded(path: &str) -> Option<Self> {
+        StaticFiles::get(path).map(Self::from_embedded_file)
+    }
+
+    fn precompressed(path: &str) -> Option<Self> {
+        PrecompressedFiles::get(path).map(Self::from_embedded_file)
+    }
+
+    fn from_embedded_file(file: EmbeddedFile) -> Self {
+        Self {
+            source: AssetSource::Embedded,
+            etag: etag(&file),
+            len: file.data.len() as u64,
+            last_modified: build_time(),
+            // Borrowed from the binary in release builds, read from the folder in debug builds
+            data: AssetData::Memory(match file.data {
+                Cow::Borrowed(data) => Bytes::from_static(data),
+                Cow::Owned(data) => Bytes::from(data),
+            }),
+        }
+    }
+
+    async fn from_disk(static_dir: &Path, path: &str) -> Option<Self> {
+        let file_path: PathBuf = resolve_disk_path(static_dir, path)?;
+        let metadata: Metadata = tokio::fs::metadata(&file_path).await.ok().filter(Metadata::is_file)?;
+
+        Some(Self {
+            source: AssetSource::Disk,
+            etag: disk_etag(&metadata),
+            len: metadata.len(),
+            last_modified: metadata.modified().ok().and_then(whole_seconds),
+            data: AssetData::File(file_path),
+        })
+    }
+
+    /// The whole file, for pages that are rewritten before they are sent
+    pub async fn bytes(&self) -> std::io::Result<Bytes> {
+        match &self.data {
+            AssetData::Memory(bytes) => Ok(bytes.clone()),
+            AssetData::File(path) => tokio::fs::read(path).await.map(Bytes::from),
+        }
+    }
+
+    /// Bytes in `range`, opening the file only once the body is polled
+    fn stream(&self, range: Range<u64>) -> BoxStream<'static, std::io::Result<Bytes>> {
+        match &self.data {
+            AssetData::Memory(bytes) => {
+                stream::once(future::ready(Ok(bytes.slice(range.start as usize..range.end as usize)))).boxed()
+            }
+            AssetData::File(path) => {
+                let path: PathBuf = path.clone();
+                stream::once(async move {
+                    let mut file: tokio::fs::File = tokio::fs::File::open(&path).await?;
+                    file.seek(SeekFrom::Start(range.start)).await?;
+                    Ok::<_, std::io::Error>(ReaderStream::new(file.take(range.end - range.start)))
+                })
+                .try_flatten()
+                .boxed()
+            }
+        }
+    }
+
+    /// A `.br` or `.gz` variant, on disk next to the file or embedded from the output of `build.rs`
+    async fn variant(source: AssetSource, frontend: &FrontendSettings, path: &str) -> Option<Self> {
+        match (source, &frontend.static_dir) {
+            (AssetSource::Disk, Some(static_dir)) => Self::from_disk(static_dir, path).await,
+            (AssetSource::Disk, None) => None,
+            (AssetSource::Embedded, _) => Self::precompressed(path),
+        }
+    }
+}
+
+/// Looks in `STATIC_DIR` first, then in the embedded client build
+pub async fn find(frontend: &FrontendSettings, path: &str) -> Option<Asset> {
+    if let Some(static_dir) = &frontend.static_dir {
+        if let Some(asset) = Asset::from_disk(static_dir, path).await {
+            return Some(asset);
+        }
+    }
+
+    Asset::embedded(path)
+}
+
+/// Maps a URL path to a file inside `static_dir`. Returns `None` for anything that could escape
+/// the folder: `..` segments, backslashes, drive prefixes and symlinks pointing outside of it.
+pub fn resolve_disk_path(static_dir: &Path, path: &str) -> Option<PathBuf> {
+    let mut resolved: PathBuf = static_dir.to_path_buf();
+    for segment in path.split('/') {
+        if segment.is_empty() || segment == "." {
+            continue;
+        }
+        if segment == ".." || segment.contains(['\\', ':', '\0']) {
+            return None;
+        }
+        resolved.push(segment);
+    }
+
+    let root: PathBuf = static_dir.canonicalize().ok()?;
+    let canonical: PathBuf = resolved.canonicalize().ok()?;
+    canonical.starts_with(&root).then_some(canonical)
+}
+
+/// Strong ETag built from the SHA-256 rust-embed computes for every embedded file
+pub fn etag(file: &EmbeddedFile) -> EntityTag {
+    EntityTag::new_strong(hex::encode(file.metadata.sha256_hash()))
+}
+
+/// Disk files can be edited in place, a weak ETag from size and modification time avoids hashing them on every request
+fn disk_etag(metadata: &Metadata) -> EntityTag {
+    let modified: u128 = metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map(|since_epoch| since_epoch.as_nanos())
+        .unwrap_or_default();
+
+    EntityTag::new_weak(format!("{:x}-{:x}", metadata.len(), modified))
+}
+
+/// Embedded files are as old as the binary, `None` when `build.rs` couldn't tell when that was
+fn build_time() -> Option<SystemTime> {
+    let timestamp: i64 = build_info().build_timestamp.timestamp();
+    u64::try_from(timestamp)
+        .ok()
+        .filter(|seconds| *seconds > 0)
+        .map(|seconds| UNIX_EPOCH + Duration::from_secs(seconds))
+}
+
+/// Drops the fraction of a second, otherwise a file would always be newer than the date the client echoes back
+fn whole_seconds(time: SystemTime) -> Option<SystemTime> {
+    time.duration_since(UNIX_EPOCH)
+        .ok()
+        .map(|since_epoch| UNIX_EPOCH + Duration::from_secs(since_epoch.as_secs()))
+}
+
+/// `Cache-Control` for a file. HTML pages don't come through here, `bootstrap::document_response` rewrites
+/// them for every request and sends them with its own `Cache-Control` and without an ETag, so they never get a 304.
+pub fn cache_control(path: &str) -> &'static str {
+    if path.starts_with(IMMUTABLE_PREFIX) {
+        IMMUTABLE_CACHE_CONTROL
+    } else {
+        REVALIDATE_CACHE_CONTROL
+    }
+}
+
+/// `If-None-Match` uses the weak comparison, `*` matches any existing file. `If-Modified-Since` is
+/// only looked at without it, the ETag is the more precise of the two.
+fn not_modified(req: &HttpRequest, asset: &Asset) -> bool {
+    match req.get_header::<IfNoneMatch>() {
+        Some(IfNoneMatch::Any) => true,
+        Some(IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(&asset.etag)),
+        None => match (req.get_header::<IfModifiedSince>(), asset.last_modified) {
+            (Some(IfModifiedSince(since)), Some(last_modified)) => last_modified <= SystemTime::from(since),
+            _ => false,
+        },
+    }
+}
+
+/// `.br` and `.gz` variants of the file, in order of preference. Only variants from the same source
+/// count, a file patched on disk must not be shadowed by its stale embedded variant.
+async fn precompressed_variants(frontend: &FrontendSettings, source: AssetSource, path: &str) -> Vec<(ContentEncoding, Asset)> {
+    let mut variants: Vec<(ContentEncoding, Asset)> = Vec::new();
+    for (encoding, suffix) in [(ContentEncoding::Brotli, "br"), (ContentEncoding::Gzip, "gz")] {
+        if let Some(asset) = Asset::variant(source, frontend, &format!("{}.{}", path, suffix)).await {
+            variants.push((encoding, asset));
+        }
+    }
+
+    variants
+}
+
+/// Index of the variant the client prefers, `None` when the plain file should be served
+fn negotiate_encoding(req: &HttpRequest, variants: &[(ContentEncoding, Asset)]) -> Option<usize> {
+    let mut supported: Vec<Encoding> = variants.iter().map(|(encoding, _)| Encoding::Known(*encoding)).collect();
+    supported.push(Encoding::identity());
+    let chosen: Encoding = req.get_header::<AcceptEncoding>()?.negotiate(supported.iter())?;
+
+    variants.iter().position(|(encoding, _)| Encoding::Known(*encoding) == chosen)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum RangeRequest {
+    Full,
+    Partial(Vec<Range<u64>>),
+    Unsatisfiable,
+}
+
+/// The byte ranges to send. A `Range` header that can't be parsed, asks for too many ranges, or comes with
+/// an `If-Range` for another version is ignored and the whole file is sent.
+fn requested_ranges(req: &HttpRequest, asset: &Asset) -> RangeRequest {
+    let Some(header::Range::Bytes(specs)) = req.get_header::<header::Range>() else {
+        return RangeRequest::Full;
+    };
+    // Resuming with the part of an older version would mix two files, `If-Range` requires a strong match
+    match req.get_header::<IfRange>() {
+        Some(IfRange::EntityTag(tag)) if !tag.strong_eq(&asset.etag) => return RangeRequest::Full,
+        Some(IfRange::Date(_)) => return RangeRequest::Full,
+        _ => {}
+    }
+    if specs.len() > MAX_RANGES {
+        return RangeRequest::Full;
+    }
+
+    let ranges: Vec<Range<u64>> = specs
+        .iter()
+        .filter_map(|spec| spec.to_satisfiable_range(asset.len))
+        .map(|(first, last)| first..last + 1)
+        .collect();
+    if ranges.is_empty() {
+        RangeRequest::Unsatisfiable
+    } else {
+        RangeRequest::Partial(ranges)
+    }
+}
+
+fn content_range(range: &Range<u64>, len: u64) -> ContentRange {
+    ContentRange(ContentRangeSpec::Bytes {
+        range: Some((range.start, range.end - 1)),
+        instance_length: Some(len),
+    })
+}
+
+/// `multipart/byteranges` body with one part per range, each with its own `Content-Range`
+fn multipart_ranges(response: &mut HttpResponseBuilder, asset: &Asset, ranges: &[Range<u64>], content_type: &str) -> HttpResponse {
+    let boundary: String = uuid::Uuid::new_v4().simple().to_string();
+    let mut len: u64 = 0;
+    let mut parts: Vec<BoxStream<'static, std::io::Result<Bytes>>> = Vec::with_capacity(ranges.len() * 2 + 1);
+    for range in ranges {
+        let part_headers: String = format!(
+            "\r\n--{}\r\nContent-Type: {}\r\nContent-Range: {}\r\n\r\n",
+            boundary,
+            content_type,
+            content_range(range, asset.len).0
+        );
+        len += part_headers.len() as u64 + (range.end - range.start);
+        parts.push(stream::once(future::ready(Ok(Bytes::from(part_headers)))).boxed());
+        parts.push(asset.stream(range.clone()));
+    }
+    let closing: String = format!("\r\n--{}--\r\n", boundary);
+    len += closing.len() as u64;
+    parts.push(stream::once(future::ready(Ok(Bytes::from(closing)))).boxed());
+
+    response
+        .status(StatusCode::PARTIAL_CONTENT)
+        .content_type(format!("multipart/byteranges; boundary={}", boundary))
+        .body(SizedStream::new(len, stream::iter(parts).flatten()))
+}
+
+/// Responds with a frontend file, or a bodyless 304 when the client already has this version, going by
+/// the ETag or, without one, the `Last-Modified` date.
+/// Precompressed variants are served to clients that accept them, and `Range` requests get the
+/// requested bytes of the chosen variant. Returns `None` when `path` doesn't exist.
+pub async fn asset_response(req: &HttpRequest, frontend: &FrontendSettings, path: &str) -> Option<HttpResponse> {
+    let plain: Asset = find(frontend, path).await?;
+    let mut variants: Vec<(ContentEncoding, Asset)> = precompressed_variants(frontend, plain.source, path).await;
+    let has_variants: bool = !variants.is_empty();
+    let (encoding, asset) = match negotiate_encoding(req, &variants) {
+        Some(index) => {
+            let (encoding, asset) = variants.swap_remove(index);
+            (Some(encoding), asset)
+        }
+        None => (None, plain),
+    };
+    // Each encoding has its own bytes and therefore its own ETag
+    let not_modified: bool = not_modified(req, &asset);
+
+    let mut response: HttpResponseBuilder = if not_modified {
+        HttpResponse::NotModified()
+    } else {
+        HttpResponse::Ok()
+    };
+    response
+        .insert_header(header::ETag(asset.etag.clone()))
+        .insert_header((header::CACHE_CONTROL, cache_control(path)));
+    if let Some(last_modified) = asset.last_modified {
+        response.insert_header(LastModified(HttpDate::from(last_modified)));
+    }
+    if has_variants {
+        response.insert_header((header::VARY, "Accept-Encoding"));
+    }
+
+    if not_modified {
+        return Some(response.finish());
+    }
+
+    response.insert_header((header::ACCEPT_RANGES, "bytes"));
+    if let Some(encoding) = encoding {
+        response.insert_header(encoding);
+    }
+    let content_type: String = mime_guess::from_path(path).first_or_octet_stream().to_string();
+    Some(match requested_ranges(req, &asset) {
+        RangeRequest::Full => response
+            .content_type(content_type)
+            .body(SizedStream::new(asset.len, asset.stream(0..asset.len))),
+        RangeRequest::Partial(ranges) if ranges.len() == 1 => response
+            .status(StatusCode::PARTIAL_CONTENT)
+            .insert_header(content_range(&ranges[0], asset.len))
+            .content_type(content_type)
+            .body(SizedStream::new(ranges[0].end - ranges[0].start, asset.stream(ranges[0].clone()))),
+        RangeRequest::Partial(ranges) => multipart_ranges(&mut response, &asset, &ranges, &content_type),
+        RangeRequest::Unsatisfiable => response
+            .status(StatusCode::RANGE_NOT_SATISFIABLE)
+            .insert_header(ContentRange(ContentRangeSpec::Bytes { range: None, instance_length: Some(asset.len) }))
+            .finish(),
+    })
+}
+
+/// Client side routes (`/about`, `/users/42`) get `index.html`, files (`/_app/foo.js`, `/favicon.png`)
+/// don't. A path with an extension still counts as a route when the browser asks for HTML.
+pub fn is_navigation(req: &HttpRequest, path: &str) -> bool {
+    let file_name: &str = path.rsplit('/').next().unwrap_or_default();
+    if !file_name.contains('.') {
+        return true;
+    }
+
+    req.get_header::<Accept>()
+        .is_some_and(|accept| accept.iter().any(|item| item.item == mime::TEXT_HTML))
+}
+
+/// 404 for a missing asset, with the configured page as body when there is one
+pub async fn not_found(frontend: &FrontendSettings) -> HttpResponse {
+    let Some(page) = frontend.not_found_page.as_deref() else {
+        return HttpResponse::NotFound().finish();
+    };
+    let Some(asset) = find(frontend, page).await else {
+        return HttpResponse::NotFound().finish();
+    };
+    match asset.bytes().await {
+        Ok(data) => HttpResponse::NotFound()
+            .insert_header((header::CACHE_CONTROL, REVALIDATE_CACHE_CONTROL))
+            .content_type(mime_guess::from_path(page).first_or_octet_stream().as_ref())
+            .body(data),
+        Err(_) => HttpResponse::NotFound().finish(),
+    }
+}
+
+/// A servable frontend file and the source serving it
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AssetEntry {
+    pub path: String,
+    pub source: AssetSource,
+    pub size: u64,
+}
+
+/// Every servable file, disk files replace embedded files with the same path. Blocks on file IO.
+pub fn list_assets(frontend: &FrontendSettings) -> Vec<AssetEntry> {
+    let mut entries: BTreeMap<String, AssetEntry> = BTreeMap::new();
+    for path in StaticFiles::iter() {
+        let size: u64 = StaticFiles::get(&path).map(|file| file.data.len() as u64).unwrap_or_default();
+        entries.insert(path.to_string(), AssetEntry { path: path.to_string(), source: AssetSource::Embedded, size });
+    }
+    if let Some(static_dir) = &frontend.static_dir {
+        let mut disk_files: Vec<(String, u64)> = Vec::new();
+        collect_disk_files(static_dir, static_dir, &mut disk_files);
+        for (path, size) in disk_files {
+            // Symlinks pointing outside the folder are never served, so they aren't listed either
+            if resolve_disk_path(static_dir, &path).is_some() {
+                entries.insert(path.clone(), AssetEntry { path, source: AssetSource::Disk, size });
+            }
+        }
+    }
+
+    entries.into_values().collect()
+}
+
+fn collect_disk_files(root: &Path, dir: &Path, files: &mut Vec<(String, u64)>) {
+    let Ok(read_dir) = std::fs::read_dir(dir) else { return };
+    for entry in read_dir.flatten() {
+        let path: PathBuf = entry.path();
+        // Only real folders are descended into, a symlinked folder could loop forever
+        if entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+            collect_disk_files(root, &path, files);
+            continue;
+        }
+        let Ok(metadata) = std::fs::metadata(&path) else { continue };
+        if let (true, Ok(relative)) = (metadata.is_file(), path.strip_prefix(root)) {
+            let url_path: String = relative
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            files.push((url_path, metadata.len()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{body::to_bytes, test::TestRequest};
+
+    const CONTENTS: &[u8] = b"0123456789abcdefghij";
+
+    /// A `STATIC_DIR` holding `file.txt`, removed again when dropped
+    struct StaticDir(PathBuf);
+
+    impl StaticDir {
+        fn new() -> Self {
+            let dir: PathBuf = std::env::temp_dir().join(format!("assets-test-{}", uuid::Uuid::new_v4().simple()));
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(dir.join("file.txt"), CONTENTS).unwrap();
+            Self(dir)
+        }
+
+        fn with_file(self, name: &str, contents: &[u8]) -> Self {
+            std::fs::write(self.0.join(name), contents).unwrap();
+            self
+        }
+
+        fn frontend(&self) -> FrontendSettings {
+            FrontendSettings { static_dir: Some(self.0.clone()), ..FrontendSettings::default() }
+        }
+    }
+
+    impl Drop for StaticDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    async fn respond(static_dir: &StaticDir, request: TestRequest) -> HttpResponse {
+        asset_response(&request.to_http_request(), &static_dir.frontend(), "file.txt").await.unwrap()
+    }
+
+    fn last_modified(response: &HttpResponse) -> String {
+        response.headers().get(header::LAST_MODIFIED).unwrap().to_str().unwrap().to_string()
+    }
+
+    #[actix_web::test]
+    async fn disk_assets_carry_their_modification_time() {
+        let static_dir: StaticDir = StaticDir::new();
+        let response: HttpResponse = respond(&static_dir, TestRequest::get()).await;
+
+        let modified: SystemTime = std::fs::metadata(static_dir.0.join("file.txt")).unwrap().modified().unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(last_modified(&response), HttpDate::from(modified).to_string());
+    }
+
+    #[actix_web::test]
+    async fn unchanged_since_the_given_date_is_not_modified() {
+        let static_dir: StaticDir = StaticDir::new();
+        let date: String = last_modified(&respond(&static_dir, TestRequest::get()).await);
+
+        let response: HttpResponse = respond(&static_dir, TestRequest::get().insert_header((header::IF_MODIFIED_SINCE, date.as_str()))).await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert!(to_bytes(response.into_body()).await.unwrap().is_empty());
+
+        let earlier: HttpDate = HttpDate::from(SystemTime::from(date.parse::<HttpDate>().unwrap()) - Duration::from_secs(1));
+        let response: HttpResponse = respond(&static_dir, TestRequest::get().insert_header((header::IF_MODIFIED_SINCE, earlier))).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn if_none_match_wins_over_if_modified_since() {
+        let static_dir: StaticDir = StaticDir::new();
+        let date: String = last_modified(&respond(&static_dir, TestRequest::get()).await);
+
+        let request: TestRequest = TestRequest::get()
+            .insert_header((header::IF_MODIFIED_SINCE, date.as_str()))
+            .insert_header((header::IF_NONE_MATCH, r#"W/"some-other-version""#));
+        assert_eq!(respond(&static_dir, request).await.status(), StatusCode::OK);
+    }
+
+    fn memory_asset(len: u64) -> Asset {
+        Asset {
+            source: AssetSource::Embedded,
+            data: AssetData::Memory(Bytes::from(vec![b'x'; len as usize])),
+            len,
+            etag: EntityTag::new_strong("v1".to_string()),
+            last_modified: None,
+        }
+    }
+
+    /// `(start, end)` pairs, end exclusive like `Range`
+    fn partial(ranges: &[(u64, u64)]) -> RangeRequest {
+        RangeRequest::Partial(ranges.iter().map(|(start, end)| *start..*end).collect())
+    }
+
+    fn ranges(range: &str, len: u64) -> RangeRequest {
+        requested_ranges(&TestRequest::get().insert_header((header::RANGE, range)).to_http_request(), &memory_asset(len))
+    }
+
+    #[test]
+    fn open_ended_range_runs_to_the_end() {
+        assert_eq!(ranges("bytes=0-", 1000), partial(&[(0, 1000)]));
+        assert_eq!(ranges("bytes=990-", 1000), partial(&[(990, 1000)]));
+    }
+
+    #[test]
+    fn suffix_range_is_the_last_bytes() {
+        assert_eq!(ranges("bytes=-500", 1000), partial(&[(500, 1000)]));
+        // Longer than the file means the whole file
+        assert_eq!(ranges("bytes=-500", 20), partial(&[(0, 20)]));
+    }
+
+    #[test]
+    fn multiple_ranges_are_kept_in_order() {
+        assert_eq!(ranges("bytes=0-9,20-29", 1000), partial(&[(0, 10), (20, 30)]));
+        // Overlapping ranges are sent as asked, not merged
+        assert_eq!(ranges("bytes=0-99,50-149", 1000), partial(&[(0, 100), (50, 150)]));
+    }
+
+    #[test]
+    fn out_of_bounds_ranges() {
+        assert_eq!(ranges("bytes=1000-", 1000), RangeRequest::Unsatisfiable);
+        assert_eq!(ranges("bytes=2000-3000", 1000), RangeRequest::Unsatisfiable);
+        // A last byte past the end is cut off, unsatisfiable ranges next to satisfiable ones are dropped
+        assert_eq!(ranges("bytes=900-5000", 1000), partial(&[(900, 1000)]));
+        assert_eq!(ranges("bytes=2000-3000,0-9", 1000), partial(&[(0, 10)]));
+    }
+
+    #[test]
+    fn unusable_range_headers_get_the_whole_file() {
+        assert_eq!(ranges("bytes=abc", 1000), RangeRequest::Full);
+        assert_eq!(ranges("items=0-9", 1000), RangeRequest::Full);
+        let too_many: String = format!("bytes={}", (0..=MAX_RANGES).map(|i| format!("{}-{}", i * 10, i * 10 + 1)).collect::<Vec<_>>().join(","));
+        assert_eq!(ranges(&too_many, 1000), RangeRequest::Full);
+    }
+
+    #[test]
+    fn if_range_needs_the_current_version() {
+        let asset: Asset = memory_asset(1000);
+        let stale = TestRequest::get().insert_header((header::RANGE, "bytes=0-9")).insert_header((header::IF_RANGE, r#""v0""#));
+        assert_eq!(requested_ranges(&stale.to_http_request(), &asset), RangeRequest::Full);
+        let current = TestRequest::get().insert_header((header::RANGE, "bytes=0-9")).insert_header((header::IF_RANGE, r#""v1""#));
+        assert_eq!(requested_ranges(&current.to_http_request(), &asset), partial(&[(0, 10)]));
+    }
+
+    #[actix_web::test]
+    async fn unsatisfiable_range_is_416() {
+        let static_dir: StaticDir = StaticDir::new();
+        let response: HttpResponse = respond(&static_dir, TestRequest::get().insert_header((header::RANGE, "bytes=500-"))).await;
+
+        assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(response.headers().get(header::CONTENT_RANGE).unwrap(), &format!("bytes */{}", CONTENTS.len()));
+    }
+
+    #[actix_web::test]
+    async fn ranges_are_sent_from_the_file() {
+        let static_dir: StaticDir = StaticDir::new();
+        let single: HttpResponse = respond(&static_dir, TestRequest::get().insert_header((header::RANGE, "bytes=-5"))).await;
+        assert_eq!(single.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(single.headers().get(header::CONTENT_RANGE).unwrap(), "bytes 15-19/20");
+        assert_eq!(to_bytes(single.into_body()).await.unwrap(), &CONTENTS[15..]);
+
+        let multiple: HttpResponse = respond(&static_dir, TestRequest::get().insert_header((header::RANGE, "bytes=0-1,10-11"))).await;
+        assert_eq!(multiple.status(), StatusCode::PARTIAL_CONTENT);
+        assert!(multiple.headers().get(header::CONTENT_TYPE).unwrap().to_str().unwrap().starts_with("multipart/byteranges; boundary="));
+        let body: String = String::from_utf8(to_bytes(multiple.into_body()).await.unwrap().to_vec()).unwrap();
+        assert!(body.contains("Content-Range: bytes 0-1/20\r\n\r\n01\r\n"), "{}", body);
+        assert!(body.contains("Content-Range: bytes 10-11/20\r\n\r\nab\r\n"), "{}", body);
+    }
+
+    fn variants() -> Vec<(ContentEncoding, Asset)> {
+        vec![(ContentEncoding::Brotli, memory_asset(10)), (ContentEncoding::Gzip, memory_asset(12))]
+    }
+
+    fn negotiate(accept_encoding: &str) -> Option<usize> {
+        let req: HttpRequest = TestRequest::get().insert_header((header::ACCEPT_ENCODING, accept_encoding)).to_http_request();
+        negotiate_encoding(&req, &variants())
+    }
+
+    #[test]
+    fn brotli_is_preferred_over_gzip() {
+        assert_eq!(negotiate("gzip, br"), Some(0));
+        assert_eq!(negotiate("gzip"), Some(1));
+        assert_eq!(negotiate("br;q=0.5, gzip;q=0.5"), Some(0));
+    }
+
+    #[test]
+    fn q_values_pick_the_variant() {
+        assert_eq!(negotiate("br;q=0.5, gzip;q=0.8"), Some(1));
+        assert_eq!(negotiate("br;q=0, gzip"), Some(1));
+        // Identity beats both variants here, the plain file is sent
+        assert_eq!(negotiate("identity, br;q=0.1, gzip;q=0.1"), None);
+        assert_eq!(negotiate("br;q=0, gzip;q=0"), None);
+    }
+
+    #[test]
+    fn no_accept_encoding_gets_the_plain_file() {
+        assert_eq!(negotiate_encoding(&TestRequest::get().to_http_request(), &variants()), None);
+        assert_eq!(negotiate_encoding(&TestRequest::get().insert_header((header::ACCEPT_ENCODING, "br")).to_http_request(), &[]), None);
+    }
+
+    #[actix_web::test]
+    async fn precompressed_variants_vary_on_accept_encoding() {
+        let static_dir: StaticDir = StaticDir::new().with_file("file.txt.br", b"brotli").with_file("file.txt.gz", b"gzip");
+
+        let brotli: HttpResponse = respond(&static_dir, TestRequest::get().insert_header((header::ACCEPT_ENCODING, "gzip, br"))).await;
+        assert_eq!(brotli.headers().get(header::CONTENT_ENCODING).unwrap(), "br");
+        assert_eq!(brotli.headers().get(header::VARY).unwrap(), "Accept-Encoding");
+        assert_eq!(brotli.headers().get(header::CONTENT_TYPE).unwrap(), "text/plain");
+        let brotli_etag: header::HeaderValue = brotli.headers().get(header::ETAG).unwrap().clone();
+        assert_eq!(to_bytes(brotli.into_body()).await.unwrap(), "brotli");
+
+        // The plain file is a different representation of the same URL, it varies too
+        let plain: HttpResponse = respond(&static_dir, TestRequest::get()).await;
+        assert!(plain.headers().get(header::CONTENT_ENCODING).is_none());
+        assert_eq!(plain.headers().get(header::VARY).unwrap(), "Accept-Encoding");
+        assert_ne!(plain.headers().get(header::ETAG).unwrap(), &brotli_etag);
+        assert_eq!(to_bytes(plain.into_body()).await.unwrap(), CONTENTS);
+    }
+
+    #[actix_web::test]
+    async fn files_without_variants_dont_vary() {
+        let static_dir: StaticDir = StaticDir::new();
+        let response: HttpResponse = respond(&static_dir, TestRequest::get().insert_header((header::ACCEPT_ENCODING, "gzip, br"))).await;
+
+        assert!(response.headers().get(header::CONTENT_ENCODING).is_none());
+        assert!(response.headers().get(header::VARY).is_none());
+    }
+
+    fn navigation(path: &str, accept: Option<&str>) -> bool {
+        let request: TestRequest = match accept {
+            Some(accept) => TestRequest::get().insert_header((header::ACCEPT, accept)),
+            None => TestRequest::get(),
+        };
+        is_navigation(&request.to_http_request(), path)
+    }
+
+    #[test]
+    fn routes_are_navigations_and_files_are_not() {
+        assert!(navigation("about", None));
+        assert!(navigation("users/42", None));
+        assert!(!navigation("_app/immutable/entry.js", None));
+        assert!(!navigation("favicon.png", Some("image/avif,image/webp,*/*")));
+        // A dot in a folder name doesn't make the path a file
+        assert!(navigation("v1.2/changelog", None));
+    }
+
+    #[test]
+    fn html_requests_for_paths_with_an_extension_are_navigations() {
+        assert!(navigation("users/jane.doe", Some("text/html,application/xhtml+xml,*/*;q=0.8")));
+        assert!(!navigation("users/jane.doe", Some("application/json")));
+    }
+
+    #[actix_web::test]
+    async fn configured_not_found_page_is_the_404_body() {
+        let static_dir: StaticDir = StaticDir::new().with_file("404.html", b"<h1>Not here</h1>");
+        let frontend: FrontendSettings = FrontendSettings { not_found_page: Some("404.html".to_string()), ..static_dir.frontend() };
+
+        let response: HttpResponse = not_found(&frontend).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(response.headers().get(header::CONTENT_TYPE).unwrap(), "text/html");
+        assert_eq!(response.headers().get(header::CACHE_CONTROL).unwrap(), REVALIDATE_CACHE_CONTROL);
+        assert_eq!(to_bytes(response.into_body()).await.unwrap(), "<h1>Not here</h1>");
+    }
+
+    #[actix_web::test]
+    async fn without_a_not_found_page_the_404_is_empty() {
+        let static_dir: StaticDir = StaticDir::new();
+        let response: HttpResponse = not_found(&static_dir.frontend()).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert!(to_bytes(response.into_body()).await.unwrap().is_empty());
+
+        // A configured page that doesn't exist is no reason to fail
+        let frontend: FrontendSettings = FrontendSettings { not_found_page: Some("missing.html".to_string()), ..static_dir.frontend() };
+        let response: HttpResponse = not_found(&frontend).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert!(response.headers().get(header::CONTENT_TYPE).is_none());
+    }
+
+    #[test]
+    fn hashed_build_output_is_immutable() {
+        assert_eq!(cache_control("_app/immutable/entry/start.BfX2k3.js"), IMMUTABLE_CACHE_CONTROL);
+        assert_eq!(cache_control("_app/version.json"), REVALIDATE_CACHE_CONTROL);
+        assert_eq!(cache_control("favicon.png"), REVALIDATE_CACHE_CONTROL);
+        // Only the prefix counts, not the folder name somewhere in the path
+        assert_eq!(cache_control("docs/_app/immutable/entry.js"), REVALIDATE_CACHE_CONTROL);
+    }
+
+    #[test]
+    fn html_revalidates() {
+        assert_eq!(cache_control("index.html"), "no-cache");
+        assert_eq!(cache_control("about/index.html"), "no-cache");
+    }
+
+    #[actix_web::test]
+    async fn immutable_files_are_served_with_a_year_long_cache() {
+        let static_dir: StaticDir = StaticDir::new();
+        std::fs::create_dir_all(static_dir.0.join("_app/immutable")).unwrap();
+        std::fs::write(static_dir.0.join("_app/immutable/app.js"), "export {}").unwrap();
+
+        let req: HttpRequest = TestRequest::get().to_http_request();
+        let immutable: HttpResponse = asset_response(&req, &static_dir.frontend(), "_app/immutable/app.js").await.unwrap();
+        assert_eq!(immutable.headers().get(header::CACHE_CONTROL).unwrap(), IMMUTABLE_CACHE_CONTROL);
+        assert_eq!(respond(&static_dir, TestRequest::get()).await.headers().get(header::CACHE_CONTROL).unwrap(), "no-cache");
+    }
+
+    fn if_none_match(value: &str) -> HttpRequest {
+        TestRequest::get().insert_header((header::IF_NONE_MATCH, value)).to_http_request()
+    }
+
+    #[test]
+    fn matching_strong_etag_is_not_modified() {
+        let asset: Asset = memory_asset(10);
+        assert!(not_modified(&if_none_match(r#""v1""#), &asset));
+        assert!(not_modified(&if_none_match(r#""v0", "v1""#), &asset));
+        // If-None-Match uses the weak comparison
+        assert!(not_modified(&if_none_match(r#"W/"v1""#), &asset));
+        assert!(not_modified(&if_none_match("*"), &asset));
+        assert!(!not_modified(&if_none_match(r#""v0""#), &asset));
+        assert!(!not_modified(&TestRequest::get().to_http_request(), &asset));
+    }
+
+    #[actix_web::test]
+    async fn matching_etag_gets_an_empty_304() {
+        let static_dir: StaticDir = StaticDir::new();
+        let etag: header::HeaderValue = respond(&static_dir, TestRequest::get()).await.headers().get(header::ETAG).unwrap().clone();
+
+        let response: HttpResponse = respond(&static_dir, TestRequest::get().insert_header((header::IF_NONE_MATCH, etag.clone()))).await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers().get(header::ETAG).unwrap(), &etag);
+        assert_eq!(response.headers().get(header::CACHE_CONTROL).unwrap(), "no-cache");
+        assert!(to_bytes(response.into_body()).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,165 @@
+use actix_web::{http::header, web::Bytes, FromRequest, HttpMessage, HttpRequest, HttpResponse};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use super::{assets::Asset, settings::FrontendSettings, AuthenticatedUser, SerializableAppState, SharedState};
+
+/// Id of the injected `<script>`, the client reads it with `document.getElementById`
+pub const BOOTSTRAP_SCRIPT_ID: &str = "app-bootstrap";
+/// Pages embed the user and a nonce, they must be fetched again on every load
+const DOCUMENT_CACHE_CONTROL: &str = "no-cache, private";
+
+/// Nonce allowing the page's inline scripts and styles. Kept in the request extensions so
+/// everything building the response (page, CSP header) uses the same value.
+#[derive(Clone, Debug)]
+pub struct CspNonce(pub String);
+
+pub fn csp_nonce(req: &HttpRequest) -> String {
+    if let Some(nonce) = req.extensions().get::<CspNonce>() {
+        return nonce.0.clone();
+    }
+    let nonce: String = uuid::Uuid::new_v4().simple().to_string();
+    req.extensions_mut().insert(CspNonce(nonce.clone()));
+
+    nonce
+}
+
+/// #### Bootstrap
+/// Data the layout needs for its first paint, saves the round trip to `/api/state`
+#[derive(Serialize)]
+pub struct Bootstrap<'a> {
+    pub state: SerializableAppState<'a>,
+    pub user: Option<AuthenticatedUser>,
+    pub features: &'a BTreeMap<String, bool>,
+    pub nonce: &'a str,
+}
+
+/// Serves an HTML page with the bootstrap `<script>` injected and the nonce added to its inline scripts and styles
+pub async fn document_response(req: &HttpRequest, page: Asset, state: &SharedState, frontend: &FrontendSettings) -> HttpResponse {
+    let nonce: String = csp_nonce(req);
+    // Anonymous visitors are the common case, don't let the extractor log a missing cookie for each of them
+    let user: Option<AuthenticatedUser> = match req.cookie("auth_token") {
+        Some(_) => AuthenticatedUser::extract(req).await.ok(),
+        None => None,
+    };
+    let bootstrap: Bootstrap<'_> = Bootstrap {
+        state: state.to_serializable().await,
+        user,
+        features: &frontend.feature_flags,
+        nonce: &nonce,
+    };
+
+    let data: Bytes = match page.bytes().await {
+        Ok(data) => data,
+        Err(e) => {
+            tracing::event!(target: "backend", tracing::Level::ERROR, "Failed to read the page: {}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    let html: String = String::from_utf8_lossy(&data).into_owned();
+    HttpResponse::Ok()
+        .insert_header((header::CACHE_CONTROL, DOCUMENT_CACHE_CONTROL))
+        .content_type("text/html; charset=utf-8")
+        .body(inject(&html, &bootstrap))
+}
+
+fn inject(html: &str, bootstrap: &Bootstrap<'_>) -> String {
+    let html: String = add_nonce(add_nonce(html.to_string(), "<script", bootstrap.nonce), "<style", bootstrap.nonce);
+    let script: String = format!(
+        r#"<script id="{}" type="application/json" nonce="{}">{}</script>"#,
+        BOOTSTRAP_SCRIPT_ID,
+        bootstrap.nonce,
+        script_safe_json(bootstrap)
+    );
+
+    match html.find("</head>") {
+        Some(index) => format!("{}{}{}", &html[..index], script, &html[index..]),
+        None => format!("{}{}", script, html),
+    }
+}
+
+/// Adds `nonce="..."` to every `tag` that doesn't have a nonce yet
+fn add_nonce(html: String, tag: &str, nonce: &str) -> String {
+    let mut output: String = String::with_capacity(html.len() + 64);
+    let mut rest: &str = &html;
+    while let Some(index) = rest.find(tag) {
+        let after_tag: &str = &rest[index + tag.len()..];
+        let is_tag: bool = after_tag.starts_with(|c: char| c == '>' || c.is_ascii_whitespace());
+        let has_nonce: bool = after_tag.split('>').next().is_some_and(|attributes| attributes.contains("nonce="));
+
+        output.push_str(&rest[..index + tag.len()]);
+        if is_tag && !has_nonce {
+            output.push_str(&format!(r#" nonce="{}""#, nonce));
+        }
+        rest = after_tag;
+    }
+    output.push_str(rest);
+
+    output
+}
+
+/// JSON that can't close the surrounding `<script>` element, whatever the app name or username contains
+fn script_safe_json(value: &impl Serialize) -> String {
+    serde_json::to_string(value)
+        .unwrap_or_else(|_| "null".to_string())
+        .replace('<', "\\u003c")
+        .replace('>', "\\u003e")
+        .replace('&', "\\u0026")
+        // Valid in JSON but line terminators in older JavaScript engines
+        .replace('\u{2028}', "\\u2028")
+        .replace('\u{2029}', "\\u2029")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NONCE: &str = "n0nce";
+
+    /// Attributes of every `<script` tag in `html`
+    fn script_tags(html: &str) -> Vec<&str> {
+        html.match_indices("<script")
+            .map(|(index, _)| html[index..].split('>').next().unwrap_or_default())
+            .filter(|tag| tag["<script".len()..].starts_with(|c: char| c.is_ascii_whitespace()) || tag.len() == "<script".len())
+            .collect()
+    }
+
+    #[test]
+    fn json_cannot_break_out_of_the_script() {
+        let value = serde_json::json!({ "app_name": "</script><script>alert(1)</script>", "user": "<!-- x -->", "note": "a\u{2028}b\u{2029}c & d" });
+        let json: String = script_safe_json(&value);
+
+        for forbidden in ["</script", "<!--", "<", ">", "&", "\u{2028}", "\u{2029}"] {
+            assert!(!json.contains(forbidden), "{:?} in {}", forbidden, json);
+        }
+        assert!(json.contains("\\u003c/script\\u003e"));
+        assert!(json.contains("\\u2028") && json.contains("\\u2029"));
+        // Still the same data for the client
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&json).unwrap(), value);
+    }
+
+    #[test]
+    fn every_script_gets_the_nonce_once() {
+        let html: &str = concat!(
+            "<head><script>one()</script>",
+            "<script type=\"module\" src=\"/app.js\"></script>",
+            "<script\nasync src=\"/late.js\"></script>",
+            "<script nonce=\"other\">kept()</script>",
+            "<scripts>not a script</scripts>",
+            "<style>body {}</style></head>",
+        );
+        let html: String = add_nonce(add_nonce(html.to_string(), "<script", NONCE), "<style", NONCE);
+        // Running it again, e.g. over a page that was already processed, adds nothing
+        let html: String = add_nonce(html, "<script", NONCE);
+
+        let tags: Vec<&str> = script_tags(&html);
+        assert_eq!(tags.len(), 4);
+        for tag in &tags {
+            assert_eq!(tag.matches("nonce=").count(), 1, "{}", tag);
+        }
+        assert_eq!(html.matches(&format!(r#"nonce="{}""#, NONCE)).count(), 4);
+        assert!(html.contains(r#"<script nonce="other">"#));
+        assert!(html.contains("<scripts>"));
+        assert!(html.contains(&format!(r#"<style nonce="{}">"#, NONCE)));
+    }
+}
@@ -0,0 +1,41 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::OnceLock;
+use utoipa::ToSchema;
+
+/// #### Build Info
+/// Captured by `build.rs` when the binary was compiled, tells exactly which build a machine is running
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct BuildInfo {
+    pub version: &'static str,
+    /// `unknown` when built outside of a git checkout
+    pub git_commit: &'static str,
+    /// Tracked files had uncommitted changes
+    pub git_dirty: bool,
+    pub build_timestamp: DateTime<Utc>,
+    pub rustc_version: &'static str,
+    /// `debug` or `release`
+    pub profile: &'static str,
+    /// Enabled cargo features, e.g. `dev_proxy`
+    pub features: Vec<&'static str>,
+    /// SHA-256 of the embedded client build, `unknown` when there was none
+    pub client_bundle_hash: &'static str,
+}
+
+pub fn build_info() -> &'static BuildInfo {
+    static BUILD_INFO: OnceLock<BuildInfo> = OnceLock::new();
+    BUILD_INFO.get_or_init(|| BuildInfo {
+        version: env!("CARGO_PKG_VERSION"),
+        git_commit: env!("BUILD_GIT_COMMIT"),
+        git_dirty: env!("BUILD_GIT_DIRTY") == "true",
+        build_timestamp: env!("BUILD_TIMESTAMP")
+            .parse()
+            .ok()
+            .and_then(|timestamp| DateTime::from_timestamp(timestamp, 0))
+            .unwrap_or_default(),
+        rustc_version: env!("BUILD_RUSTC_VERSION"),
+        profile: env!("BUILD_PROFILE"),
+        features: env!("BUILD_FEATURES").split(',').filter(|feature| !feature.is_empty()).collect(),
+        client_bundle_hash: env!("BUILD_CLIENT_HASH"),
+    })
+}
@@ -0,0 +1,361 @@
+use actix_web::{
+    body::{BoxBody, EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::StatusCode,
+    middleware::Next,
+    web::{Bytes, Data},
+    HttpResponse,
+};
+use futures_util::stream;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+use utoipa::ToSchema;
+
+/// The rules can't lock the admin out of removing them again
+const ADMIN_PATH: &str = "/api/admin/chaos";
+/// Longest delay a rule can add, longer ones only tie up workers
+const MAX_LATENCY_MS: f64 = 600_000.0;
+
+/// When a matching request gets the fault
+#[derive(Clone, Debug, Deserialize, Serialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Trigger {
+    /// Every matching request with this chance, from 0 to 100
+    Percentage { percent: f64 },
+    /// Only the nth matching request, counting from 1
+    NthHit { n: u64 },
+}
+
+/// Delay added before the handler runs
+#[derive(Clone, Debug, Deserialize, Serialize, ToSchema)]
+#[serde(tag = "distribution", rename_all = "snake_case")]
+pub enum LatencyDistribution {
+    Fixed { ms: u64 },
+    Uniform { min_ms: u64, max_ms: u64 },
+    Normal { mean_ms: f64, std_dev_ms: f64 },
+}
+
+/// What happens to a request the rule fires on
+#[derive(Clone, Debug, Deserialize, Serialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Fault {
+    /// Waits, then lets the handler answer as usual
+    Latency(LatencyDistribution),
+    /// Answers with `status` instead of calling the handler
+    Error { status: u16, message: Option<String> },
+    /// Closes the connection without an answer
+    Drop,
+}
+
+/// #### Fault Rule
+/// Matching requests are counted on every rule, the first rule that fires injects its fault
+#[derive(Clone, Debug, Deserialize, Serialize, ToSchema)]
+pub struct FaultRule {
+    #[serde(default, skip_deserializing)]
+    #[schema(read_only)]
+    pub id: u64,
+    /// e.g. `GET`, any method when missing
+    pub method: Option<String>,
+    /// Relative to `BASE_PATH`, `*` matches any characters, e.g. `/api/health` or `/api/users/*`
+    pub path: String,
+    pub trigger: Trigger,
+    pub fault: Fault,
+    /// Matching requests so far
+    #[serde(default, skip_deserializing)]
+    #[schema(read_only)]
+    pub hits: u64,
+    /// Requests the fault was injected into
+    #[serde(default, skip_deserializing)]
+    #[schema(read_only)]
+    pub injected: u64,
+}
+
+impl FaultRule {
+    /// Rejects rules that could never fire or would panic when they do
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.path.starts_with('/') {
+            return Err(format!("Path '{}' must start with /", self.path));
+        }
+        match self.trigger {
+            Trigger::Percentage { percent } if !(0.0..=100.0).contains(&percent) => {
+                return Err(format!("Percentage {} is not between 0 and 100", percent));
+            }
+            Trigger::NthHit { n: 0 } => return Err("Hits are counted from 1".to_string()),
+            _ => {}
+        }
+        match &self.fault {
+            Fault::Latency(LatencyDistribution::Fixed { ms }) if *ms as f64 > MAX_LATENCY_MS => {
+                Err(format!("ms {} is longer than {} ms", ms, MAX_LATENCY_MS))
+            }
+            Fault::Latency(LatencyDistribution::Uniform { min_ms, max_ms }) if min_ms > max_ms => {
+                Err(format!("min_ms {} is larger than max_ms {}", min_ms, max_ms))
+            }
+            Fault::Latency(LatencyDistribution::Uniform { max_ms, .. }) if *max_ms as f64 > MAX_LATENCY_MS => {
+                Err(format!("max_ms {} is longer than {} ms", max_ms, MAX_LATENCY_MS))
+            }
+            Fault::Latency(LatencyDistribution::Normal { mean_ms, std_dev_ms })
+                if !(0.0..=MAX_LATENCY_MS).contains(mean_ms) || !(0.0..=MAX_LATENCY_MS).contains(std_dev_ms) =>
+            {
+                Err(format!("mean_ms and std_dev_ms must be between 0 and {} ms", MAX_LATENCY_MS))
+            }
+            Fault::Error { status, .. } if StatusCode::from_u16(*status).is_err() => Err(format!("{} is not an HTTP status", status)),
+            _ => Ok(()),
+        }
+    }
+
+    fn matches(&self, method: &str, path: &str) -> bool {
+        self.method.as_deref().is_none_or(|rule_method| rule_method.eq_ignore_ascii_case(method))
+            && wildcard_match(&self.path, path)
+    }
+
+    fn fires(&self) -> bool {
+        match self.trigger {
+            Trigger::Percentage { percent } => rand::rng().random_bool(percent / 100.0),
+            Trigger::NthHit { n } => self.hits == n,
+        }
+    }
+}
+
+/// `*` matches any run of characters, everything else must match exactly
+fn wildcard_match(pattern: &str, path: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first: &str = parts.next().unwrap_or_default();
+    let Some(mut rest) = path.strip_prefix(first) else { return false };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+
+    rest.ends_with(last)
+}
+
+impl LatencyDistribution {
+    fn sample(&self) -> Duration {
+        let mut rng = rand::rng();
+        let ms: f64 = match *self {
+            Self::Fixed { ms } => ms as f64,
+            Self::Uniform { min_ms, max_ms } => rng.random_range(min_ms..=max_ms) as f64,
+            // Box-Muller, negative samples are clamped to no delay
+            Self::Normal { mean_ms, std_dev_ms } => {
+                let (u1, u2): (f64, f64) = (1.0 - rng.random::<f64>(), rng.random::<f64>());
+                mean_ms + std_dev_ms * (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+            }
+        };
+
+        // The tails of the normal distribution are cut off at the maximum too
+        Duration::from_secs_f64(ms.clamp(0.0, MAX_LATENCY_MS) / 1000.0)
+    }
+}
+
+/// #### Chaos Rules
+/// Fault injection rules, managed on `/api/admin/chaos` and only compiled with the `chaos` feature
+pub struct ChaosRules {
+    base_path: String,
+    rules: Mutex<Vec<FaultRule>>,
+    next_id: AtomicU64,
+}
+
+impl ChaosRules {
+    pub fn new(base_path: &str) -> Self {
+        Self {
+            base_path: base_path.to_string(),
+            rules: Mutex::new(Vec::new()),
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    pub fn list(&self) -> Vec<FaultRule> {
+        self.rules.lock().expect("chaos rules lock poisoned").clone()
+    }
+
+    pub fn add(&self, mut rule: FaultRule) -> FaultRule {
+        rule.id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        rule.hits = 0;
+        rule.injected = 0;
+        self.rules.lock().expect("chaos rules lock poisoned").push(rule.clone());
+
+        rule
+    }
+
+    /// `false` when there is no rule with this id
+    pub fn remove(&self, id: u64) -> bool {
+        let mut rules = self.rules.lock().expect("chaos rules lock poisoned");
+        let count: usize = rules.len();
+        rules.retain(|rule| rule.id != id);
+
+        rules.len() != count
+    }
+
+    pub fn clear(&self) {
+        self.rules.lock().expect("chaos rules lock poisoned").clear();
+    }
+
+    /// Starts counting hits from zero again, so `nth_hit` rules fire once more
+    pub fn reset_hits(&self) {
+        for rule in self.rules.lock().expect("chaos rules lock poisoned").iter_mut() {
+            rule.hits = 0;
+            rule.injected = 0;
+        }
+    }
+
+    /// Counts the request on every matching rule and returns the fault of the first one that fires
+    fn select(&self, method: &str, path: &str) -> Option<(u64, Fault)> {
+        let mut rules = self.rules.lock().expect("chaos rules lock poisoned");
+        let mut selected: Option<(u64, Fault)> = None;
+        for rule in rules.iter_mut().filter(|rule| rule.matches(method, path)) {
+            rule.hits += 1;
+            if selected.is_none() && rule.fires() {
+                rule.injected += 1;
+                selected = Some((rule.id, rule.fault.clone()));
+            }
+        }
+
+        selected
+    }
+}
+
+/// Applies the `ChaosRules` app data to every request below `BASE_PATH`
+pub async fn inject_faults(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody, BoxBody>>, actix_web::Error> {
+    let Some(chaos) = req.app_data::<Data<ChaosRules>>().cloned() else {
+        return next.call(req).await.map(ServiceResponse::map_into_left_body);
+    };
+    let path: &str = req.path().strip_prefix(chaos.base_path.as_str()).unwrap_or(req.path());
+    if path.starts_with(ADMIN_PATH) {
+        return next.call(req).await.map(ServiceResponse::map_into_left_body);
+    }
+    let Some((id, fault)) = chaos.select(req.method().as_str(), path) else {
+        return next.call(req).await.map(ServiceResponse::map_into_left_body);
+    };
+
+    tracing::event!(target: "chaos", tracing::Level::WARN, "Rule {} injects {:?} into {} {}", id, fault, req.method(), req.path());
+    match fault {
+        Fault::Latency(distribution) => {
+            actix_web::rt::time::sleep(distribution.sample()).await;
+            next.call(req).await.map(ServiceResponse::map_into_left_body)
+        }
+        Fault::Error { status, message } => {
+            let status: StatusCode = StatusCode::from_u16(status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            let message: String = message.unwrap_or_else(|| format!("Fault injected by chaos rule {}", id));
+            Ok(req.into_response(HttpResponse::build(status).body(message)).map_into_right_body())
+        }
+        // A body that fails before its first chunk makes actix close the connection without a response
+        Fault::Drop => {
+            let body = stream::once(async {
+                Err::<Bytes, _>(std::io::Error::new(std::io::ErrorKind::ConnectionAborted, "Connection dropped by a chaos rule"))
+            });
+            Ok(req.into_response(HttpResponse::Ok().streaming(body)).map_into_right_body())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(path: &str, trigger: Trigger, fault: Fault) -> FaultRule {
+        FaultRule { id: 0, method: None, path: path.to_string(), trigger, fault, hits: 0, injected: 0 }
+    }
+
+    fn always(fault: Fault) -> FaultRule {
+        rule("/api/health", Trigger::Percentage { percent: 100.0 }, fault)
+    }
+
+    #[test]
+    fn wildcard_suffix() {
+        assert!(wildcard_match("/api/users/*", "/api/users/"));
+        assert!(wildcard_match("/api/users/*", "/api/users/42/posts"));
+        assert!(!wildcard_match("/api/users/*", "/api/user"));
+    }
+
+    #[test]
+    fn wildcard_prefix() {
+        assert!(wildcard_match("*/health", "/api/health"));
+        assert!(wildcard_match("*/health", "/health"));
+        assert!(!wildcard_match("*/health", "/api/health/live"));
+    }
+
+    #[test]
+    fn wildcard_middle() {
+        assert!(wildcard_match("/api/*/posts", "/api/users/posts"));
+        assert!(wildcard_match("/api/*/posts/*", "/api/users/posts/7"));
+        assert!(wildcard_match("/a*b*c", "/abc"));
+        assert!(!wildcard_match("/api/*/posts", "/api/users/comments"));
+        // Parts can't overlap, the second `b` needs its own character
+        assert!(!wildcard_match("/a*b*b", "/ab"));
+    }
+
+    #[test]
+    fn wildcard_exact_and_empty() {
+        assert!(wildcard_match("/api/health", "/api/health"));
+        assert!(!wildcard_match("/api/health", "/api/health/live"));
+        assert!(wildcard_match("*", "/anything"));
+        assert!(wildcard_match("", ""));
+        assert!(!wildcard_match("", "/api/health"));
+    }
+
+    #[test]
+    fn empty_or_relative_path_is_rejected() {
+        assert!(rule("", Trigger::NthHit { n: 1 }, Fault::Drop).validate().is_err());
+        assert!(rule("api/*", Trigger::NthHit { n: 1 }, Fault::Drop).validate().is_err());
+        assert!(rule("/api/*", Trigger::NthHit { n: 1 }, Fault::Drop).validate().is_ok());
+    }
+
+    #[test]
+    fn invalid_probabilities_are_rejected() {
+        for percent in [-1.0, 100.5, f64::NAN, f64::INFINITY] {
+            assert!(rule("/", Trigger::Percentage { percent }, Fault::Drop).validate().is_err(), "{} accepted", percent);
+        }
+        for percent in [0.0, 50.0, 100.0] {
+            assert!(rule("/", Trigger::Percentage { percent }, Fault::Drop).validate().is_ok(), "{} rejected", percent);
+        }
+        assert!(rule("/", Trigger::NthHit { n: 0 }, Fault::Drop).validate().is_err());
+    }
+
+    #[test]
+    fn invalid_durations_are_rejected() {
+        let invalid: [LatencyDistribution; 6] = [
+            LatencyDistribution::Fixed { ms: 600_001 },
+            LatencyDistribution::Uniform { min_ms: 10, max_ms: 5 },
+            LatencyDistribution::Uniform { min_ms: 0, max_ms: u64::MAX },
+            LatencyDistribution::Normal { mean_ms: -1.0, std_dev_ms: 10.0 },
+            LatencyDistribution::Normal { mean_ms: 100.0, std_dev_ms: -10.0 },
+            LatencyDistribution::Normal { mean_ms: f64::NAN, std_dev_ms: 10.0 },
+        ];
+        for distribution in invalid {
+            assert!(always(Fault::Latency(distribution.clone())).validate().is_err(), "{:?} accepted", distribution);
+        }
+        assert!(always(Fault::Latency(LatencyDistribution::Fixed { ms: 3_000 })).validate().is_ok());
+        assert!(always(Fault::Latency(LatencyDistribution::Uniform { min_ms: 5, max_ms: 5 })).validate().is_ok());
+        assert!(always(Fault::Latency(LatencyDistribution::Normal { mean_ms: 200.0, std_dev_ms: 50.0 })).validate().is_ok());
+    }
+
+    #[test]
+    fn invalid_status_is_rejected() {
+        assert!(always(Fault::Error { status: 42, message: None }).validate().is_err());
+        assert!(always(Fault::Error { status: 503, message: None }).validate().is_ok());
+    }
+
+    #[test]
+    fn latency_samples_stay_in_bounds() {
+        let normal: LatencyDistribution = LatencyDistribution::Normal { mean_ms: 0.0, std_dev_ms: MAX_LATENCY_MS };
+        for _ in 0..1_000 {
+            assert!(normal.sample() <= Duration::from_millis(MAX_LATENCY_MS as u64));
+        }
+    }
+}
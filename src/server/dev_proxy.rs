@@ -0,0 +1,248 @@
+use actix_web::{
+    error::{ErrorBadGateway, ErrorBadRequest},
+    http::{
+        header::{self, HeaderValue},
+        StatusCode,
+    },
+    web::{Data, Payload},
+    HttpRequest, HttpResponse,
+};
+use futures_util::{SinkExt, StreamExt};
+use tokio_tungstenite::tungstenite::{client::IntoClientRequest, Message as UpstreamMessage};
+
+/// Headers that only describe one connection, plus `Host`, which has to name the dev server
+const HOP_BY_HOP_HEADERS: [&str; 10] = [
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+    "host",
+    "content-length",
+];
+
+/// #### Dev Proxy
+/// Forwards every non-API request to the Vite dev server, so in development the frontend and the
+/// API are served from one origin and Vite's hot module reloading WebSocket keeps working.
+pub struct DevProxy {
+    client: reqwest::Client,
+    /// e.g. `http://localhost:5173`, without a trailing slash
+    upstream: String,
+    /// `BASE_PATH`, Vite serves the app below the same prefix through `paths.base`
+    base_path: String,
+}
+
+impl DevProxy {
+    pub fn new(upstream: &str, base_path: &str) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            upstream: upstream.trim_end_matches('/').to_string(),
+            base_path: base_path.to_string(),
+        }
+    }
+}
+
+fn is_hop_by_hop(name: &str) -> bool {
+    HOP_BY_HOP_HEADERS.iter().any(|hop_by_hop| name.eq_ignore_ascii_case(hop_by_hop))
+}
+
+/// `/api` and everything below it, relative to the base path. `/apidocs` is still the frontend's.
+fn is_api_path(path: &str, base_path: &str) -> bool {
+    let path: &str = path.strip_prefix(base_path).unwrap_or(path);
+    path.strip_prefix("/api").is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// Default service of the app when the proxy is enabled
+pub async fn proxy(req: HttpRequest, payload: Payload, proxy: Data<DevProxy>) -> actix_web::Result<HttpResponse> {
+    // Unknown API routes fall through to the default service too, they are not the frontend's business
+    if is_api_path(req.path(), &proxy.base_path) {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+
+    let path_and_query: &str = req.uri().path_and_query().map(|p| p.as_str()).unwrap_or("/");
+    let is_websocket: bool = req
+        .headers()
+        .get(header::UPGRADE)
+        .and_then(|upgrade| upgrade.to_str().ok())
+        .is_some_and(|upgrade| upgrade.eq_ignore_ascii_case("websocket"));
+    if is_websocket {
+        let url: String = format!("{}{}", proxy.upstream.replacen("http", "ws", 1), path_and_query);
+        return proxy_websocket(&req, payload, &url).await;
+    }
+
+    let method = reqwest::Method::from_bytes(req.method().as_str().as_bytes()).map_err(ErrorBadRequest)?;
+    let mut upstream_request = proxy.client.request(method, format!("{}{}", proxy.upstream, path_and_query));
+    for (name, value) in req.headers() {
+        if !is_hop_by_hop(name.as_str()) {
+            upstream_request = upstream_request.header(name.as_str(), value.as_bytes());
+        }
+    }
+
+    let body = payload.to_bytes().await?;
+    let upstream_response = upstream_request.body(body).send().await.map_err(|e| {
+        tracing::event!(target: "backend", tracing::Level::WARN, "Vite dev server at {} is not reachable: {}", proxy.upstream, e);
+        ErrorBadGateway(format!("Vite dev server at {} is not reachable, is `pnpm dev` running?", proxy.upstream))
+    })?;
+
+    let status: StatusCode = StatusCode::from_u16(upstream_response.status().as_u16()).unwrap_or(StatusCode::BAD_GATEWAY);
+    let mut response = HttpResponse::build(status);
+    for (name, value) in upstream_response.headers() {
+        if !is_hop_by_hop(name.as_str()) {
+            response.append_header((name.as_str(), value.as_bytes()));
+        }
+    }
+
+    Ok(response.streaming(upstream_response.bytes_stream()))
+}
+
+/// Accepts the browser's WebSocket and pipes messages to and from a second WebSocket opened to Vite.
+/// The subprotocol (`vite-hmr`) is passed through, the browser refuses the connection without it.
+async fn proxy_websocket(req: &HttpRequest, payload: Payload, url: &str) -> actix_web::Result<HttpResponse> {
+    let mut upstream_request = url.into_client_request().map_err(ErrorBadGateway)?;
+    if let Some(protocol) = req.headers().get(header::SEC_WEBSOCKET_PROTOCOL) {
+        upstream_request
+            .headers_mut()
+            .insert("sec-websocket-protocol", protocol.as_bytes().try_into().map_err(ErrorBadRequest)?);
+    }
+    let (upstream, upstream_response) = tokio_tungstenite::connect_async(upstream_request).await.map_err(|e| {
+        tracing::event!(target: "backend", tracing::Level::WARN, "Vite dev server WebSocket at {} failed: {}", url, e);
+        ErrorBadGateway(e)
+    })?;
+
+    let (mut response, mut session, mut messages) = actix_ws::handle(req, payload)?;
+    if let Some(protocol) = upstream_response.headers().get("sec-websocket-protocol") {
+        let protocol: HeaderValue = HeaderValue::from_bytes(protocol.as_bytes()).map_err(ErrorBadGateway)?;
+        response.headers_mut().insert(header::SEC_WEBSOCKET_PROTOCOL, protocol);
+    }
+
+    let (mut upstream_tx, mut upstream_rx) = upstream.split();
+    actix_web::rt::spawn(async move {
+        loop {
+            tokio::select! {
+                message = messages.next() => {
+                    let Some(Ok(message)) = message else { break };
+                    let forwarded: UpstreamMessage = match message {
+                        actix_ws::Message::Text(text) => UpstreamMessage::text(text.to_string()),
+                        actix_ws::Message::Binary(bytes) => UpstreamMessage::binary(bytes),
+                        actix_ws::Message::Ping(bytes) => UpstreamMessage::Ping(bytes),
+                        actix_ws::Message::Pong(bytes) => UpstreamMessage::Pong(bytes),
+                        actix_ws::Message::Close(_) => break,
+                        actix_ws::Message::Continuation(_) | actix_ws::Message::Nop => continue,
+                    };
+                    if upstream_tx.send(forwarded).await.is_err() {
+                        break;
+                    }
+                }
+                message = upstream_rx.next() => {
+                    let Some(Ok(message)) = message else { break };
+                    let result = match message {
+                        UpstreamMessage::Text(text) => session.text(text.as_str().to_string()).await,
+                        UpstreamMessage::Binary(bytes) => session.binary(bytes).await,
+                        UpstreamMessage::Ping(bytes) => session.ping(&bytes).await,
+                        UpstreamMessage::Pong(bytes) => session.pong(&bytes).await,
+                        UpstreamMessage::Close(_) => break,
+                        UpstreamMessage::Frame(_) => continue,
+                    };
+                    if result.is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+        let _ = session.close(None).await;
+        let _ = upstream_tx.close().await;
+    });
+
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{
+        test::{call_service, init_service, read_body, TestRequest},
+        web, App, HttpServer,
+    };
+    use std::net::TcpListener;
+
+    /// Stand-in for Vite that echoes the request headers it got and sends a few hop-by-hop ones back
+    fn upstream() -> String {
+        let listener: TcpListener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address: String = format!("http://{}", listener.local_addr().unwrap());
+        let server = HttpServer::new(|| {
+            App::new().default_service(web::to(|req: HttpRequest| async move {
+                let mut received: Vec<String> = req.headers().keys().map(|name| name.to_string()).collect();
+                received.sort();
+                HttpResponse::Ok()
+                    .insert_header(("keep-alive", "timeout=5"))
+                    .insert_header(("x-upstream", "vite"))
+                    .body(format!("{} {}", req.uri(), received.join(",")))
+            }))
+        })
+        .listen(listener)
+        .unwrap()
+        .workers(1)
+        .run();
+        actix_web::rt::spawn(server);
+        address
+    }
+
+    macro_rules! app {
+        ($upstream:expr, $base_path:expr) => {
+            init_service(App::new().app_data(Data::new(DevProxy::new($upstream, $base_path))).default_service(web::to(proxy))).await
+        };
+    }
+
+    #[test]
+    fn api_paths_are_matched_by_segment() {
+        assert!(is_api_path("/api", ""));
+        assert!(is_api_path("/api/nope", ""));
+        assert!(is_api_path("/app/api", "/app"));
+        assert!(is_api_path("/app/api/nope", "/app"));
+        assert!(!is_api_path("/apidocs", ""));
+        assert!(!is_api_path("/app/apiary", "/app"));
+        assert!(!is_api_path("/", ""));
+    }
+
+    #[actix_web::test]
+    async fn api_paths_are_not_proxied() {
+        // Nothing listens here, so a proxied request would be a 502
+        let app = app!("http://127.0.0.1:9", "/app");
+        for path in ["/app/api", "/app/api/nope", "/api/nope"] {
+            let status: StatusCode = call_service(&app, TestRequest::get().uri(path).to_request()).await.status();
+            assert_eq!(status, StatusCode::NOT_FOUND, "{}", path);
+        }
+    }
+
+    #[actix_web::test]
+    async fn unreachable_dev_server_is_a_bad_gateway() {
+        let app = app!("http://127.0.0.1:9", "");
+        let status: StatusCode = call_service(&app, TestRequest::get().uri("/").to_request()).await.status();
+        assert_eq!(status, StatusCode::BAD_GATEWAY);
+    }
+
+    #[actix_web::test]
+    async fn hop_by_hop_headers_are_stripped() {
+        let app = app!(&upstream(), "");
+        let request = TestRequest::get()
+            .uri("/src/app.ts?t=1")
+            .insert_header((header::CONNECTION, "keep-alive"))
+            .insert_header(("keep-alive", "timeout=5"))
+            .insert_header((header::TE, "trailers"))
+            .insert_header((header::ACCEPT, "text/javascript"));
+        let res = call_service(&app, request.to_request()).await;
+
+        assert_eq!(res.status(), StatusCode::OK);
+        assert!(!res.headers().contains_key("keep-alive"));
+        assert_eq!(res.headers().get("x-upstream").unwrap(), "vite");
+        let body = read_body(res).await;
+        let (uri, received) = std::str::from_utf8(&body).unwrap().split_once(' ').unwrap();
+        assert_eq!(uri, "/src/app.ts?t=1");
+        let received: Vec<&str> = received.split(',').collect();
+        assert!(received.contains(&"accept"), "{:?}", received);
+        assert!(!received.contains(&"te") && !received.contains(&"keep-alive"), "{:?}", received);
+    }
+}
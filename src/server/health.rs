@@ -0,0 +1,271 @@
+use serde::Serialize;
+use sqlx::{migrate::Migrator, sqlite::SqliteConnectOptions, Pool, Sqlite};
+use std::{
+    collections::{BTreeMap, HashSet},
+    future::Future,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{Duration, Instant},
+};
+use tracing::Instrument;
+use utoipa::ToSchema;
+
+use actix_svelte::otel;
+
+use super::{maintenance, settings::HealthSettings};
+
+/// Same migrations `sqlx migrate run` applies, embedded at compile time
+static MIGRATOR: Migrator = sqlx::migrate!();
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Pass,
+    Fail,
+}
+
+/// Outcome of one readiness check
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CheckResult {
+    pub status: CheckStatus,
+    pub latency_ms: f64,
+    /// What was found, e.g. the free space or why the check failed
+    pub detail: String,
+}
+
+/// #### Readiness Report
+/// `status` only passes when every check passes
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ReadinessReport {
+    pub status: CheckStatus,
+    pub checks: BTreeMap<&'static str, CheckResult>,
+}
+
+/// #### Liveness Report
+/// The process is up and serving requests, nothing else is checked
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LivenessReport {
+    pub status: CheckStatus,
+    pub uptime_seconds: u64,
+}
+
+/// #### Health Checks
+/// What the readiness probe looks at besides the database pool
+pub struct HealthChecks {
+    db_timeout: Duration,
+    min_free_disk_bytes: u64,
+    /// `None` for in-memory databases
+    database_dir: Option<PathBuf>,
+    log_dir: PathBuf,
+}
+
+impl HealthChecks {
+    pub fn new(settings: &HealthSettings, database_url: &str, log_dir: PathBuf) -> Self {
+        // sqlx renames `:memory:` to `file:sqlx-in-memory-<n>` and doesn't tell whether a database is in memory
+        let in_memory = |filename: &Path| {
+            let filename = filename.to_string_lossy();
+            filename == ":memory:" || filename.starts_with("file:sqlx-in-memory-") || database_url.contains("mode=memory")
+        };
+        let database_dir: Option<PathBuf> = SqliteConnectOptions::from_str(database_url)
+            .ok()
+            .map(|options| options.get_filename().to_path_buf())
+            .filter(|filename| !in_memory(filename))
+            .map(|filename| match filename.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+                _ => PathBuf::from("."),
+            });
+
+        Self {
+            db_timeout: settings.db_timeout,
+            min_free_disk_bytes: settings.min_free_disk_bytes,
+            database_dir,
+            log_dir,
+        }
+    }
+
+    /// Runs every check, the database ones concurrently and each bounded by the timeout
+    pub async fn readiness(&self, pool: &Pool<Sqlite>) -> ReadinessReport {
+        let (database, migrations) = tokio::join!(
+            timed(self.db_timeout, check_database(pool)),
+            timed(self.db_timeout, check_migrations(pool)),
+        );
+        let mut checks: BTreeMap<&'static str, CheckResult> = BTreeMap::new();
+        checks.insert("database", database);
+        checks.insert("migrations", migrations);
+        if let Some(database_dir) = &self.database_dir {
+            checks.insert("database_disk", measure(|| check_disk(database_dir, self.min_free_disk_bytes)));
+        }
+        checks.insert("logs_disk", measure(|| check_disk(&self.log_dir, self.min_free_disk_bytes)));
+        checks.insert("paused", measure(|| match maintenance::current() {
+            Some(notice) => Err(match notice.until {
+                Some(until) => format!("Service is paused until {}", until.to_rfc3339()),
+                None => "Service is paused".to_string(),
+            }),
+            None => Ok("Service is running".to_string()),
+        }));
+
+        let status: CheckStatus = if checks.values().all(|check| check.status == CheckStatus::Pass) {
+            CheckStatus::Pass
+        } else {
+            CheckStatus::Fail
+        };
+
+        ReadinessReport { status, checks }
+    }
+}
+
+fn result(outcome: Result<String, String>, started: Instant) -> CheckResult {
+    // Microsecond precision is plenty for a probe
+    let latency_ms: f64 = (started.elapsed().as_secs_f64() * 1_000_000.0).round() / 1000.0;
+    match outcome {
+        Ok(detail) => CheckResult { status: CheckStatus::Pass, latency_ms, detail },
+        Err(detail) => CheckResult { status: CheckStatus::Fail, latency_ms, detail },
+    }
+}
+
+async fn timed(timeout: Duration, check: impl Future<Output = Result<String, String>>) -> CheckResult {
+    let started: Instant = Instant::now();
+    let outcome: Result<String, String> = match tokio::time::timeout(timeout, check).await {
+        Ok(outcome) => outcome,
+        Err(_) => Err(format!("Timed out after {} ms", timeout.as_millis())),
+    };
+
+    result(outcome, started)
+}
+
+fn measure(check: impl FnOnce() -> Result<String, String>) -> CheckResult {
+    let started: Instant = Instant::now();
+    result(check(), started)
+}
+
+async fn check_database(pool: &Pool<Sqlite>) -> Result<String, String> {
+    sqlx::query("SELECT 1").execute(pool).instrument(otel::db_span("SELECT", "1")).await.map_err(|e| e.to_string())?;
+
+    Ok("SELECT 1 succeeded".to_string())
+}
+
+/// Fails while the schema is behind the migrations this binary was built with, or one of them failed halfway
+async fn check_migrations(pool: &Pool<Sqlite>) -> Result<String, String> {
+    let has_table: bool = sqlx::query_scalar("SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = '_sqlx_migrations'")
+        .fetch_one(pool)
+        .instrument(otel::db_span("SELECT", "sqlite_master"))
+        .await
+        .map_err(|e| e.to_string())?;
+    let applied: Vec<(i64, bool)> = if has_table {
+        sqlx::query_as("SELECT version, success FROM _sqlx_migrations")
+            .fetch_all(pool)
+            .instrument(otel::db_span("SELECT", "_sqlx_migrations"))
+            .await
+            .map_err(|e| e.to_string())?
+    } else {
+        Vec::new()
+    };
+
+    if let Some((version, _)) = applied.iter().find(|(_, success)| !success) {
+        return Err(format!("Migration {} did not finish", version));
+    }
+    let applied: HashSet<i64> = applied.into_iter().map(|(version, _)| version).collect();
+    let pending: Vec<String> = MIGRATOR
+        .iter()
+        .filter(|migration| migration.migration_type.is_up_migration() && !applied.contains(&migration.version))
+        .map(|migration| format!("{}_{}", migration.version, migration.description))
+        .collect();
+
+    if pending.is_empty() {
+        Ok(format!("{} applied", applied.len()))
+    } else {
+        Err(format!("Pending: {}", pending.join(", ")))
+    }
+}
+
+fn check_disk(dir: &Path, min_free_bytes: u64) -> Result<String, String> {
+    let free: u64 = free_space(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    let detail: String = format!("{} MiB free in {}", free / 1024 / 1024, dir.display());
+    if free < min_free_bytes {
+        Err(format!("{}, {} MiB required", detail, min_free_bytes / 1024 / 1024))
+    } else {
+        Ok(detail)
+    }
+}
+
+/// Space available to this process on the volume holding `dir`
+#[cfg(unix)]
+fn free_space(dir: &Path) -> std::io::Result<u64> {
+    let stat = rustix::fs::statvfs(dir)?;
+    Ok(stat.f_bavail * stat.f_frsize)
+}
+
+/// Space available to this process on the volume holding `dir`
+#[cfg(windows)]
+fn free_space(dir: &Path) -> std::io::Result<u64> {
+    use std::os::windows::ffi::OsStrExt;
+
+    let wide: Vec<u16> = dir.as_os_str().encode_wide().chain(std::iter::once(0)).collect();
+    let mut free: u64 = 0;
+    // SAFETY: `wide` is NUL terminated and outlives the call, the totals we don't need may be null
+    let ok = unsafe {
+        windows_sys::Win32::Storage::FileSystem::GetDiskFreeSpaceExW(wide.as_ptr(), &mut free, std::ptr::null_mut(), std::ptr::null_mut())
+    };
+    if ok == 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    Ok(free)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::DatabaseState;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    fn settings() -> HealthSettings {
+        HealthSettings { db_timeout: Duration::from_secs(5), min_free_disk_bytes: 0 }
+    }
+
+    #[tokio::test]
+    async fn migrations_pass_once_applied() {
+        let db: DatabaseState = DatabaseState::in_memory().await;
+        let detail: String = check_migrations(&db.pool).await.unwrap();
+        assert_eq!(detail, format!("{} applied", MIGRATOR.iter().filter(|migration| migration.migration_type.is_up_migration()).count()));
+    }
+
+    #[tokio::test]
+    async fn pending_migrations_are_listed() {
+        let pool: Pool<Sqlite> = SqlitePoolOptions::new().max_connections(1).connect("sqlite::memory:").await.unwrap();
+        let first = MIGRATOR.iter().next().expect("at least one migration");
+        let error: String = check_migrations(&pool).await.unwrap_err();
+        assert!(error.starts_with("Pending: "), "{}", error);
+        assert!(error.contains(&format!("{}_{}", first.version, first.description)), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn unfinished_migration_fails() {
+        let db: DatabaseState = DatabaseState::in_memory().await;
+        let version: i64 = sqlx::query_scalar("UPDATE _sqlx_migrations SET success = FALSE WHERE version = (SELECT MAX(version) FROM _sqlx_migrations) RETURNING version")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(check_migrations(&db.pool).await.unwrap_err(), format!("Migration {} did not finish", version));
+    }
+
+    #[test]
+    fn database_dir_comes_from_the_url() {
+        let database_dir = |url: &str| HealthChecks::new(&settings(), url, PathBuf::from("logs")).database_dir;
+        assert_eq!(database_dir("sqlite::memory:"), None);
+        assert_eq!(database_dir("sqlite://shared.db?mode=memory&cache=shared"), None);
+        assert_eq!(database_dir("sqlite://data/app.db"), Some(PathBuf::from("data")));
+        assert_eq!(database_dir("sqlite:database.db"), Some(PathBuf::from(".")));
+        assert_eq!(database_dir("sqlite:///var/lib/app/app.db"), Some(PathBuf::from("/var/lib/app")));
+    }
+
+    #[tokio::test]
+    async fn readiness_passes_on_a_migrated_database() {
+        let _paused = maintenance::TEST_LOCK.lock().await;
+        let db: DatabaseState = DatabaseState::in_memory().await;
+        let report: ReadinessReport = HealthChecks::new(&settings(), "sqlite::memory:", std::env::temp_dir()).readiness(&db.pool).await;
+
+        assert_eq!(report.status, CheckStatus::Pass, "{:?}", report);
+        assert_eq!(report.checks.keys().copied().collect::<Vec<_>>(), ["database", "logs_disk", "migrations", "paused"]);
+    }
+}
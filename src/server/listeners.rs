@@ -0,0 +1,239 @@
+use actix_web::{web::Data, HttpRequest};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::{
+    io::{Error, Result},
+    net::{SocketAddr, TcpListener, ToSocketAddrs},
+};
+
+use super::settings::Settings;
+
+/// #### Listeners
+/// Every socket the server accepts connections on. Admin listeners serve the whole app plus the
+/// admin routes, public listeners only serve those to admin credentials (see `server::admin_auth`).
+pub struct Listeners {
+    pub public: Vec<TcpListener>,
+    pub admin: Vec<TcpListener>,
+    #[cfg(unix)]
+    pub unix_socket: Option<std::os::unix::net::UnixListener>,
+}
+
+/// Local addresses of the admin listeners, shared with the `admin_auth::admin_only` middleware
+#[derive(Clone, Debug, Default)]
+pub struct AdminListeners {
+    pub addresses: Vec<SocketAddr>,
+}
+
+/// Marks connections accepted on the unix socket, which are never treated as admin connections
+#[derive(Clone, Copy, Debug)]
+pub struct UnixSocketConnection;
+
+impl Listeners {
+    pub fn bind(settings: &Settings) -> Result<Self> {
+        let backlog: u32 = settings.http.backlog;
+        let primary: TcpListener =
+            bind_tcp(format!("{}:{}", settings.application.host, settings.application.port), backlog)
+                .expect("Failed to bind address");
+        let mut public: Vec<TcpListener> = vec![primary];
+        for address in &settings.listen.addresses {
+            public.push(bind_tcp(address, backlog).expect("Failed to bind address"));
+        }
+
+        let mut admin: Vec<TcpListener> = Vec::new();
+        for address in &settings.listen.admin_addresses {
+            admin.push(bind_tcp(address, backlog).expect("Failed to bind admin address"));
+        }
+
+        Ok(Self {
+            public,
+            admin,
+            #[cfg(unix)]
+            unix_socket: match &settings.listen.unix_socket_path {
+                Some(path) => Some(bind_unix_socket(path, settings.listen.unix_socket_mode)?),
+                None => None,
+            },
+        })
+    }
+
+    /// Port of the primary `HOST:PORT` listener, useful when binding to port 0
+    pub fn port(&self) -> Result<u16> {
+        Ok(self.public[0].local_addr()?.port())
+    }
+
+    pub fn admin_listeners(&self) -> Result<AdminListeners> {
+        let addresses: Vec<SocketAddr> = self
+            .admin
+            .iter()
+            .map(|listener| listener.local_addr())
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(AdminListeners { addresses })
+    }
+
+    /// Human readable list of every bound address, printed at startup
+    pub fn describe(&self, scheme: &str) -> Vec<String> {
+        let mut addresses: Vec<String> = Vec::new();
+        for (listener, kind) in self
+            .public
+            .iter()
+            .map(|l| (l, "public"))
+            .chain(self.admin.iter().map(|l| (l, "admin")))
+        {
+            if let Ok(address) = listener.local_addr() {
+                addresses.push(format!("{}://{} ({})", scheme, address, kind));
+            }
+        }
+        #[cfg(unix)]
+        if let Some(path) = self
+            .unix_socket
+            .as_ref()
+            .and_then(|socket| socket.local_addr().ok())
+            .and_then(|address| address.as_pathname().map(|p| p.display().to_string()))
+        {
+            addresses.push(format!("unix:{} (public)", path));
+        }
+
+        addresses
+    }
+}
+
+/// Binds a TCP listener with the configured backlog, `HttpServer::backlog` only applies to
+/// sockets actix binds itself. IPv6 sockets are IPv6 only so `0.0.0.0` and `[::]` can share a port.
+fn bind_tcp<A: ToSocketAddrs>(address: A, backlog: u32) -> Result<TcpListener> {
+    let mut last_error: Option<Error> = None;
+    for address in address.to_socket_addrs()? {
+        match bind_socket(address, backlog) {
+            Ok(listener) => return Ok(listener),
+            Err(e) => last_error = Some(e),
+        }
+    }
+
+    Err(last_error.unwrap_or_else(|| Error::other("Address did not resolve to any socket address")))
+}
+
+fn bind_socket(address: SocketAddr, backlog: u32) -> Result<TcpListener> {
+    let socket: Socket = Socket::new(Domain::for_address(address), Type::STREAM, Some(Protocol::TCP))?;
+    // Same as std on unix, lets the service restart while old connections sit in TIME_WAIT
+    #[cfg(unix)]
+    socket.set_reuse_address(true)?;
+    if address.is_ipv6() {
+        socket.set_only_v6(true)?;
+    }
+    socket.bind(&address.into())?;
+    socket.listen(i32::try_from(backlog).unwrap_or(i32::MAX))?;
+
+    Ok(socket.into())
+}
+
+/// Binds the unix socket, removing a stale socket file left behind by a previous run,
+/// and applies the configured file permissions so nginx can connect to it.
+#[cfg(unix)]
+fn bind_unix_socket(path: &std::path::Path, mode: u32) -> Result<std::os::unix::net::UnixListener> {
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+    if let Ok(metadata) = std::fs::symlink_metadata(path) {
+        if metadata.file_type().is_socket() {
+            std::fs::remove_file(path)?;
+        }
+    }
+    let listener: std::os::unix::net::UnixListener = std::os::unix::net::UnixListener::bind(path)?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))?;
+
+    Ok(listener)
+}
+
+/// Tags unix socket connections so the admin check can tell them apart from TCP connections,
+/// actix reports a placeholder local address for them.
+pub fn tag_connection(connection: &dyn std::any::Any, extensions: &mut actix_web::dev::Extensions) {
+    #[cfg(unix)]
+    if connection.downcast_ref::<actix_web::rt::net::UnixStream>().is_some() {
+        extensions.insert(UnixSocketConnection);
+    }
+    #[cfg(not(unix))]
+    let _ = (connection, extensions);
+}
+
+/// `true` when the request arrived on one of the admin listeners, always `false` when none are configured
+pub fn on_admin_listener(req: &HttpRequest) -> bool {
+    req.app_data::<Data<AdminListeners>>().is_some_and(|admin| {
+        req.conn_data::<UnixSocketConnection>().is_none() && admin.addresses.contains(&req.app_config().local_addr())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    /// Address `TestRequest` reports as the local address of the connection
+    const TEST_LOCAL_ADDR: &str = "127.0.0.1:8080";
+
+    fn request(admin_addresses: &[&str]) -> HttpRequest {
+        let admin: AdminListeners = AdminListeners { addresses: admin_addresses.iter().map(|address| address.parse().unwrap()).collect() };
+        TestRequest::get().app_data(Data::new(admin)).to_http_request()
+    }
+
+    #[test]
+    fn admin_listener_is_matched_by_local_address() {
+        assert!(on_admin_listener(&request(&[TEST_LOCAL_ADDR])));
+        assert!(!on_admin_listener(&request(&["127.0.0.1:9999"])));
+        assert!(!on_admin_listener(&request(&[])));
+        assert!(!on_admin_listener(&TestRequest::get().to_http_request()));
+    }
+
+    #[test]
+    fn bound_listeners_are_described_and_shared() {
+        let public: TcpListener = bind_tcp("127.0.0.1:0", 16).unwrap();
+        let admin: TcpListener = bind_tcp("127.0.0.1:0", 16).unwrap();
+        let admin_address: SocketAddr = admin.local_addr().unwrap();
+        let listeners: Listeners = Listeners {
+            public: vec![public],
+            admin: vec![admin],
+            #[cfg(unix)]
+            unix_socket: None,
+        };
+
+        assert_ne!(listeners.port().unwrap(), 0);
+        assert_eq!(listeners.admin_listeners().unwrap().addresses, [admin_address]);
+        let described: Vec<String> = listeners.describe("https");
+        assert_eq!(described.len(), 2);
+        assert_eq!(described[0], format!("https://127.0.0.1:{} (public)", listeners.port().unwrap()));
+        assert_eq!(described[1], format!("https://{} (admin)", admin_address));
+    }
+
+    #[test]
+    fn ipv4_and_ipv6_can_share_a_port() {
+        let ipv4: TcpListener = bind_tcp("127.0.0.1:0", 16).unwrap();
+        let port: u16 = ipv4.local_addr().unwrap().port();
+        // Hosts without IPv6 can't run this part
+        if std::net::TcpListener::bind("[::1]:0").is_err() {
+            return;
+        }
+        assert!(bind_tcp(format!("[::1]:{}", port), 16).is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn stale_unix_socket_is_replaced() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path: std::path::PathBuf = std::env::temp_dir().join(format!("listeners-test-{}.sock", uuid::Uuid::new_v4().simple()));
+        // Left behind like after a crash, the file stays when the listener is dropped
+        drop(bind_unix_socket(&path, 0o600).unwrap());
+        let listener: std::os::unix::net::UnixListener = bind_unix_socket(&path, 0o660).unwrap();
+
+        assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o660);
+        drop(listener);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn regular_file_is_not_removed_for_the_socket() {
+        let path: std::path::PathBuf = std::env::temp_dir().join(format!("listeners-test-{}.sock", uuid::Uuid::new_v4().simple()));
+        std::fs::write(&path, "not a socket").unwrap();
+
+        assert!(bind_unix_socket(&path, 0o660).is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "not a socket");
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,244 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::{
+    collections::VecDeque,
+    fmt::{self, Write},
+    sync::{Mutex, OnceLock},
+};
+use tokio::sync::broadcast;
+use tracing::{
+    field::{Field, Visit},
+    span, Event, Level, Subscriber,
+};
+use tracing_log::NormalizeEvent;
+use tracing_subscriber::{layer::Context, registry::LookupSpan, Layer};
+use utoipa::ToSchema;
+
+/// Live subscribers that fall further behind than this miss lines and are told how many
+const SUBSCRIBER_BACKLOG: usize = 1024;
+
+static LOG_BUFFER: OnceLock<LogBuffer> = OnceLock::new();
+
+/// One event as kept in memory and sent to live subscribers
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct LogRecord {
+    /// Increases by one per event, also the SSE event id
+    pub seq: u64,
+    pub timestamp: DateTime<Utc>,
+    pub level: String,
+    pub target: String,
+    /// The message followed by the other fields as `key=value`
+    pub message: String,
+    /// Of the request the event was logged in, see `server::request_id`
+    pub request_id: Option<String>,
+}
+
+/// Which records a search or a live subscriber wants
+#[derive(Clone, Debug, Default)]
+pub struct RecordFilter {
+    /// Minimum level, `warn` also gets errors
+    pub level: Option<Level>,
+    /// Prefix of the target, e.g. `actix_svelte::server`
+    pub target: Option<String>,
+    /// Case insensitive text in the message, target or request id
+    pub text: Option<String>,
+}
+
+impl RecordFilter {
+    pub fn matches(&self, record: &LogRecord) -> bool {
+        let request_id: &str = record.request_id.as_deref().unwrap_or_default();
+        self.matches_level(Some(&record.level))
+            && self.matches_target(Some(&record.target))
+            && self.matches_text(&[&record.message, &record.target, request_id])
+    }
+
+    /// Lines without a level only match without a level filter, the same goes for the target
+    pub fn matches_level(&self, level: Option<&str>) -> bool {
+        self.level.is_none_or(|min_level| level.and_then(|level| level.parse::<Level>().ok()).is_some_and(|level| level <= min_level))
+    }
+
+    pub fn matches_target(&self, target: Option<&str>) -> bool {
+        self.target.as_deref().is_none_or(|prefix| target.is_some_and(|target| target.starts_with(prefix)))
+    }
+
+    pub fn matches_text(&self, haystacks: &[&str]) -> bool {
+        self.text.as_deref().is_none_or(|text| {
+            let text: String = text.to_lowercase();
+            haystacks.iter().any(|haystack| haystack.to_lowercase().contains(&text))
+        })
+    }
+}
+
+/// #### Log Buffer
+/// The latest events that passed the log filter, kept in memory for the admin log console, and a
+/// channel that passes every new one on to the live subscribers.
+pub struct LogBuffer {
+    capacity: usize,
+    records: Mutex<Records>,
+    sender: broadcast::Sender<LogRecord>,
+}
+
+struct Records {
+    next_seq: u64,
+    lines: VecDeque<LogRecord>,
+}
+
+impl LogBuffer {
+    /// The records still in memory that match, oldest first, at most `limit` of the newest ones
+    pub fn recent(&self, filter: &RecordFilter, after_seq: Option<u64>, limit: usize) -> Vec<LogRecord> {
+        let records = self.lock_records();
+        let mut matching: Vec<LogRecord> = records
+            .lines
+            .iter()
+            .rev()
+            .filter(|record| after_seq.is_none_or(|after_seq| record.seq > after_seq) && filter.matches(record))
+            .take(limit)
+            .cloned()
+            .collect();
+        matching.reverse();
+        matching
+    }
+
+    /// New records from now on, subscribe before reading `recent` so nothing falls in between
+    pub fn subscribe(&self) -> broadcast::Receiver<LogRecord> {
+        self.sender.subscribe()
+    }
+
+    fn push(&self, timestamp: DateTime<Utc>, level: &Level, target: &str, message: String, request_id: Option<String>) {
+        let record: LogRecord = {
+            let mut records = self.lock_records();
+            let record: LogRecord = LogRecord {
+                seq: records.next_seq,
+                timestamp,
+                level: level.to_string(),
+                target: target.to_string(),
+                message,
+                request_id,
+            };
+            records.next_seq += 1;
+            if records.lines.len() == self.capacity {
+                records.lines.pop_front();
+            }
+            records.lines.push_back(record.clone());
+            record
+        };
+        // Fails when nobody is watching, which is fine
+        let _ = self.sender.send(record);
+    }
+
+    fn lock_records(&self) -> std::sync::MutexGuard<'_, Records> {
+        self.records.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// `None` until `telemetry::setup_logging` ran
+pub fn log_buffer() -> Option<&'static LogBuffer> {
+    LOG_BUFFER.get()
+}
+
+/// Creates the buffer and the layer filling it, the layer gets the same filter as the log files
+pub fn layer(capacity: usize) -> LogBufferLayer {
+    let capacity: usize = capacity.max(1);
+    let _ = LOG_BUFFER.set(LogBuffer {
+        capacity,
+        records: Mutex::new(Records { next_seq: 1, lines: VecDeque::with_capacity(capacity) }),
+        sender: broadcast::channel(SUBSCRIBER_BACKLOG).0,
+    });
+
+    LogBufferLayer
+}
+
+pub struct LogBufferLayer;
+
+/// Kept on `request` spans so the events inside them know their request id
+struct SpanRequestId(String);
+
+impl<S> Layer<S> for LogBufferLayer
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        let mut visitor: RequestIdVisitor = RequestIdVisitor(None);
+        attrs.record(&mut visitor);
+        if let (Some(request_id), Some(span)) = (visitor.0, ctx.span(id)) {
+            span.extensions_mut().insert(SpanRequestId(request_id));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let Some(buffer) = log_buffer() else {
+            return;
+        };
+        let mut visitor: MessageVisitor = MessageVisitor::default();
+        event.record(&mut visitor);
+        let request_id: Option<String> = ctx
+            .event_scope(event)
+            .and_then(|scope| scope.from_root().find_map(|span| span.extensions().get::<SpanRequestId>().map(|id| id.0.clone())));
+        // Records of the `log` crate, e.g. the access log, carry their real target in fields
+        let normalized = event.normalized_metadata();
+        let metadata = normalized.as_ref().unwrap_or_else(|| event.metadata());
+
+        buffer.push(Utc::now(), metadata.level(), metadata.target(), visitor.finish(), request_id);
+    }
+}
+
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+    fields: String,
+}
+
+impl MessageVisitor {
+    fn finish(self) -> String {
+        match (self.message.is_empty(), self.fields.is_empty()) {
+            (_, true) => self.message,
+            (true, false) => self.fields,
+            (false, false) => format!("{} {}", self.message, self.fields),
+        }
+    }
+
+    fn push_field(&mut self, name: &str, value: fmt::Arguments<'_>) {
+        // Fields of `log` records bridged by `tracing-log`, already in the metadata
+        if name.starts_with("log.") {
+            return;
+        }
+        if !self.fields.is_empty() {
+            self.fields.push(' ');
+        }
+        let _ = write!(self.fields, "{}={}", name, value);
+    }
+}
+
+impl Visit for MessageVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "message" => self.message.push_str(value),
+            name => self.push_field(name, format_args!("{:?}", value)),
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        match field.name() {
+            "message" => {
+                let _ = write!(self.message, "{:?}", value);
+            }
+            name => self.push_field(name, format_args!("{:?}", value)),
+        }
+    }
+}
+
+struct RequestIdVisitor(Option<String>);
+
+impl Visit for RequestIdVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "request_id" {
+            self.0 = Some(value.to_string());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "request_id" {
+            self.0 = Some(format!("{:?}", value));
+        }
+    }
+}
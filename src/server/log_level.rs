@@ -0,0 +1,199 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::{Mutex, OnceLock},
+    time::Duration,
+};
+use tracing_subscriber::{reload, EnvFilter, Registry};
+use utoipa::ToSchema;
+
+/// Handle to the `EnvFilter` installed by `telemetry::setup_logging`
+pub type LogFilterHandle = reload::Handle<EnvFilter, Registry>;
+
+static LOG_FILTER: OnceLock<LogFilterHandle> = OnceLock::new();
+
+static STATE: Mutex<FilterState> = Mutex::new(FilterState { default: String::new(), active_override: None, generation: 0 });
+
+struct FilterState {
+    /// `RUST_LOG`, in effect whenever no override is
+    default: String,
+    active_override: Option<LogFilterOverride>,
+    /// Bumped on every change, so the expiry of a replaced override does nothing
+    generation: u64,
+}
+
+/// Directives set through the admin API in place of `RUST_LOG`
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct LogFilterOverride {
+    pub directives: String,
+    /// Admin user or token and client address of the request that set it
+    pub set_by: String,
+    pub set_at: DateTime<Utc>,
+    /// `RUST_LOG` is back in effect at this time
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// The log filter in effect and where it comes from
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct LogFilterStatus {
+    /// `EnvFilter` directives in effect
+    pub directives: String,
+    /// `RUST_LOG`, restored when the override is reverted or expires
+    pub default: String,
+    #[serde(rename = "override")]
+    pub active_override: Option<LogFilterOverride>,
+}
+
+/// Body of `PUT /api/admin/log-filter`
+#[derive(Clone, Debug, Deserialize, ToSchema)]
+pub struct LogFilterChange {
+    /// `EnvFilter` directives, e.g. `info,actix_svelte::server=debug,sqlx=trace`
+    pub directives: String,
+    /// Reverts to `RUST_LOG` after this many minutes, the override stays until reverted without it
+    pub expires_in_minutes: Option<u64>,
+}
+
+/// Registers the reloadable log filter and the directives it started with, binaries that don't
+/// set up logging simply never call this
+pub fn install_log_filter(handle: LogFilterHandle, directives: String) {
+    if LOG_FILTER.set(handle).is_ok() {
+        lock_state().default = directives;
+    }
+}
+
+pub fn log_filter() -> Option<&'static LogFilterHandle> {
+    LOG_FILTER.get()
+}
+
+pub fn status() -> LogFilterStatus {
+    let state = lock_state();
+    LogFilterStatus {
+        directives: state.active_override.as_ref().map_or(&state.default, |active| &active.directives).clone(),
+        default: state.default.clone(),
+        active_override: state.active_override.clone(),
+    }
+}
+
+/// A new `RUST_LOG` from a settings reload, only applied right away when no override is active
+pub fn set_default(directives: &str) -> Result<(), String> {
+    let filter: EnvFilter = EnvFilter::try_new(directives).map_err(|e| e.to_string())?;
+    let mut state = lock_state();
+    if state.active_override.is_none() {
+        apply(filter)?;
+    }
+    state.default = directives.to_string();
+
+    Ok(())
+}
+
+/// Replaces the filter until `revert` or the expiry, `set_by` ends up in the logs
+pub fn set_override(change: &LogFilterChange, set_by: &str) -> Result<LogFilterStatus, String> {
+    let directives: &str = change.directives.trim();
+    if directives.is_empty() {
+        return Err("directives must not be empty".to_string());
+    }
+    let filter: EnvFilter = EnvFilter::try_new(directives).map_err(|e| e.to_string())?;
+    let expires_in: Option<Duration> = change
+        .expires_in_minutes
+        .filter(|minutes| *minutes > 0)
+        .map(|minutes| Duration::from_secs(minutes * 60));
+
+    let generation: u64 = {
+        let mut state = lock_state();
+        let previous: String = state.active_override.as_ref().map_or(&state.default, |active| &active.directives).clone();
+        // Nothing is recorded for a filter that didn't take effect
+        apply(filter)?;
+        let now: DateTime<Utc> = Utc::now();
+        state.active_override = Some(LogFilterOverride {
+            directives: directives.to_string(),
+            set_by: set_by.to_string(),
+            set_at: now,
+            expires_at: expires_in.and_then(|expires_in| chrono::Duration::from_std(expires_in).ok()).map(|expires_in| now + expires_in),
+        });
+        state.generation += 1;
+        // Goes through the new filter, which lets `backend` warnings through unless it silences them on purpose
+        tracing::event!(
+            target: "backend",
+            tracing::Level::WARN,
+            "Log filter changed by {} from '{}' to '{}', expires {}",
+            set_by,
+            previous,
+            directives,
+            expires_in.map_or("never".to_string(), |expires_in| format!("in {} min", expires_in.as_secs() / 60))
+        );
+        state.generation
+    };
+
+    if let Some(expires_in) = expires_in {
+        tokio::spawn(async move {
+            tokio::time::sleep(expires_in).await;
+            if lock_state().generation == generation {
+                revert("its expiry");
+            }
+        });
+    }
+
+    Ok(status())
+}
+
+/// Goes back to `RUST_LOG`, does nothing without an override
+pub fn revert(reverted_by: &str) -> LogFilterStatus {
+    let mut state = lock_state();
+    if let Some(active) = state.active_override.clone() {
+        // The override stays in the status for as long as it stays in effect
+        match EnvFilter::try_new(&state.default).map_err(|e| e.to_string()).and_then(apply) {
+            Ok(()) => {
+                state.active_override = None;
+                state.generation += 1;
+                tracing::event!(
+                    target: "backend",
+                    tracing::Level::WARN,
+                    "Log filter reverted by {} from '{}' (set by {}) to '{}'",
+                    reverted_by,
+                    active.directives,
+                    active.set_by,
+                    state.default
+                );
+            }
+            Err(e) => tracing::event!(target: "backend", tracing::Level::ERROR, "Failed to revert the log filter to '{}': {}", state.default, e),
+        }
+    }
+    drop(state);
+
+    status()
+}
+
+fn apply(filter: EnvFilter) -> Result<(), String> {
+    match log_filter() {
+        Some(handle) => handle.reload(filter).map_err(|e| e.to_string()),
+        None => Err("logging was not set up with a reloadable filter".to_string()),
+    }
+}
+
+fn lock_state() -> std::sync::MutexGuard<'static, FilterState> {
+    STATE.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failed_override_leaves_no_trace() {
+        // Tests don't install the reloadable filter, so applying always fails here
+        let change: LogFilterChange = LogFilterChange { directives: "debug".to_string(), expires_in_minutes: Some(5) };
+        let generation: u64 = lock_state().generation;
+
+        assert!(set_override(&change, "tester (127.0.0.1)").is_err());
+        assert!(status().active_override.is_none());
+        assert_eq!(lock_state().generation, generation);
+    }
+
+    #[test]
+    fn invalid_directives_are_rejected() {
+        let change: LogFilterChange = LogFilterChange { directives: "info,[".to_string(), expires_in_minutes: None };
+        assert!(set_override(&change, "tester").is_err());
+        let empty: LogFilterChange = LogFilterChange { directives: "  ".to_string(), expires_in_minutes: None };
+        assert!(set_override(&empty, "tester").is_err());
+    }
+}
@@ -0,0 +1,203 @@
+use chrono::{DateTime, Utc};
+use flate2::read::GzDecoder;
+use serde::Serialize;
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, BufRead, BufReader, Read},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+use utoipa::ToSchema;
+
+use super::{log_buffer::RecordFilter, settings::LoggingSettings};
+use crate::log_rotation;
+
+/// Name of the main log in listings and searches, the other logs are named after their target file
+pub const MAIN_LOG: &str = "main";
+
+/// A file in the log directory
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct LogFileEntry {
+    pub name: String,
+    /// `main` or the name of the target file it belongs to
+    pub log: String,
+    pub size: u64,
+    pub modified: DateTime<Utc>,
+    pub compressed: bool,
+}
+
+/// A line of a log file that matched a search
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct LogLine {
+    pub file: String,
+    /// Missing for lines that don't start with a timestamp, e.g. continued or `pretty` lines
+    pub timestamp: Option<DateTime<Utc>>,
+    pub level: Option<String>,
+    pub target: Option<String>,
+    /// The line as written
+    pub text: String,
+}
+
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct LogSearchResult {
+    /// Oldest first, the newest `limit` matches when there were more
+    pub lines: Vec<LogLine>,
+    /// More lines matched than were returned, narrow the search to see them
+    pub truncated: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct LogSearch {
+    /// `main` when not given
+    pub log: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub filter: RecordFilter,
+    pub limit: usize,
+}
+
+/// Every file of the main log and the target files, newest first
+pub fn list_files(settings: &LoggingSettings) -> io::Result<Vec<LogFileEntry>> {
+    let logs = std::iter::once((MAIN_LOG, settings.file_prefix.as_str()))
+        .chain(settings.target_files.iter().map(|target| (target.name.as_str(), target.name.as_str())));
+    let mut entries: Vec<LogFileEntry> = Vec::new();
+    for (log, prefix) in logs {
+        for (path, modified) in log_rotation::log_files(settings, prefix)? {
+            entries.push(LogFileEntry {
+                name: file_name(&path),
+                log: log.to_string(),
+                size: path.metadata()?.len(),
+                modified: modified.into(),
+                compressed: is_compressed(&path),
+            });
+        }
+    }
+    entries.sort_by_key(|entry| std::cmp::Reverse(entry.modified));
+
+    Ok(entries)
+}
+
+/// Reads the files of one log, gzipped ones included, oldest first. Files last written before
+/// `from` are skipped without reading them.
+pub fn search(settings: &LoggingSettings, search: &LogSearch) -> Result<LogSearchResult, String> {
+    let log: &str = search.log.as_deref().unwrap_or(MAIN_LOG);
+    let prefix: &str = match log {
+        MAIN_LOG => settings.file_prefix.as_str(),
+        target => match settings.target_files.iter().find(|file| file.name == target) {
+            Some(file) => file.name.as_str(),
+            None => return Err(format!("Unknown log '{}'", target)),
+        },
+    };
+    let mut files: Vec<(PathBuf, SystemTime)> = log_rotation::log_files(settings, prefix).map_err(|e| e.to_string())?;
+    files.reverse();
+
+    let mut lines: VecDeque<LogLine> = VecDeque::with_capacity(search.limit);
+    let mut truncated: bool = false;
+    for (path, modified) in files {
+        if search.from.is_some_and(|from| DateTime::<Utc>::from(modified) < from) {
+            continue;
+        }
+        let file: String = file_name(&path);
+        let reader: Box<dyn Read> = match File::open(&path) {
+            Ok(opened) if is_compressed(&path) => Box::new(GzDecoder::new(opened)),
+            Ok(opened) => Box::new(opened),
+            // Pruned since it was listed
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(format!("Failed to read {}: {}", file, e)),
+        };
+        let mut reader: BufReader<Box<dyn Read>> = BufReader::new(reader);
+        let mut buf: Vec<u8> = Vec::new();
+        loop {
+            buf.clear();
+            match reader.read_until(b'\n', &mut buf) {
+                Ok(0) => break,
+                Ok(_) => {}
+                // A gzipped file that is still being written, or cut short
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(format!("Failed to read {}: {}", file, e)),
+            }
+            let line: LogLine = parse_line(&file, String::from_utf8_lossy(&buf).trim_end());
+            if line.text.is_empty() || !matches(&line, search) {
+                continue;
+            }
+            if lines.len() == search.limit {
+                lines.pop_front();
+                truncated = true;
+            }
+            lines.push_back(line);
+        }
+    }
+
+    Ok(LogSearchResult { lines: lines.into(), truncated })
+}
+
+fn matches(line: &LogLine, search: &LogSearch) -> bool {
+    let in_range: bool = match (search.from, search.to) {
+        (None, None) => true,
+        _ => line.timestamp.is_some_and(|timestamp| {
+            search.from.is_none_or(|from| timestamp >= from) && search.to.is_none_or(|to| timestamp <= to)
+        }),
+    };
+
+    in_range
+        && search.filter.matches_level(line.level.as_deref())
+        && search.filter.matches_target(line.target.as_deref())
+        && search.filter.matches_text(&[&line.text])
+}
+
+/// Understands the `json` lines and the `full` and `compact` text lines, whose timestamp, level,
+/// span context and target come in that order
+fn parse_line(file: &str, text: &str) -> LogLine {
+    let mut line: LogLine = LogLine {
+        file: file.to_string(),
+        timestamp: None,
+        level: None,
+        target: None,
+        text: text.to_string(),
+    };
+
+    if text.starts_with('{') {
+        if let Ok(json) = serde_json::from_str::<serde_json::Value>(text) {
+            line.timestamp = json["timestamp"].as_str().and_then(parse_timestamp);
+            line.level = json["level"].as_str().map(str::to_string);
+            line.target = json["target"].as_str().map(str::to_string);
+        }
+        return line;
+    }
+
+    let Some((timestamp, rest)) = text.trim_start().split_once(char::is_whitespace) else {
+        return line;
+    };
+    let Some(timestamp) = parse_timestamp(timestamp) else {
+        return line;
+    };
+    let Some((level, rest)) = rest.trim_start().split_once(char::is_whitespace) else {
+        return line;
+    };
+    if level.parse::<tracing::Level>().is_err() {
+        return line;
+    }
+    line.timestamp = Some(timestamp);
+    line.level = Some(level.to_string());
+    // Span contexts like `request{request_id=...}` come first, the target is the first plain module path
+    line.target = rest
+        .trim_start()
+        .split(": ")
+        .find(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':'))
+        .map(str::to_string);
+
+    line
+}
+
+fn parse_timestamp(text: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(text).ok().map(|timestamp| timestamp.with_timezone(&Utc))
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default()
+}
+
+fn is_compressed(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension == "gz")
+}
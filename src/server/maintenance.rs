@@ -0,0 +1,224 @@
+use actix_web::{
+    body::{BoxBody, EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::header,
+    middleware::Next,
+    web::Data,
+    HttpResponse,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::{atomic::Ordering, RwLock},
+    time::Duration,
+};
+use utoipa::ToSchema;
+
+use super::{bootstrap, listeners, settings::MaintenanceSettings};
+use actix_svelte::PAUSED;
+
+/// What visitors are told while the service is paused
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq, Serialize, ToSchema)]
+pub struct MaintenanceNotice {
+    pub message: Option<String>,
+    /// Maintenance ends by itself at this time, also used for `Retry-After`
+    pub until: Option<DateTime<Utc>>,
+}
+
+/// Held by tests that pause the service, `PAUSED` is shared by every test in the binary
+#[cfg(test)]
+pub static TEST_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// Only read while `PAUSED` is set
+static NOTICE: RwLock<MaintenanceNotice> = RwLock::new(MaintenanceNotice { message: None, until: None });
+
+/// Starts maintenance, or replaces the notice when already paused. `source` ends up in the logs.
+pub fn pause(notice: MaintenanceNotice, source: &str) {
+    tracing::event!(target: "backend", tracing::Level::INFO, "Service PAUSED by {} until {:?}: {:?}", source, notice.until, notice.message);
+    *NOTICE.write().expect("maintenance notice lock poisoned") = notice;
+    PAUSED.store(true, Ordering::SeqCst);
+}
+
+pub fn resume(source: &str) {
+    if PAUSED.swap(false, Ordering::SeqCst) {
+        tracing::event!(target: "backend", tracing::Level::INFO, "Service UNPAUSED by {}", source);
+    }
+}
+
+/// The notice while in maintenance, `None` otherwise. Ends maintenance whose scheduled end has passed.
+pub fn current() -> Option<MaintenanceNotice> {
+    if !PAUSED.load(Ordering::SeqCst) {
+        return None;
+    }
+    let notice: MaintenanceNotice = NOTICE.read().expect("maintenance notice lock poisoned").clone();
+    if notice.until.is_some_and(|until| until <= Utc::now()) {
+        resume("its scheduled end");
+        return None;
+    }
+
+    Some(notice)
+}
+
+pub fn is_active() -> bool {
+    current().is_some()
+}
+
+/// Which requests get through during maintenance, built from `MaintenanceSettings`
+#[derive(Clone, Debug)]
+pub struct MaintenancePolicy {
+    base_path: String,
+    allow_paths: Vec<String>,
+    retry_after: Duration,
+}
+
+impl MaintenancePolicy {
+    pub fn new(settings: &MaintenanceSettings, base_path: &str) -> Self {
+        Self {
+            base_path: base_path.to_string(),
+            allow_paths: settings.allow_paths.clone(),
+            retry_after: settings.retry_after,
+        }
+    }
+
+    /// `/api/health` allows `/api/health/ready` but not `/api/healthy`
+    fn allows(&self, path: &str) -> bool {
+        let path: &str = path.strip_prefix(self.base_path.as_str()).unwrap_or(path);
+        self.allow_paths
+            .iter()
+            .any(|allowed| path.strip_prefix(allowed.as_str()).is_some_and(|rest| rest.is_empty() || rest.starts_with('/')))
+    }
+}
+
+/// #### Maintenance
+/// Answers `503` while the service is paused, except for the allow-listed paths and the admin
+/// listeners, so operators can still check on and use the app while visitors are kept out.
+pub async fn enforce(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody, BoxBody>>, actix_web::Error> {
+    let Some(notice) = current() else {
+        return next.call(req).await.map(ServiceResponse::map_into_left_body);
+    };
+    let Some(policy) = req.app_data::<Data<MaintenancePolicy>>().cloned() else {
+        return next.call(req).await.map(ServiceResponse::map_into_left_body);
+    };
+    if policy.allows(req.path()) || listeners::on_admin_listener(req.request()) {
+        return next.call(req).await.map(ServiceResponse::map_into_left_body);
+    }
+
+    let retry_after: u64 = match notice.until {
+        Some(until) => (until - Utc::now()).num_seconds().max(1) as u64,
+        None => policy.retry_after.as_secs(),
+    };
+    let mut response = HttpResponse::ServiceUnavailable();
+    response
+        .insert_header((header::RETRY_AFTER, retry_after.to_string()))
+        .insert_header((header::CACHE_CONTROL, "no-store"));
+    // Page loads of the SPA get a page to look at, fetches get something they can parse
+    let wants_html: bool = req
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains("text/html"));
+    let response: HttpResponse = if wants_html {
+        let nonce: String = bootstrap::csp_nonce(req.request());
+        response.content_type("text/html; charset=utf-8").body(maintenance_page(&notice, &nonce))
+    } else {
+        response.json(serde_json::json!({
+            "error": "Service is in maintenance",
+            "message": notice.message,
+            "until": notice.until,
+        }))
+    };
+
+    Ok(req.into_response(response).map_into_right_body())
+}
+
+fn maintenance_page(notice: &MaintenanceNotice, nonce: &str) -> String {
+    let message: String = escape_html(notice.message.as_deref().unwrap_or("The service is down for maintenance."));
+    let until: String = match notice.until {
+        Some(until) => format!("<p>Expected back at {}.</p>", until.format("%Y-%m-%d %H:%M UTC")),
+        None => "<p>Please try again in a few minutes.</p>".to_string(),
+    };
+
+    format!(
+        r#"<!doctype html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>Maintenance</title>
+<style nonce="{nonce}">body {{ font-family: system-ui, sans-serif; max-width: 36rem; margin: 4rem auto; padding: 0 1rem; color: #333; }}</style>
+</head>
+<body>
+<h1>Down for maintenance</h1>
+<p>{message}</p>
+{until}
+</body>
+</html>
+"#
+    )
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{
+        http::StatusCode,
+        middleware::from_fn,
+        test::{call_service, init_service, TestRequest},
+        web, App,
+    };
+
+    fn default_policy(base_path: &str) -> MaintenancePolicy {
+        let settings: MaintenanceSettings = MaintenanceSettings {
+            start_paused: false,
+            allow_paths: MaintenanceSettings::parse_allow_paths(MaintenanceSettings::DEFAULT_ALLOW_PATHS),
+            retry_after: Duration::from_secs(300),
+        };
+        MaintenancePolicy::new(&settings, base_path)
+    }
+
+    #[test]
+    fn admins_can_sign_in_by_default() {
+        let policy: MaintenancePolicy = default_policy("");
+        assert!(policy.allows("/api/auth/login"));
+        assert!(policy.allows("/api/auth/protected"));
+        assert!(policy.allows("/api/admin/log-filter"));
+        assert!(!policy.allows("/api/auth/register"));
+        assert!(!policy.allows("/api/auth/loginx"));
+        assert!(!policy.allows("/api/counter"));
+    }
+
+    #[test]
+    fn allow_paths_are_below_the_base_path() {
+        let policy: MaintenancePolicy = default_policy("/cleanroom");
+        assert!(policy.allows("/cleanroom/api/auth/login"));
+        assert!(!policy.allows("/cleanroom/api/auth/register"));
+    }
+
+    #[actix_web::test]
+    async fn sign_in_gets_through_maintenance() {
+        let app = init_service(
+            App::new()
+                .app_data(Data::new(default_policy("")))
+                .wrap(from_fn(enforce))
+                .route("/api/auth/login", web::post().to(HttpResponse::Ok))
+                .route("/api/counter", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        let _paused = TEST_LOCK.lock().await;
+        pause(MaintenanceNotice::default(), "test");
+        let login: StatusCode = call_service(&app, TestRequest::post().uri("/api/auth/login").to_request()).await.status();
+        let counter: StatusCode = call_service(&app, TestRequest::get().uri("/api/counter").to_request()).await.status();
+        resume("test");
+
+        assert_eq!(login, StatusCode::OK);
+        assert_eq!(counter, StatusCode::SERVICE_UNAVAILABLE);
+    }
+}
@@ -0,0 +1,421 @@
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    middleware::Next,
+};
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{
+        atomic::{AtomicI64, AtomicU64, Ordering},
+        Mutex, OnceLock,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use super::build_info::build_info;
+
+/// Prometheus' default buckets, in seconds
+const LATENCY_BUCKETS: [f64; 11] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+/// Requests no route matched, e.g. unknown paths proxied to Vite, share one label value
+const UNMATCHED_ROUTE: &str = "unmatched";
+
+#[derive(Clone, Debug, Default)]
+struct Histogram {
+    /// Observations per bucket, not cumulative, the last entry counts everything above the largest bucket
+    counts: [u64; LATENCY_BUCKETS.len() + 1],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, seconds: f64) {
+        let bucket: usize = LATENCY_BUCKETS.iter().position(|bound| seconds <= *bound).unwrap_or(LATENCY_BUCKETS.len());
+        self.counts[bucket] += 1;
+        self.sum += seconds;
+        self.count += 1;
+    }
+
+    fn write(&self, out: &mut String, name: &str, labels: &str) {
+        let separator: &str = if labels.is_empty() { "" } else { "," };
+        let mut cumulative: u64 = 0;
+        for (bound, count) in LATENCY_BUCKETS.iter().zip(self.counts) {
+            cumulative += count;
+            let _ = writeln!(out, "{}_bucket{{{}{}le=\"{}\"}} {}", name, labels, separator, bound, cumulative);
+        }
+        let _ = writeln!(out, "{}_bucket{{{}{}le=\"+Inf\"}} {}", name, labels, separator, self.count);
+        let labels: String = if labels.is_empty() { String::new() } else { format!("{{{}}}", labels) };
+        let _ = writeln!(out, "{}_sum{} {}", name, labels, self.sum);
+        let _ = writeln!(out, "{}_count{} {}", name, labels, self.count);
+    }
+}
+
+/// Labels of the HTTP series: method, route template and status
+type HttpKey = (String, String, u16);
+
+/// #### Metrics
+/// Collected in-process and rendered in the Prometheus text format by `GET /metrics`
+pub struct Metrics {
+    started_at: SystemTime,
+    http: Mutex<BTreeMap<HttpKey, Histogram>>,
+    in_flight: AtomicI64,
+    db_acquire: Mutex<Histogram>,
+    db_acquire_errors: AtomicU64,
+    logins: Mutex<BTreeMap<&'static str, u64>>,
+}
+
+pub fn metrics() -> &'static Metrics {
+    static METRICS: OnceLock<Metrics> = OnceLock::new();
+    METRICS.get_or_init(|| Metrics {
+        started_at: SystemTime::now(),
+        http: Mutex::new(BTreeMap::new()),
+        in_flight: AtomicI64::new(0),
+        db_acquire: Mutex::new(Histogram::default()),
+        db_acquire_errors: AtomicU64::new(0),
+        logins: Mutex::new(BTreeMap::new()),
+    })
+}
+
+/// Numbers read when `/metrics` is scraped instead of being tracked as they change
+pub struct PoolGauges {
+    pub size: u32,
+    pub idle: usize,
+    pub max: u32,
+}
+
+/// Login outcomes, `failure` is a wrong username or password, `error` a server side problem
+#[derive(Clone, Copy, Debug)]
+pub enum LoginResult {
+    Success,
+    Failure,
+    Error,
+}
+
+impl Metrics {
+    pub fn observe_request(&self, method: &str, route: &str, status: u16, elapsed: Duration) {
+        self.http
+            .lock()
+            .expect("metrics lock poisoned")
+            .entry((method.to_string(), route.to_string(), status))
+            .or_default()
+            .observe(elapsed.as_secs_f64());
+    }
+
+    pub fn observe_db_acquire(&self, elapsed: Duration, succeeded: bool) {
+        self.db_acquire.lock().expect("metrics lock poisoned").observe(elapsed.as_secs_f64());
+        if !succeeded {
+            self.db_acquire_errors.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn record_login(&self, result: LoginResult) {
+        let label: &'static str = match result {
+            LoginResult::Success => "success",
+            LoginResult::Failure => "failure",
+            LoginResult::Error => "error",
+        };
+        *self.logins.lock().expect("metrics lock poisoned").entry(label).or_default() += 1;
+    }
+
+    /// Every metric in the Prometheus text exposition format
+    pub fn render(&self, pool: &PoolGauges, app_counter: i32) -> String {
+        let mut out: String = String::new();
+
+        header(&mut out, "http_requests_total", "counter", "HTTP requests by method, route template and status");
+        let http = self.http.lock().expect("metrics lock poisoned").clone();
+        for ((method, route, status), histogram) in &http {
+            let _ = writeln!(out, "http_requests_total{{{}}} {}", http_labels(method, route, *status), histogram.count);
+        }
+        header(&mut out, "http_request_duration_seconds", "histogram", "Time until the response head was ready");
+        for ((method, route, status), histogram) in &http {
+            histogram.write(&mut out, "http_request_duration_seconds", &http_labels(method, route, *status));
+        }
+        header(&mut out, "http_requests_in_flight", "gauge", "Requests being handled right now");
+        let _ = writeln!(out, "http_requests_in_flight {}", self.in_flight.load(Ordering::Relaxed));
+
+        header(&mut out, "db_pool_connections", "gauge", "Open SQLite connections");
+        let _ = writeln!(out, "db_pool_connections {}", pool.size);
+        header(&mut out, "db_pool_idle_connections", "gauge", "Open SQLite connections not in use");
+        let _ = writeln!(out, "db_pool_idle_connections {}", pool.idle);
+        header(&mut out, "db_pool_max_connections", "gauge", "Connection limit of the pool");
+        let _ = writeln!(out, "db_pool_max_connections {}", pool.max);
+        header(&mut out, "db_pool_acquire_duration_seconds", "histogram", "Wait for a connection from the pool");
+        self.db_acquire.lock().expect("metrics lock poisoned").write(&mut out, "db_pool_acquire_duration_seconds", "");
+        header(&mut out, "db_pool_acquire_errors_total", "counter", "Connections that could not be acquired");
+        let _ = writeln!(out, "db_pool_acquire_errors_total {}", self.db_acquire_errors.load(Ordering::Relaxed));
+
+        header(&mut out, "auth_logins_total", "counter", "Login attempts by result");
+        for (result, count) in self.logins.lock().expect("metrics lock poisoned").iter() {
+            let _ = writeln!(out, "auth_logins_total{{result=\"{}\"}} {}", result, count);
+        }
+
+        header(&mut out, "app_counter", "gauge", "Value of the counter behind /api/counter");
+        let _ = writeln!(out, "app_counter {}", app_counter);
+
+        let process: ProcessStats = process_stats();
+        let started_at: f64 = self.started_at.duration_since(UNIX_EPOCH).map(|since_epoch| since_epoch.as_secs_f64()).unwrap_or_default();
+        header(&mut out, "process_start_time_seconds", "gauge", "Start time of the process since the unix epoch");
+        let _ = writeln!(out, "process_start_time_seconds {}", started_at.floor());
+        for (name, kind, help, value) in [
+            ("process_resident_memory_bytes", "gauge", "Resident memory size", process.resident_memory_bytes),
+            ("process_virtual_memory_bytes", "gauge", "Virtual memory size", process.virtual_memory_bytes),
+            ("process_open_fds", "gauge", "Open file descriptors, or handles on Windows", process.open_fds),
+            ("process_max_fds", "gauge", "Limit of open file descriptors", process.max_fds),
+            ("process_cpu_seconds_total", "counter", "User and system CPU time", process.cpu_seconds),
+        ] {
+            if let Some(value) = value {
+                header(&mut out, name, kind, help);
+                let _ = writeln!(out, "{} {}", name, value);
+            }
+        }
+
+        let build = build_info();
+        header(&mut out, "app_build_info", "gauge", "Always 1, the labels describe the running build");
+        let _ = writeln!(
+            out,
+            "app_build_info{{version=\"{}\",git_commit=\"{}\",git_dirty=\"{}\",rustc_version=\"{}\",profile=\"{}\",features=\"{}\",client_bundle_hash=\"{}\"}} 1",
+            escape(build.version),
+            escape(build.git_commit),
+            build.git_dirty,
+            escape(build.rustc_version),
+            escape(build.profile),
+            escape(&build.features.join(",")),
+            escape(build.client_bundle_hash),
+        );
+
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn http_labels(method: &str, route: &str, status: u16) -> String {
+    format!("method=\"{}\",route=\"{}\",status=\"{}\"", escape(method), escape(route), status)
+}
+
+/// Label values may contain anything but these three need escaping
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Keeps `http_requests_in_flight` right when a request future is dropped halfway, e.g. on disconnect
+struct InFlight;
+
+impl InFlight {
+    fn start() -> Self {
+        metrics().in_flight.fetch_add(1, Ordering::Relaxed);
+        Self
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        metrics().in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Records the count and latency of every request, labeled with the route template instead of the
+/// path so `/api/users/1` and `/api/users/2` end up in the same series
+pub async fn track_requests(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let _in_flight: InFlight = InFlight::start();
+    let started: Instant = Instant::now();
+    let method: String = req.method().to_string();
+    let res: Result<ServiceResponse<_>, actix_web::Error> = next.call(req).await;
+
+    let (route, status): (Option<String>, u16) = match &res {
+        Ok(res) => (res.request().match_pattern(), res.status().as_u16()),
+        Err(e) => (None, e.as_response_error().status_code().as_u16()),
+    };
+    metrics().observe_request(&method, route.as_deref().unwrap_or(UNMATCHED_ROUTE), status, started.elapsed());
+
+    res
+}
+
+/// Floats so every value fits the same rendering loop, whole numbers print without a fraction
+#[derive(Default)]
+struct ProcessStats {
+    resident_memory_bytes: Option<f64>,
+    virtual_memory_bytes: Option<f64>,
+    open_fds: Option<f64>,
+    max_fds: Option<f64>,
+    cpu_seconds: Option<f64>,
+}
+
+#[cfg(target_os = "linux")]
+fn process_stats() -> ProcessStats {
+    let page_size: u64 = rustix::param::page_size() as u64;
+    // `size resident shared text lib data dt`, in pages
+    let statm: Vec<u64> = std::fs::read_to_string("/proc/self/statm")
+        .map(|statm| statm.split_whitespace().filter_map(|field| field.parse().ok()).collect())
+        .unwrap_or_default();
+    // utime and stime are the 14th and 15th fields, counted after the parenthesized command name
+    let cpu_seconds: Option<f64> = std::fs::read_to_string("/proc/self/stat").ok().and_then(|stat| {
+        let fields: Vec<&str> = stat.rsplit_once(')')?.1.split_whitespace().collect();
+        let ticks: u64 = fields.get(11)?.parse::<u64>().ok()? + fields.get(12)?.parse::<u64>().ok()?;
+        Some(ticks as f64 / rustix::param::clock_ticks_per_second() as f64)
+    });
+
+    ProcessStats {
+        resident_memory_bytes: statm.get(1).map(|pages| (pages * page_size) as f64),
+        virtual_memory_bytes: statm.first().map(|pages| (pages * page_size) as f64),
+        open_fds: std::fs::read_dir("/proc/self/fd").ok().map(|entries| entries.count() as f64),
+        max_fds: rustix::process::getrlimit(rustix::process::Resource::Nofile).current.map(|limit| limit as f64),
+        cpu_seconds,
+    }
+}
+
+#[cfg(windows)]
+fn process_stats() -> ProcessStats {
+    use windows_sys::Win32::System::{
+        ProcessStatus::{GetProcessMemoryInfo, PROCESS_MEMORY_COUNTERS},
+        Threading::{GetCurrentProcess, GetProcessHandleCount},
+    };
+
+    let mut stats: ProcessStats = ProcessStats::default();
+    // SAFETY: the pseudo handle of the current process needs no closing, both out parameters live on this stack frame
+    unsafe {
+        let process = GetCurrentProcess();
+        let mut counters: PROCESS_MEMORY_COUNTERS = std::mem::zeroed();
+        let size: u32 = std::mem::size_of::<PROCESS_MEMORY_COUNTERS>() as u32;
+        if GetProcessMemoryInfo(process, &mut counters, size) != 0 {
+            stats.resident_memory_bytes = Some(counters.WorkingSetSize as f64);
+            stats.virtual_memory_bytes = Some(counters.PagefileUsage as f64);
+        }
+        let mut handles: u32 = 0;
+        if GetProcessHandleCount(process, &mut handles) != 0 {
+            stats.open_fds = Some(handles as f64);
+        }
+    }
+
+    stats
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+fn process_stats() -> ProcessStats {
+    ProcessStats::default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{
+        middleware::from_fn,
+        test::{call_service, init_service, read_body, TestRequest},
+        web, App, HttpResponse,
+    };
+
+    /// A fresh instance, the global one is shared with every other test
+    fn empty() -> Metrics {
+        Metrics {
+            started_at: SystemTime::now(),
+            http: Mutex::new(BTreeMap::new()),
+            in_flight: AtomicI64::new(0),
+            db_acquire: Mutex::new(Histogram::default()),
+            db_acquire_errors: AtomicU64::new(0),
+            logins: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    fn render(metrics: &Metrics) -> String {
+        metrics.render(&PoolGauges { size: 2, idle: 1, max: 4 }, 7)
+    }
+
+    #[test]
+    fn exposition_format() {
+        let metrics: Metrics = empty();
+        metrics.observe_request("GET", "/api/users/{id}", 200, Duration::from_millis(3));
+        metrics.record_login(LoginResult::Failure);
+        let out: String = render(&metrics);
+
+        let mut declared: Vec<&str> = Vec::new();
+        for line in out.lines() {
+            if let Some(help) = line.strip_prefix("# HELP ") {
+                declared.push(help.split_once(' ').unwrap().0);
+                continue;
+            }
+            if let Some(kind) = line.strip_prefix("# TYPE ") {
+                let (name, kind) = kind.split_once(' ').unwrap();
+                assert_eq!(Some(&name), declared.last(), "TYPE without HELP: {line}");
+                assert!(["counter", "gauge", "histogram"].contains(&kind), "{line}");
+                continue;
+            }
+            let (series, value) = line.rsplit_once(' ').unwrap();
+            let name: &str = series.split('{').next().unwrap();
+            assert!(
+                declared.last().is_some_and(|family| name == *family || name.strip_prefix(family).is_some_and(|suffix| ["_bucket", "_sum", "_count"].contains(&suffix))),
+                "sample outside its family: {line}"
+            );
+            assert!(value.parse::<f64>().is_ok(), "not a number: {line}");
+        }
+
+        assert!(out.contains("http_requests_total{method=\"GET\",route=\"/api/users/{id}\",status=\"200\"} 1\n"));
+        assert!(out.contains("auth_logins_total{result=\"failure\"} 1\n"));
+        assert!(out.contains("db_pool_connections 2\n"));
+        assert!(out.contains("db_pool_idle_connections 1\n"));
+        assert!(out.contains("db_pool_max_connections 4\n"));
+        assert!(out.contains("app_counter 7\n"));
+    }
+
+    #[test]
+    fn histogram_buckets_are_cumulative() {
+        let mut histogram: Histogram = Histogram::default();
+        histogram.observe(0.25);
+        histogram.observe(0.5);
+        histogram.observe(0.5);
+        histogram.observe(20.0);
+        let mut out: String = String::new();
+        histogram.write(&mut out, "latency", "route=\"/\"");
+
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), LATENCY_BUCKETS.len() + 3);
+        assert_eq!(lines[4], "latency_bucket{route=\"/\",le=\"0.1\"} 0");
+        assert_eq!(lines[5], "latency_bucket{route=\"/\",le=\"0.25\"} 1");
+        assert_eq!(lines[6], "latency_bucket{route=\"/\",le=\"0.5\"} 3");
+        assert_eq!(lines[10], "latency_bucket{route=\"/\",le=\"10\"} 3");
+        assert_eq!(lines[11], "latency_bucket{route=\"/\",le=\"+Inf\"} 4");
+        assert_eq!(lines[12], "latency_sum{route=\"/\"} 21.25");
+        assert_eq!(lines[13], "latency_count{route=\"/\"} 4");
+    }
+
+    #[test]
+    fn histogram_without_labels() {
+        let mut histogram: Histogram = Histogram::default();
+        histogram.observe(1.0);
+        let mut out: String = String::new();
+        histogram.write(&mut out, "wait", "");
+
+        assert!(out.starts_with("wait_bucket{le=\"0.005\"} 0\n"));
+        assert!(out.contains("wait_bucket{le=\"1\"} 1\n"));
+        assert!(out.contains("wait_bucket{le=\"+Inf\"} 1\nwait_sum 1\nwait_count 1\n"));
+    }
+
+    #[test]
+    fn label_values_are_escaped() {
+        assert_eq!(escape(r#"a\b"c"#), r#"a\\b\"c"#);
+        assert_eq!(escape("line\nbreak"), "line\\nbreak");
+        assert_eq!(http_labels("GET", "/\"x\"", 404), r#"method="GET",route="/\"x\"",status="404""#);
+    }
+
+    #[actix_web::test]
+    async fn in_flight_goes_back_down() {
+        let app = init_service(
+            App::new().wrap(from_fn(track_requests)).route(
+                "/items/{id}",
+                web::get().to(|| async { HttpResponse::Ok().body(metrics().in_flight.load(Ordering::Relaxed).to_string()) }),
+            ),
+        )
+        .await;
+
+        let res = call_service(&app, TestRequest::get().uri("/items/1").to_request()).await;
+        assert_eq!(read_body(res).await, "1");
+        assert_eq!(metrics().in_flight.load(Ordering::Relaxed), 0);
+        let out: String = render(metrics());
+        assert!(out.contains("http_requests_total{method=\"GET\",route=\"/items/{id}\",status=\"200\"} 1\n"));
+    }
+}
@@ -1,4 +1,24 @@
+pub mod admin_auth;
 pub mod api;
+pub mod assets;
+pub mod bootstrap;
+pub mod build_info;
+#[cfg(feature = "chaos")]
+pub mod chaos;
+#[cfg(feature = "dev_proxy")]
+pub mod dev_proxy;
+pub mod health;
+pub mod listeners;
+pub mod log_buffer;
+pub mod log_level;
+pub mod log_search;
+pub mod maintenance;
+pub mod metrics;
+pub mod reload;
+pub mod request_id;
+pub mod security_headers;
+pub mod settings;
+pub mod tls;
 pub mod utils;
 
 use actix_cors::Cors;
@@ -8,20 +28,19 @@ use actix_web::{http, web, FromRequest, HttpRequest};
 use chrono::{DateTime, Utc};
 use futures::future::{ready, Ready};
 use sqlx::{Pool, Sqlite, SqlitePool};
-use std::env;
 use std::io::Result;
-use std::net::TcpListener;
 use tokio::signal;
 use tokio_util::sync::CancellationToken;
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 
+use actix_svelte::secrets::{secret_var, Secret};
 use rust_embed::RustEmbed;
 use rusty_paseto::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::{
   sync::{
-    atomic::{AtomicUsize, Ordering}, 
+    atomic::{AtomicUsize, Ordering},
     Arc
   }
 };
@@ -29,21 +48,56 @@ use tokio::sync::RwLock;
 
 #[derive(RustEmbed)]
 #[folder = "client/build"]
+// The client doesn't have to be built when Vite serves it
+#[cfg_attr(feature = "dev_proxy", allow_missing = true)]
 pub struct StaticFiles;
 
+/// `.br` and `.gz` variants of `StaticFiles` under the same paths, written to `OUT_DIR` by `build.rs`
+#[derive(RustEmbed)]
+#[folder = "$OUT_DIR/precompressed"]
+pub struct PrecompressedFiles;
+
 #[derive(Debug)]
 pub struct DatabaseState {
     pub pool: Pool<Sqlite>,
 }
 
+impl DatabaseState {
+    /// A connection from the pool, the wait is recorded in `db_pool_acquire_duration_seconds`
+    pub async fn acquire(&self) -> std::result::Result<sqlx::pool::PoolConnection<Sqlite>, sqlx::Error> {
+        let started: std::time::Instant = std::time::Instant::now();
+        let connection = self.pool.acquire().await;
+        metrics::metrics().observe_db_acquire(started.elapsed(), connection.is_ok());
+
+        connection
+    }
+
+    /// In-memory database with the migrations applied, tests never touch `database.db`
+    #[cfg(test)]
+    pub async fn in_memory() -> Self {
+        let pool: Pool<Sqlite> = sqlx::sqlite::SqlitePoolOptions::new().max_connections(1).connect("sqlite::memory:").await.unwrap();
+        sqlx::migrate!().run(&pool).await.unwrap();
+
+        DatabaseState { pool }
+    }
+}
+
 pub struct AppState {
     pub app_name: String,
     pub app_version: String,
+    /// `BASE_PATH`, empty when the app is served from the root
+    pub base_path: String,
+    /// Reported as the uptime by the liveness probe
+    pub started_at: std::time::Instant,
     pub counter: RwLock<i32>,
     pub global_count: RwLock<AtomicUsize>,
-    pub secret_key_string: String,
+    pub secret_key_string: Secret<String>,
     pub private_key: Key<64>,
     pub public_key: Key<32>,
+    /// Set when the server terminates TLS itself, cookies are then marked `Secure`
+    pub secure_cookies: bool,
+    /// Settings re-applied by `reload::ConfigReloader`, read synchronously by middleware
+    pub runtime: std::sync::RwLock<settings::RuntimeSettings>,
 }
 
 impl std::fmt::Debug for AppState {
@@ -51,8 +105,11 @@ impl std::fmt::Debug for AppState {
         f.debug_struct("AppState")
             .field("app_name", &self.app_name)
             .field("app_version", &self.app_version)
+            .field("base_path", &self.base_path)
             .field("counter", &self.counter)
             .field("global_count", &self.global_count)
+            .field("secure_cookies", &self.secure_cookies)
+            .field("runtime", &self.runtime)
             .finish_non_exhaustive()
     }
 }
@@ -64,13 +121,15 @@ pub struct SerializableAppState<'a> {
     app_version: &'a str,
     counter: i32,
     global_counter: usize,
+    build: &'static build_info::BuildInfo,
     // private_key: &'a str,
     // public_key: &'a str,
 }
 impl AppState {
-  pub fn new(app_name: &str) -> SharedState {
-      let secret_key_string: String = env::var("SECRET_KEY").expect("SECRET_KEY must be set");
-      let private_key: Key<64> = Key::<64>::try_from(secret_key_string.clone().as_str()).expect("Failed to parse PASETO secret key");
+  pub fn new(app_name: &str, base_path: &str, secure_cookies: bool, runtime: settings::RuntimeSettings) -> SharedState {
+      // Also read from SECRET_KEY_FILE or the encrypted secrets file, see `actix_svelte::secrets`
+      let secret_key_string: Secret<String> = secret_var("SECRET_KEY").expect("SECRET_KEY must be set");
+      let private_key: Key<64> = Key::<64>::try_from(secret_key_string.expose().as_str()).expect("Failed to parse PASETO secret key");
       // let pk: &[u8] = private_key.to_owned().as_slice();
       // let private_key = PasetoAsymmetricPrivateKey::<V4, Public>::from(pk);
       let public_key: Key<32> = Key::<32>::try_from("1eb9dbbbbc047c03fd70604e0071f0987e16b28b757225c11f00415d0e20b1a2").expect("Failed to parse public key");
@@ -80,20 +139,50 @@ impl AppState {
       Arc::new(AppState {
           app_name: app_name.to_string(),
           app_version: env!("CARGO_PKG_VERSION").to_string(),
+          base_path: base_path.to_string(),
+          started_at: std::time::Instant::now(),
           counter: RwLock::new(0),
           global_count: RwLock::new(AtomicUsize::new(0)),
           secret_key_string,
           private_key,
-          public_key 
+          public_key,
+          secure_cookies,
+          runtime: std::sync::RwLock::new(runtime),
+      })
+  }
+
+  /// State for tests, signed with the public PASETO v4 test vector so no `SECRET_KEY` is needed
+  #[cfg(test)]
+  pub fn for_tests() -> SharedState {
+      const TEST_SECRET_KEY: &str = "b4cbfb43df4ce210727d953e4a713307fa19bb7d9f85041438d9e11b942a37741eb9dbbbbc047c03fd70604e0071f0987e16b28b757225c11f00415d0e20b1a2";
+
+      Arc::new(AppState {
+          app_name: "test".to_string(),
+          app_version: env!("CARGO_PKG_VERSION").to_string(),
+          base_path: String::new(),
+          started_at: std::time::Instant::now(),
+          counter: RwLock::new(0),
+          global_count: RwLock::new(AtomicUsize::new(0)),
+          secret_key_string: Secret::new(TEST_SECRET_KEY.to_string()),
+          private_key: Key::<64>::try_from(TEST_SECRET_KEY).unwrap(),
+          public_key: Key::<32>::try_from("1eb9dbbbbc047c03fd70604e0071f0987e16b28b757225c11f00415d0e20b1a2").unwrap(),
+          secure_cookies: false,
+          runtime: std::sync::RwLock::new(settings::RuntimeSettings::from_source(|_| None).unwrap()),
       })
   }
   
+  /// Cookies are scoped to the base path so apps mounted side by side don't share them
+  pub fn cookie_path(&self) -> &str {
+      if self.base_path.is_empty() { "/" } else { &self.base_path }
+  }
+
   pub async fn to_serializable(&self) -> SerializableAppState {
     SerializableAppState {
         app_name: &self.app_name,
         app_version: &self.app_version,
         counter: *self.counter.read().await,
         global_counter: self.global_count.read().await.load(Ordering::SeqCst),
+        build: build_info::build_info(),
         // private_key: hex::encode(self.private_key.as_ref()).as_str(),
         // public_key: hex::encode(self.public_key.as_ref()).as_str(),
     }
@@ -104,7 +193,10 @@ pub type SharedState = Arc<AppState>;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AuthenticatedUser {
-    pub token: String,
+    /// Only travels in the `auth_token` cookie, never in a response body or the logs
+    #[serde(skip)]
+    pub token: Secret<String>,
+    pub username: String,
     pub expiration: DateTime<Utc>
 }
 
@@ -120,37 +212,40 @@ impl FromRequest for AuthenticatedUser {
                 .unwrap_or_default();
             let public_key: PasetoAsymmetricPublicKey<'_, V4, Public> = PasetoAsymmetricPublicKey::<V4, Public>::from(&p_key);
 
-            tracing::info!("Extracted token from cookie: {}", token);
-            let parsed_token_json = PasetoParser::<V4, Public>::default()
-            .parse(&token, &public_key)
-            .expect("Failed to parse token");
+            tracing::debug!("Extracted auth_token cookie from request");
+            let parsed_token_json = match PasetoParser::<V4, Public>::default().parse(token, &public_key) {
+                Ok(parsed_token_json) => parsed_token_json,
+                Err(e) => {
+                    tracing::warn!("Rejected auth_token cookie: {}", e);
+                    return ready(Err(actix_web::error::ErrorUnauthorized("Invalid token")));
+                }
+            };
             // .set_footer(Footer::from("Footer example"))
 
-            // print the parsed token for debugging
-            tracing::info!("Parsed token: {:?}", parsed_token_json);
             // check if the expiration in the token is valid
             let expiration: &serde_json::Value = &parsed_token_json["exp"];
-            println!("Expiration value: {:?}", expiration);
             // Check if the expiration field exists and is valid
 
             if expiration.is_null() {
                 return ready(Err(actix_web::error::ErrorUnauthorized("Token does not have an expiration")));
             }
-            // Parsed token: Object {"aud": String("custoemrs"), "data": String("this is a secret message"), "exp": String("2025-05-19T06:34:46.993882200+00:00"), "iat": String("2025-05-18T06:34:46.994121400+00:00"), "iss": String("me"), "jti": String("me"), "nbf": String("2025-05-18T06:34:46.994173900+00:00"), "sub": String("my local subjects"), "username": String("pass123")}
             // We are expecting expiration to be a String(DateTime<Utc>), so we need to parse it
             let expiration_str: &str = expiration.as_str().unwrap_or_default();
             let expiration: DateTime<Utc> = match DateTime::parse_from_rfc3339(expiration_str) {
                 Ok(dt) => dt.with_timezone(&Utc),
                 Err(_) => return ready(Err(actix_web::error::ErrorUnauthorized("Invalid expiration format"))),
             };
-            tracing::info!("Token expiration: {}", expiration);
+            let username: String = parsed_token_json["username"].as_str().unwrap_or_default().to_string();
+            request_id::record_user(req, &username);
+            tracing::debug!("Token for user {} expires at {}", username, expiration);
             // check if its expired, i.e., if the expiration is in the past
             if expiration < Utc::now() {
                 return ready(Err(actix_web::error::ErrorUnauthorized("Token has expired")));
             } else {
-                tracing::info!("Token is valid and not expired");
+                tracing::debug!("Token is valid and not expired");
                 return ready(Ok(AuthenticatedUser {
-                    token: token.to_string(),
+                    token: Secret::new(token.to_string()),
+                    username,
                     expiration,
                 }));
             }
@@ -170,20 +265,35 @@ impl FromRequest for AuthenticatedUser {
 pub struct Application {
     pub hostname: String,
     pub port: u16,
+    pub tls_enabled: bool,
+    pub base_path: String,
+    pub addresses: Vec<String>,
     pub server: actix_web::dev::Server,
+    pub redirect_server: Option<actix_web::dev::Server>,
     pub cancel_token: CancellationToken,
 }
 impl Application {
-    pub async fn build(hostname: String, port: u16, _test_pool: Option<Pool<Sqlite>>) -> Result<Self> {
-        let listener: TcpListener =
-            TcpListener::bind(format!("{}:{}", hostname, port)).expect("Failed to bind address");
+    pub async fn build(settings: settings::Settings, _test_pool: Option<Pool<Sqlite>>) -> Result<Self> {
+        let hostname: String = settings.application.host.clone();
+        let listeners: listeners::Listeners = listeners::Listeners::bind(&settings)?;
+        let port: u16 = listeners.port()?;
         let cancel_token: CancellationToken = CancellationToken::new();
-        let server: actix_web::dev::Server = build_server_app(listener, cancel_token.clone()).await?;
+        let tls_enabled: bool = settings.tls.enabled();
+        let base_path: String = settings.application.base_path.clone();
+        let addresses: Vec<String> = listeners.describe(if tls_enabled { "https" } else { "http" });
+
+        // Optional plain HTTP listener that only redirects to the HTTPS port
+        let redirect_server: Option<actix_web::dev::Server> = tls::redirect_server(&settings.tls, &hostname, port)?;
+        let server: actix_web::dev::Server = build_server_app(listeners, settings, cancel_token.clone()).await?;
 
         Ok(Self {
             hostname,
             port,
+            tls_enabled,
+            base_path,
+            addresses,
             server,
+            redirect_server,
             cancel_token,
         })
     }
@@ -196,18 +306,22 @@ impl Application {
         self.port
     }
 
+    pub fn scheme(&self) -> &'static str {
+        if self.tls_enabled { "https" } else { "http" }
+    }
+
     pub async fn run_until_stopped(self) -> Result<()> {
         println!("\n\t✅ Database connected successfully");
         println!("\t🚀 Server started successfully");
+        for address in &self.addresses {
+            println!("\t🌍 Listening on: {}", address);
+        }
         println!(
-            "\t🌍 Listening on: http://{}:{}",
-            self.hostname(),
-            self.port()
-        );
-        println!(
-            "\t🔗 Swagger Docs: http://{}:{}/api/swagger-ui/#/\n",
+            "\t🔗 Swagger Docs: {}://{}:{}{}/api/swagger-ui/#/\n",
+            self.scheme(),
             self.hostname(),
-            self.port()
+            self.port(),
+            self.base_path
         );
 
         // Start listening for shutdown
@@ -217,9 +331,18 @@ impl Application {
             println!("Shutdown signal received");
         };
 
+        // The redirect listener is optional, when it is missing this branch never completes
+        let redirect_server = async {
+            match self.redirect_server {
+                Some(redirect_server) => redirect_server.await,
+                None => std::future::pending().await,
+            }
+        };
+
         // Run the server and shutdown signal in parallel
         tokio::select! {
                 _ = self.server => Ok(()),
+                _ = redirect_server => Ok(()),
                 _ = shutdown_signal => Ok(())
         }
 
@@ -229,45 +352,204 @@ impl Application {
 }
 
 pub async fn build_server_app(
-    listener: TcpListener,
+    listeners: listeners::Listeners,
+    settings: settings::Settings,
     _cancel_token: CancellationToken,
 ) -> Result<actix_web::dev::Server> {
+    // Fixes the process start time reported by `/metrics`
+    metrics::metrics();
     let database_url: String = dotenvy::var("DATABASE_URL").expect("DATABASE_URL must be set");
     let pool: Pool<Sqlite> = SqlitePool::connect(&database_url).await.expect("Failed to connect to database");
     let db_state: Data<DatabaseState> = Data::new(DatabaseState { pool: pool.clone() });
-    let app_name: String = dotenvy::var("APP_NAME").unwrap_or_else(|_| "App Template".to_string());
-    let shared_state: Data<Arc<AppState>> = Data::new(AppState::new(app_name.as_str()));
+    let shared_state: Data<Arc<AppState>> = Data::new(AppState::new(
+        settings.application.app_name.as_str(),
+        settings.application.base_path.as_str(),
+        settings.tls.enabled(),
+        settings.runtime.clone(),
+    ));
+    if settings.maintenance.start_paused {
+        maintenance::pause(settings.runtime.maintenance_notice(), "MAINTENANCE_MODE");
+    }
 
-    let openapi: utoipa::openapi::OpenApi = api::swagger::ApiDocumentation::openapi();
+    // Live configuration reload from the settings file, SIGHUP and the admin endpoint
+    let reloader: Data<reload::ConfigReloader> = Data::new(reload::ConfigReloader::new(
+        settings.application.settings_file.clone(),
+        shared_state.get_ref().clone(),
+        settings.maintenance.start_paused,
+    ));
+    if let Err(e) = reload::watch_settings_file(reloader.clone()) {
+        tracing::event!(target: "backend", tracing::Level::WARN, "Not watching {} for changes: {}", settings.application.settings_file.display(), e);
+    }
+    #[cfg(unix)]
+    reload::reload_on_sighup(reloader.clone())?;
+    let admin_listeners: Data<listeners::AdminListeners> = Data::new(listeners.admin_listeners()?);
+    let http_settings: settings::HttpSettings = settings.http.clone();
+    // Every route is registered below this prefix, empty when the app is served from the root
+    let base_path: String = settings.application.base_path.clone();
+    let health_checks: Data<health::HealthChecks> = Data::new(health::HealthChecks::new(
+        &settings.health,
+        &database_url,
+        settings.logging.directory.clone(),
+    ));
+    let maintenance_policy: Data<maintenance::MaintenancePolicy> =
+        Data::new(maintenance::MaintenancePolicy::new(&settings.maintenance, &base_path));
+    let metrics_settings: Data<settings::MetricsSettings> = Data::new(settings.metrics.clone());
+    let admin_settings: Data<settings::AdminSettings> = Data::new(settings.admin.clone());
+    let frontend_settings: Data<settings::FrontendSettings> = Data::new(settings.frontend.clone());
+    let logging_settings: Data<settings::LoggingSettings> = Data::new(settings.logging.clone());
+    if let Some(static_dir) = &settings.frontend.static_dir {
+        if static_dir.is_dir() {
+            tracing::event!(target: "backend", tracing::Level::INFO, "Files in {} override the embedded client.", static_dir.display());
+        } else {
+            tracing::event!(target: "backend", tracing::Level::WARN, "STATIC_DIR {} is not a folder, only the embedded client is served.", static_dir.display());
+        }
+    }
+    #[cfg(feature = "dev_proxy")]
+    let dev_proxy: Option<Data<dev_proxy::DevProxy>> = settings.frontend.dev_server_url.as_deref().map(|url| {
+        tracing::event!(target: "backend", tracing::Level::INFO, "Proxying the frontend to the Vite dev server at {}", url);
+        Data::new(dev_proxy::DevProxy::new(url, &base_path))
+    });
+    let security_headers = security_headers::SecurityHeaders::new(&settings.security, settings.tls.enabled(), &base_path)
+        .scope(&format!("{}/api/swagger-ui", base_path), &settings.security.swagger_csp_policy)
+        .scope(&format!("{}/api-docs", base_path), &settings.security.swagger_csp_policy);
+    // Pages served by Vite carry no nonce, their inline scripts would be blocked
+    #[cfg(feature = "dev_proxy")]
+    let security_headers = match (&dev_proxy, settings.security.csp_mode) {
+        (Some(_), settings::CspMode::Enforce) => security_headers.with_csp_mode(settings::CspMode::ReportOnly),
+        _ => security_headers,
+    };
+    let security_headers: Data<security_headers::SecurityHeaders> = Data::new(security_headers);
+    #[cfg(not(feature = "dev_proxy"))]
+    if settings.frontend.dev_server_url.is_some() {
+        tracing::event!(target: "backend", tracing::Level::WARN, "FRONTEND_DEV_URL is set but this binary was built without the dev_proxy feature, serving the embedded client.");
+    }
+    // Without an admin listener or admin credentials nothing could keep the public from injecting faults
+    #[cfg(feature = "chaos")]
+    let chaos_rules: Option<Data<chaos::ChaosRules>> = if !admin_listeners.addresses.is_empty() || settings.admin.has_credentials() {
+        tracing::event!(target: "chaos", tracing::Level::WARN, "Built with the chaos feature, fault injection rules can be added on /api/admin/chaos/rules");
+        Some(Data::new(chaos::ChaosRules::new(&base_path)))
+    } else {
+        tracing::event!(target: "chaos", tracing::Level::ERROR, "Built with the chaos feature but neither ADMIN_LISTEN_ADDRESSES, ADMIN_TOKEN nor ADMIN_USERS is set, fault injection is disabled");
+        None
+    };
+    let json_limit: usize = http_settings.json_limit;
+    let payload_limit: usize = http_settings.payload_limit;
+    let tls_config: Option<rustls::ServerConfig> = tls::server_config(&settings.tls, &settings.application.host)?;
 
-    let server_app: actix_web::dev::Server = HttpServer::new(move || {
-        App::new()
+    let mut openapi: utoipa::openapi::OpenApi = api::swagger::ApiDocumentation::openapi();
+    #[cfg(feature = "chaos")]
+    openapi.merge(api::swagger::ChaosDocumentation::openapi());
+    // "Try it out" sends requests relative to the first server
+    if !base_path.is_empty() {
+        openapi.servers = Some(vec![utoipa::openapi::Server::new(base_path.clone())]);
+    }
+
+    let mut server_app = HttpServer::new(move || {
+        let app = App::new()
             .app_data(db_state.clone())
             .app_data(shared_state.clone())
-            .wrap(middleware::Logger::default())
+            .app_data(admin_listeners.clone())
+            .app_data(reloader.clone())
+            .app_data(health_checks.clone())
+            .app_data(metrics_settings.clone())
+            .app_data(admin_settings.clone())
+            .app_data(maintenance_policy.clone())
+            .app_data(frontend_settings.clone())
+            .app_data(logging_settings.clone())
+            .app_data(security_headers.clone())
+            .app_data(web::JsonConfig::default().limit(json_limit))
+            .app_data(web::PayloadConfig::new(payload_limit))
+            // Inside the security headers so the maintenance page gets them too
+            .wrap(middleware::from_fn(maintenance::enforce))
+            .wrap(middleware::from_fn(security_headers::security_headers))
+            .wrap(middleware::from_fn(metrics::track_requests))
+            .wrap(middleware::from_fn(request_id::request_context))
+            .wrap(middleware::Logger::new(r#"%a "%r" %s %b "%{Referer}i" "%{User-Agent}i" %T request_id=%{x-request-id}o"#))
             .wrap(
                 Cors::default()
-                    .allowed_origin("http://localhost:5173")
+                    .allowed_origin_fn({
+                        // Origins come from the runtime settings so a reload applies them immediately
+                        let state: SharedState = shared_state.get_ref().clone();
+                        move |origin, _request_head| {
+                            let runtime = state.runtime.read().expect("runtime settings lock poisoned");
+                            origin.to_str().is_ok_and(|origin| runtime.cors_origins.iter().any(|allowed| allowed == origin))
+                        }
+                    })
                     .allowed_methods(vec!["GET"])
                     .allowed_headers(vec![http::header::CONTENT_TYPE])
                     .max_age(3600),
             )
             .service(
-                SwaggerUi::new("/api/swagger-ui/{_:.*}")
-                    .url("/api-docs/openapi.json", openapi.clone()),
-            )
-            .service(
-                web::scope("/api")
-                    .configure(api::routes::app_services)
-                    .configure(api::routes::auth_services)
-            )
-            .service(api::handlers::serve_static_files)
+                SwaggerUi::new(format!("{}/api/swagger-ui/{{_:.*}}", base_path))
+                    .url(format!("{}/api-docs/openapi.json", base_path), openapi.clone()),
+            );
+        // `/cleanroom` would miss every route of the scope below, the pages' relative links need the trailing slash
+        let app = if base_path.is_empty() {
+            app
+        } else {
+            app.service(web::redirect(base_path.clone(), format!("{}/", base_path)))
+        };
+        // An empty prefix matches from the root, so this also covers apps without a base path
+        let api_scope = web::scope("/api")
+            // Static files carry precompressed variants, API responses are compressed on the fly
+            .wrap(middleware::Compress::default())
+            .configure(api::routes::app_services)
+            .configure(api::routes::auth_services);
+        #[cfg(feature = "chaos")]
+        let api_scope = match &chaos_rules {
+            Some(_) => api_scope.configure(api::routes::chaos_services),
+            None => api_scope,
+        };
+        let base_scope = web::scope(&base_path)
+            // After the fault injection routes, the /admin scope would otherwise shadow /admin/chaos
+            .service(api_scope.configure(api::routes::admin_services))
+            .service(api::handlers::metrics::metrics_endpoint);
+        // Everything below the base path, static files included, can get injected faults
+        #[cfg(feature = "chaos")]
+        let base_scope = match &chaos_rules {
+            Some(chaos_rules) => base_scope.app_data(chaos_rules.clone()),
+            None => base_scope,
+        }
+        .wrap(middleware::from_fn(chaos::inject_faults));
+
+        #[cfg(feature = "dev_proxy")]
+        if let Some(dev_proxy) = &dev_proxy {
+            return app
+                .service(base_scope)
+                .app_data(dev_proxy.clone())
+                .default_service(web::to(dev_proxy::proxy));
+        }
+        app.service(base_scope.service(api::handlers::serve_static_files))
     })
-    .listen(listener)
-    .expect("Failed to listen on address")
-    .workers(1)
-    .shutdown_timeout(5)
-    .run();
+    .on_connect(listeners::tag_connection);
+
+    // Public and admin TCP listeners share the same TLS configuration
+    for listener in listeners.public.into_iter().chain(listeners.admin) {
+        server_app = match &tls_config {
+            Some(tls_config) => server_app
+                .listen_rustls_0_23(listener, tls_config.clone())
+                .expect("Failed to listen on address"),
+            None => server_app
+                .listen(listener)
+                .expect("Failed to listen on address"),
+        };
+    }
+    // The unix socket sits behind a local reverse proxy and always speaks plain HTTP
+    #[cfg(unix)]
+    if let Some(unix_socket) = listeners.unix_socket {
+        server_app = server_app
+            .listen_uds(unix_socket)
+            .expect("Failed to listen on unix socket");
+    }
+
+    let server_app: actix_web::dev::Server = server_app
+        .workers(http_settings.workers)
+        .max_connections(http_settings.max_connections)
+        .keep_alive(http_settings.keep_alive)
+        .client_request_timeout(http_settings.client_request_timeout)
+        .client_disconnect_timeout(http_settings.client_disconnect_timeout)
+        .shutdown_timeout(http_settings.shutdown_timeout)
+        .run();
 
     Ok(server_app)
 }
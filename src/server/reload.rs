@@ -0,0 +1,291 @@
+use actix_web::web::Data;
+use notify::{Event, RecursiveMode, Watcher};
+use serde::Serialize;
+use std::{
+    collections::{BTreeSet, HashMap},
+    path::{Path, PathBuf},
+    sync::{mpsc, Mutex, OnceLock},
+    time::Duration,
+};
+use utoipa::ToSchema;
+
+use super::{log_level, maintenance, settings::RuntimeSettings, SharedState};
+
+/// Runtime settings set in the real process environment, captured before `.env` is loaded into it
+static PROCESS_ENV: OnceLock<HashMap<String, String>> = OnceLock::new();
+
+/// Call before `.env` is loaded. Values set outside the settings file keep winning over it after a
+/// reload, like they do at startup, without a value that came from the file sticking around.
+pub fn capture_process_env() {
+    PROCESS_ENV.get_or_init(|| {
+        RuntimeSettings::KEYS
+            .iter()
+            .filter_map(|key| std::env::var(key).ok().map(|value| (key.to_string(), value)))
+            .collect()
+    });
+}
+
+/// Outcome of a reload, lists variable names only, never their values
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct ReloadReport {
+    /// Variables whose new values are already in effect
+    pub applied: Vec<String>,
+    /// Variables that changed but only take effect after a restart
+    pub restart_required: Vec<String>,
+    pub errors: Vec<String>,
+}
+
+/// #### Config Reloader
+/// Re-reads the settings file and applies the runtime settings to `AppState`, the log filter and
+/// the middleware that reads them. Triggered by file changes, SIGHUP and `POST /api/admin/reload`.
+pub struct ConfigReloader {
+    settings_file: PathBuf,
+    /// Values the process started with, used to report "restart required" changes
+    startup_values: HashMap<String, String>,
+    state: SharedState,
+    /// `MAINTENANCE_MODE` as last read, the service is only paused or resumed when it changes.
+    /// Also serializes reloads, they can race between the watcher, the signal handler and the endpoint.
+    maintenance_mode: Mutex<bool>,
+}
+
+impl ConfigReloader {
+    pub fn new(settings_file: PathBuf, state: SharedState, maintenance_mode: bool) -> Self {
+        let startup_values: HashMap<String, String> = read_settings_file(&settings_file).unwrap_or_default();
+
+        Self {
+            settings_file,
+            startup_values,
+            state,
+            maintenance_mode: Mutex::new(maintenance_mode),
+        }
+    }
+
+    pub fn settings_file(&self) -> &Path {
+        &self.settings_file
+    }
+
+    pub fn reload(&self, trigger: &str) -> ReloadReport {
+        let mut maintenance_mode = self.maintenance_mode.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut report: ReloadReport = ReloadReport::default();
+
+        let values: HashMap<String, String> = match read_settings_file(&self.settings_file) {
+            Ok(values) => values,
+            Err(e) => {
+                report.errors.push(describe_read_error(&self.settings_file, &e));
+                tracing::event!(target: "backend", tracing::Level::ERROR, "Settings reload ({}) failed: {:?}", trigger, report.errors);
+                return report;
+            }
+        };
+
+        let keys: BTreeSet<&String> = values.keys().chain(self.startup_values.keys()).collect();
+        for key in keys {
+            if !RuntimeSettings::KEYS.contains(&key.as_str()) && values.get(key) != self.startup_values.get(key) {
+                report.restart_required.push(key.clone());
+            }
+        }
+
+        let no_process_env: HashMap<String, String> = HashMap::new();
+        let process_env: &HashMap<String, String> = PROCESS_ENV.get().unwrap_or(&no_process_env);
+        match (runtime_settings(&values, process_env), reloaded_maintenance_mode(&values, process_env)) {
+            (Ok(runtime), Ok(reloaded)) => {
+                apply_runtime_settings(&self.state, runtime, (*maintenance_mode, reloaded), &mut report);
+                *maintenance_mode = reloaded;
+            }
+            (Err(e), _) | (_, Err(e)) => report.errors.push(e),
+        }
+
+        tracing::event!(
+            target: "backend",
+            tracing::Level::INFO,
+            "Settings reloaded ({}): applied {:?}, restart required {:?}, errors {:?}",
+            trigger,
+            report.applied,
+            report.restart_required,
+            report.errors
+        );
+        report
+    }
+}
+
+/// A key removed from the file goes back to its default. The process environment can't be asked,
+/// it still holds the values `.env` had at startup.
+fn runtime_settings(values: &HashMap<String, String>, process_env: &HashMap<String, String>) -> Result<RuntimeSettings, String> {
+    RuntimeSettings::from_source(|key| process_env.get(key).or_else(|| values.get(key)).cloned())
+}
+
+fn reloaded_maintenance_mode(values: &HashMap<String, String>, process_env: &HashMap<String, String>) -> Result<bool, String> {
+    RuntimeSettings::maintenance_mode(|key| process_env.get(key).or_else(|| values.get(key)).cloned())
+}
+
+fn read_settings_file(path: &Path) -> Result<HashMap<String, String>, dotenvy::Error> {
+    dotenvy::from_path_iter(path)?.collect()
+}
+
+/// The error for the report and the logs. A line that fails to parse may hold a secret, so only
+/// its position is reported, not the line itself.
+fn describe_read_error(path: &Path, error: &dotenvy::Error) -> String {
+    match error {
+        // The line where the entry starts, a quoted value can span several lines
+        dotenvy::Error::LineParse(line, _) => {
+            let contents: String = std::fs::read_to_string(path).unwrap_or_default();
+            match contents.find(line.as_str()) {
+                Some(offset) => format!("Failed to parse {} at line {}", path.display(), contents[..offset].matches('\n').count() + 1),
+                None => format!("Failed to parse {}", path.display()),
+            }
+        }
+        _ => format!("Failed to read {}: {}", path.display(), error),
+    }
+}
+
+/// Applies what changed, a log filter that fails to parse keeps the previous one. An override set
+/// through the admin API stays in effect, the new `RUST_LOG` is used once it ends.
+fn apply_runtime_settings(state: &SharedState, mut runtime: RuntimeSettings, (was_on, on): (bool, bool), report: &mut ReloadReport) {
+    let current: RuntimeSettings = state.runtime.read().expect("runtime settings lock poisoned").clone();
+
+    if runtime.log_filter != current.log_filter {
+        match log_level::set_default(&runtime.log_filter) {
+            Ok(()) => report.applied.push("RUST_LOG".to_string()),
+            Err(e) => {
+                report.errors.push(format!("RUST_LOG: {}", e));
+                runtime.log_filter = current.log_filter.clone();
+            }
+        }
+    }
+    if runtime.cors_origins != current.cors_origins {
+        report.applied.push("CORS_ALLOWED_ORIGINS".to_string());
+    }
+    if runtime.maintenance_message != current.maintenance_message {
+        report.applied.push("MAINTENANCE_MESSAGE".to_string());
+    }
+    if runtime.maintenance_until != current.maintenance_until {
+        report.applied.push("MAINTENANCE_UNTIL".to_string());
+    }
+    if on != was_on {
+        report.applied.push("MAINTENANCE_MODE".to_string());
+    }
+    // A new message or end time replaces the notice of a maintenance that is already running
+    let notice_changed: bool = runtime.maintenance_notice() != current.maintenance_notice();
+    if on && (notice_changed || !was_on) {
+        maintenance::pause(runtime.maintenance_notice(), "a settings reload");
+    } else if !on && was_on {
+        maintenance::resume("a settings reload");
+    }
+
+    *state.runtime.write().expect("runtime settings lock poisoned") = runtime;
+}
+
+/// Reloads when the settings file changes. Editors tend to write a file in several steps,
+/// so events are collected for a moment before reloading.
+pub fn watch_settings_file(reloader: Data<ConfigReloader>) -> notify::Result<()> {
+    let settings_file: PathBuf = reloader.settings_file().to_path_buf();
+    let file_name = settings_file.file_name().map(|n| n.to_os_string());
+    let folder: PathBuf = settings_file
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or(Path::new("."))
+        .to_path_buf();
+
+    let (tx, rx) = mpsc::channel::<notify::Result<Event>>();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    watcher.watch(&folder, RecursiveMode::NonRecursive)?;
+
+    std::thread::spawn(move || {
+        // The watcher stops when dropped, so it lives as long as this thread
+        let _watcher = watcher;
+        while let Ok(event) = rx.recv() {
+            let Ok(event) = event else { continue };
+            if event.kind.is_access() || !event.paths.iter().any(|p| p.file_name().map(|n| n.to_os_string()) == file_name) {
+                continue;
+            }
+            std::thread::sleep(Duration::from_millis(250));
+            while rx.try_recv().is_ok() {}
+            reloader.reload("settings file changed");
+        }
+    });
+
+    Ok(())
+}
+
+/// Reloads on SIGHUP, the usual "re-read your configuration" signal
+#[cfg(unix)]
+pub fn reload_on_sighup(reloader: Data<ConfigReloader>) -> std::io::Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangup = signal(SignalKind::hangup())?;
+    tokio::spawn(async move {
+        while hangup.recv().await.is_some() {
+            reloader.reload("SIGHUP");
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect()
+    }
+
+    #[test]
+    fn removed_key_goes_back_to_its_default() {
+        let before: RuntimeSettings = runtime_settings(
+            &values(&[("CORS_ALLOWED_ORIGINS", "https://kiosk.example"), ("MAINTENANCE_MESSAGE", "Back at noon")]),
+            &HashMap::new(),
+        )
+        .unwrap();
+        assert_eq!(before.cors_origins, ["https://kiosk.example"]);
+        assert_eq!(before.maintenance_message.as_deref(), Some("Back at noon"));
+
+        let after: RuntimeSettings = runtime_settings(&HashMap::new(), &HashMap::new()).unwrap();
+        assert_eq!(after.cors_origins.join(","), RuntimeSettings::DEFAULT_CORS_ORIGINS);
+        assert_eq!(after.maintenance_message, None);
+        assert_eq!(after.log_filter, "info");
+        assert!(!reloaded_maintenance_mode(&HashMap::new(), &HashMap::new()).unwrap());
+    }
+
+    #[test]
+    fn process_environment_wins_over_the_file() {
+        let process_env: HashMap<String, String> = values(&[("RUST_LOG", "warn")]);
+
+        assert_eq!(runtime_settings(&values(&[("RUST_LOG", "debug")]), &process_env).unwrap().log_filter, "warn");
+        assert_eq!(runtime_settings(&HashMap::new(), &process_env).unwrap().log_filter, "warn");
+    }
+
+    #[test]
+    fn invalid_value_is_an_error() {
+        assert!(reloaded_maintenance_mode(&values(&[("MAINTENANCE_MODE", "maybe")]), &HashMap::new()).is_err());
+        assert!(runtime_settings(&values(&[("MAINTENANCE_UNTIL", "tomorrow")]), &HashMap::new()).is_err());
+    }
+
+    #[test]
+    fn parse_errors_leave_the_line_out() {
+        let path: PathBuf = std::env::temp_dir().join(format!("reload-parse-test-{}.env", uuid::Uuid::new_v4().simple()));
+        std::fs::write(&path, "RUST_LOG=debug\n# comment\nSECRET_KEY=\"s3cret\n").unwrap();
+        let error: dotenvy::Error = read_settings_file(&path).unwrap_err();
+        let described: String = describe_read_error(&path, &error);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(described, format!("Failed to parse {} at line 3", path.display()));
+    }
+
+    #[test]
+    fn settings_file_is_parsed_fresh() {
+        let path: PathBuf = std::env::temp_dir().join(format!("reload-test-{}.env", std::process::id()));
+        std::fs::write(&path, "CORS_ALLOWED_ORIGINS=https://a.example\nRUST_LOG=debug\n").unwrap();
+        let first: HashMap<String, String> = read_settings_file(&path).unwrap();
+        std::fs::write(&path, "RUST_LOG=debug\n").unwrap();
+        let second: HashMap<String, String> = read_settings_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(runtime_settings(&first, &HashMap::new()).unwrap().cors_origins, ["https://a.example"]);
+        assert_eq!(runtime_settings(&second, &HashMap::new()).unwrap().cors_origins.join(","), RuntimeSettings::DEFAULT_CORS_ORIGINS);
+    }
+
+    #[test]
+    fn empty_cors_origins_allow_no_other_origin() {
+        assert!(runtime_settings(&values(&[("CORS_ALLOWED_ORIGINS", "")]), &HashMap::new()).unwrap().cors_origins.is_empty());
+    }
+}
@@ -0,0 +1,247 @@
+use actix_web::{
+    body::{self, BoxBody, BodySize, EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::{self, HeaderName, HeaderValue},
+    middleware::Next,
+    HttpMessage, HttpRequest, HttpResponse,
+};
+use tracing::{field, Instrument, Span};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use actix_svelte::otel;
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+/// Ids sent by clients or proxies longer than this are replaced
+const MAX_REQUEST_ID_LEN: usize = 128;
+/// Error bodies up to this size get the id added, larger ones only carry the header
+const MAX_ERROR_BODY: usize = 64 * 1024;
+
+/// Kept in the request extensions by `request_context`
+#[derive(Clone, Debug)]
+struct RequestContext {
+    id: String,
+    span: Span,
+}
+
+/// Id of the request, as echoed in the `X-Request-Id` header
+pub fn request_id(req: &HttpRequest) -> Option<String> {
+    req.extensions().get::<RequestContext>().map(|context| context.id.clone())
+}
+
+/// Adds the user to the request's span, so every later log line of the request names them
+pub fn record_user(req: &HttpRequest, username: &str) {
+    if let Some(context) = req.extensions().get::<RequestContext>() {
+        context.span.record("user", username);
+    }
+}
+
+/// Reuses the id a proxy or client sent when it looks like one, otherwise a new UUID v4
+fn incoming_or_new_id(req: &ServiceRequest) -> String {
+    req.headers()
+        .get(&REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|id| {
+            !id.is_empty()
+                && id.len() <= MAX_REQUEST_ID_LEN
+                && id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        })
+        .map(str::to_string)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string())
+}
+
+/// #### Request Context
+/// Tags each request with an id: logged in a span together with the method, route template, user
+/// and client IP, echoed in `X-Request-Id` and added to error bodies so a reported error can be
+/// found in the logs. The span is also the server span exported to OpenTelemetry, continuing the
+/// trace of a caller that sent a W3C `traceparent` header.
+pub async fn request_context(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody, BoxBody>>, actix_web::Error> {
+    let id: String = incoming_or_new_id(&req);
+    // The resource map is complete before routing, so the template is known up front
+    let route: String = req.match_pattern().unwrap_or_else(|| "unmatched".to_string());
+    let span: Span = tracing::info_span!(
+        "request",
+        otel.name = %format!("{} {}", req.method(), route),
+        otel.kind = "server",
+        request_id = %id,
+        method = %req.method(),
+        route = %route,
+        user = field::Empty,
+        // Honors `Forwarded` and `X-Forwarded-For`, set by the reverse proxy in front of the app
+        client_ip = req.connection_info().realip_remote_addr().unwrap_or("unknown"),
+        http.response.status_code = field::Empty,
+        otel.status_code = field::Empty,
+    );
+    if req.headers().contains_key("traceparent") {
+        // Only fails when spans aren't exported, then there is no trace to continue
+        let _ = span.set_parent(otel::parent_context(req.headers()));
+    }
+    req.extensions_mut().insert(RequestContext { id: id.clone(), span: span.clone() });
+
+    // Handler errors already are responses here, only failing middleware ends up as `Err`
+    let res: ServiceResponse<_> = next.call(req).instrument(span.clone()).await?;
+    span.record("http.response.status_code", res.status().as_u16());
+    if res.status().is_server_error() {
+        span.record("otel.status_code", "error");
+    }
+    let res: ServiceResponse<EitherBody<_, BoxBody>> = res.map_into_left_body();
+    let mut res: ServiceResponse<EitherBody<_, BoxBody>> = match res.status().is_client_error() || res.status().is_server_error() {
+        true => with_id_in_body(res, &id).instrument(span).await,
+        false => res,
+    };
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        res.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+
+    Ok(res)
+}
+
+/// JSON objects get a `request_id` field, plain text a last line with the id and empty bodies
+/// become `{"error": ..., "request_id": ...}`. Pages and large or streamed bodies are left alone.
+async fn with_id_in_body<B: MessageBody + 'static>(
+    res: ServiceResponse<EitherBody<B, BoxBody>>,
+    id: &str,
+) -> ServiceResponse<EitherBody<B, BoxBody>> {
+    let content_type: String = res
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    let is_json: bool = content_type.starts_with("application/json");
+    let is_text: bool = content_type.starts_with("text/plain");
+    let size: BodySize = res.response().body().size();
+    let rewrite: bool = res.request().method() != actix_web::http::Method::HEAD
+        && match size {
+            BodySize::None => true,
+            BodySize::Sized(0) => true,
+            BodySize::Sized(len) => (is_json || is_text) && len <= MAX_ERROR_BODY as u64,
+            BodySize::Stream => false,
+        };
+    if !rewrite {
+        return res;
+    }
+
+    let (http_req, res) = res.into_parts();
+    let (head, old_body) = res.into_parts();
+    let Ok(Ok(old_body)) = body::to_bytes_limited(old_body, MAX_ERROR_BODY).await else {
+        tracing::event!(target: "backend", tracing::Level::WARN, "Failed to read the error body to add the request id");
+        return ServiceResponse::new(http_req, head.set_body(BoxBody::new(())).map_into_right_body());
+    };
+
+    let new_body: Option<(String, &str)> = if old_body.is_empty() {
+        let reason: &str = head.status().canonical_reason().unwrap_or("Error");
+        Some((serde_json::json!({ "error": reason, "request_id": id }).to_string(), "application/json"))
+    } else if is_json {
+        match serde_json::from_slice::<serde_json::Value>(&old_body) {
+            Ok(serde_json::Value::Object(mut object)) => {
+                object.insert("request_id".to_string(), serde_json::Value::String(id.to_string()));
+                Some((serde_json::Value::Object(object).to_string(), "application/json"))
+            }
+            _ => None,
+        }
+    } else {
+        Some((format!("{}\nRequest ID: {}", String::from_utf8_lossy(&old_body).trim_end(), id), content_type.as_str()))
+    };
+
+    let res: HttpResponse<EitherBody<B, BoxBody>> = match new_body {
+        Some((new_body, content_type)) => {
+            let mut res: HttpResponse<BoxBody> = head.set_body(BoxBody::new(new_body));
+            res.headers_mut().remove(header::CONTENT_LENGTH);
+            if let Ok(content_type) = HeaderValue::from_str(content_type) {
+                res.headers_mut().insert(header::CONTENT_TYPE, content_type);
+            }
+            res.map_into_right_body()
+        }
+        None => head.set_body(BoxBody::new(old_body)).map_into_right_body(),
+    };
+
+    ServiceResponse::new(http_req, res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{
+        http::StatusCode,
+        middleware::from_fn,
+        test::{call_service, init_service, read_body, TestRequest},
+        web, App,
+    };
+
+    /// A `macro_rules!` because the type of an initialized test service can't be named here
+    macro_rules! app {
+        () => {
+            init_service(
+                App::new()
+                    .wrap(from_fn(request_context))
+                    .route("/ok", web::get().to(|req: HttpRequest| async move { request_id(&req).unwrap_or_default() }))
+                    .route("/json", web::get().to(|| async { HttpResponse::BadRequest().json(serde_json::json!({ "error": "bad input" })) }))
+                    .route("/text", web::get().to(|| async { HttpResponse::NotFound().content_type("text/plain").body("no such thing\n") }))
+                    .route("/empty", web::get().to(|| async { HttpResponse::InternalServerError().finish() }))
+                    .route("/page", web::get().to(|| async { HttpResponse::ServiceUnavailable().content_type("text/html").body("<p>down</p>") })),
+            )
+            .await
+        };
+    }
+
+    fn response_id<B>(res: &ServiceResponse<B>) -> String {
+        res.headers().get(&REQUEST_ID_HEADER).unwrap().to_str().unwrap().to_string()
+    }
+
+    #[actix_web::test]
+    async fn well_formed_incoming_id_is_kept() {
+        let app = app!();
+        let res = call_service(&app, TestRequest::get().uri("/ok").insert_header((REQUEST_ID_HEADER, "proxy-42.a_b")).to_request()).await;
+
+        assert_eq!(response_id(&res), "proxy-42.a_b");
+        assert_eq!(read_body(res).await, "proxy-42.a_b");
+    }
+
+    #[actix_web::test]
+    async fn long_or_invalid_incoming_id_is_replaced() {
+        let app = app!();
+        let too_long: String = "a".repeat(MAX_REQUEST_ID_LEN + 1);
+        let longest: String = "a".repeat(MAX_REQUEST_ID_LEN);
+        assert_eq!(response_id(&call_service(&app, TestRequest::get().uri("/ok").insert_header((REQUEST_ID_HEADER, longest.as_str())).to_request()).await), longest);
+
+        for incoming in [too_long.as_str(), "with space", "quote\"<script>", "semi;colon", ""] {
+            let res = call_service(&app, TestRequest::get().uri("/ok").insert_header((REQUEST_ID_HEADER, incoming)).to_request()).await;
+            let id: String = response_id(&res);
+            assert_ne!(id, incoming);
+            assert!(uuid::Uuid::parse_str(&id).is_ok(), "{} replaced with {}", incoming, id);
+        }
+    }
+
+    #[actix_web::test]
+    async fn id_is_added_to_error_bodies() {
+        let app = app!();
+
+        let res = call_service(&app, TestRequest::get().uri("/json").to_request()).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        let id: String = response_id(&res);
+        let body: serde_json::Value = serde_json::from_slice(&read_body(res).await).unwrap();
+        assert_eq!(body, serde_json::json!({ "error": "bad input", "request_id": id }));
+
+        let res = call_service(&app, TestRequest::get().uri("/text").insert_header((REQUEST_ID_HEADER, "from-proxy")).to_request()).await;
+        assert_eq!(read_body(res).await, "no such thing\nRequest ID: from-proxy");
+
+        let res = call_service(&app, TestRequest::get().uri("/empty").to_request()).await;
+        let id: String = response_id(&res);
+        assert_eq!(res.headers().get(header::CONTENT_TYPE).unwrap(), "application/json");
+        let body: serde_json::Value = serde_json::from_slice(&read_body(res).await).unwrap();
+        assert_eq!(body, serde_json::json!({ "error": "Internal Server Error", "request_id": id }));
+    }
+
+    #[actix_web::test]
+    async fn pages_keep_their_body() {
+        let app = app!();
+
+        let res = call_service(&app, TestRequest::get().uri("/page").to_request()).await;
+        assert!(res.headers().contains_key(&REQUEST_ID_HEADER));
+        assert_eq!(read_body(res).await, "<p>down</p>");
+    }
+}
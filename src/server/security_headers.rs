@@ -0,0 +1,219 @@
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::{self, HeaderMap, HeaderName, HeaderValue},
+    middleware::Next,
+    web::Data,
+    HttpMessage,
+};
+
+use super::{
+    bootstrap::CspNonce,
+    settings::{CspMode, SecuritySettings},
+};
+
+/// Violations are posted here by browsers, in both enforce and report-only mode. Relative to `BASE_PATH`.
+pub const CSP_REPORT_PATH: &str = "/api/csp-report";
+
+/// #### Security Headers
+/// Headers added to every response by the `security_headers` middleware. The CSP can be replaced
+/// for paths under a prefix with `scope`, e.g. for the Swagger UI. Headers a handler already set are kept.
+#[derive(Clone, Debug)]
+pub struct SecurityHeaders {
+    csp_mode: CspMode,
+    default_policy: String,
+    /// Path prefixes with their own policy, the longest matching prefix wins
+    scopes: Vec<(String, String)>,
+    frame_ancestors: String,
+    /// `CSP_REPORT_PATH` below the base path
+    report_uri: String,
+    hsts: Option<String>,
+    referrer_policy: String,
+    permissions_policy: String,
+}
+
+impl SecurityHeaders {
+    pub fn new(settings: &SecuritySettings, tls_enabled: bool, base_path: &str) -> Self {
+        Self {
+            csp_mode: settings.csp_mode,
+            default_policy: settings.csp_policy.clone(),
+            scopes: Vec::new(),
+            frame_ancestors: settings.frame_ancestors.clone(),
+            report_uri: format!("{}{}", base_path, CSP_REPORT_PATH),
+            hsts: (tls_enabled && settings.hsts_max_age > 0)
+                .then(|| format!("max-age={}; includeSubDomains", settings.hsts_max_age)),
+            referrer_policy: settings.referrer_policy.clone(),
+            permissions_policy: settings.permissions_policy.clone(),
+        }
+    }
+
+    /// Uses `policy` instead of the default CSP for every path starting with `prefix`
+    pub fn scope(mut self, prefix: &str, policy: &str) -> Self {
+        self.scopes.push((prefix.to_string(), policy.to_string()));
+        self
+    }
+
+    pub fn with_csp_mode(mut self, csp_mode: CspMode) -> Self {
+        self.csp_mode = csp_mode;
+        self
+    }
+
+    fn policy_for(&self, path: &str) -> &str {
+        self.scopes
+            .iter()
+            .filter(|(prefix, _)| path.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, policy)| policy.as_str())
+            .unwrap_or(&self.default_policy)
+    }
+
+    /// The policy for `path` with the nonce filled in. Responses without a nonce (anything but
+    /// HTML pages) drop the `'nonce-{nonce}'` source instead of allowing a predictable value.
+    fn content_security_policy(&self, path: &str, nonce: Option<&str>) -> String {
+        let policy: String = match nonce {
+            Some(nonce) => self.policy_for(path).replace("{nonce}", nonce),
+            None => self.policy_for(path).replace(" 'nonce-{nonce}'", "").replace("'nonce-{nonce}'", ""),
+        };
+
+        format!(
+            "{}; frame-ancestors {}; report-uri {}",
+            policy.trim().trim_end_matches(';'),
+            self.frame_ancestors,
+            self.report_uri
+        )
+    }
+
+    fn apply(&self, headers: &mut HeaderMap, path: &str, nonce: Option<&str>) {
+        let csp_header: Option<HeaderName> = match self.csp_mode {
+            CspMode::Enforce => Some(header::CONTENT_SECURITY_POLICY),
+            CspMode::ReportOnly => Some(header::CONTENT_SECURITY_POLICY_REPORT_ONLY),
+            CspMode::Off => None,
+        };
+        if let Some(csp_header) = csp_header {
+            insert_missing(headers, csp_header, &self.content_security_policy(path, nonce));
+        }
+        if let Some(hsts) = &self.hsts {
+            insert_missing(headers, header::STRICT_TRANSPORT_SECURITY, hsts);
+        }
+        insert_missing(headers, header::X_CONTENT_TYPE_OPTIONS, "nosniff");
+        insert_missing(headers, header::REFERRER_POLICY, &self.referrer_policy);
+        insert_missing(headers, HeaderName::from_static("permissions-policy"), &self.permissions_policy);
+    }
+}
+
+fn insert_missing(headers: &mut HeaderMap, name: HeaderName, value: &str) {
+    if headers.contains_key(&name) {
+        return;
+    }
+    match HeaderValue::from_str(value) {
+        Ok(value) => {
+            headers.insert(name, value);
+        }
+        Err(_) => {
+            tracing::event!(target: "backend", tracing::Level::ERROR, "Invalid value for the {} header: '{}'", name, value);
+        }
+    }
+}
+
+/// Adds the headers configured in the `SecurityHeaders` app data to every response
+pub async fn security_headers(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let config: Option<Data<SecurityHeaders>> = req.app_data::<Data<SecurityHeaders>>().cloned();
+    let mut res: ServiceResponse<_> = next.call(req).await?;
+
+    if let Some(config) = config {
+        // The page handler stores the nonce it put into the HTML, see `bootstrap::csp_nonce`
+        let nonce: Option<String> = res.request().extensions().get::<CspNonce>().map(|nonce| nonce.0.clone());
+        let path: String = res.request().path().to_string();
+        config.apply(res.headers_mut(), &path, nonce.as_deref());
+    }
+
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> SecuritySettings {
+        SecuritySettings {
+            csp_mode: CspMode::Enforce,
+            csp_policy: "default-src 'self'; script-src 'self' 'nonce-{nonce}'".to_string(),
+            swagger_csp_policy: String::new(),
+            frame_ancestors: "'none'".to_string(),
+            hsts_max_age: 3600,
+            referrer_policy: "no-referrer".to_string(),
+            permissions_policy: "camera=()".to_string(),
+        }
+    }
+
+    fn applied(config: &SecurityHeaders, path: &str, nonce: Option<&str>) -> HeaderMap {
+        let mut headers: HeaderMap = HeaderMap::new();
+        config.apply(&mut headers, path, nonce);
+        headers
+    }
+
+    #[test]
+    fn longest_prefix_picks_the_policy() {
+        let config: SecurityHeaders = SecurityHeaders::new(&settings(), false, "")
+            .scope("/api", "default-src 'none'")
+            .scope("/api/swagger-ui", "default-src 'self' 'unsafe-inline'");
+
+        assert_eq!(config.policy_for("/api/swagger-ui/index.html"), "default-src 'self' 'unsafe-inline'");
+        assert_eq!(config.policy_for("/api/users"), "default-src 'none'");
+        assert_eq!(config.policy_for("/"), settings().csp_policy);
+    }
+
+    #[test]
+    fn nonce_is_filled_in_or_dropped() {
+        let config: SecurityHeaders = SecurityHeaders::new(&settings(), false, "/app");
+
+        assert_eq!(
+            config.content_security_policy("/", Some("abc123")),
+            "default-src 'self'; script-src 'self' 'nonce-abc123'; frame-ancestors 'none'; report-uri /app/api/csp-report"
+        );
+        assert_eq!(
+            config.content_security_policy("/", None),
+            "default-src 'self'; script-src 'self'; frame-ancestors 'none'; report-uri /app/api/csp-report"
+        );
+    }
+
+    #[test]
+    fn hsts_only_with_tls() {
+        assert!(!applied(&SecurityHeaders::new(&settings(), false, ""), "/", None).contains_key(header::STRICT_TRANSPORT_SECURITY));
+        let headers: HeaderMap = applied(&SecurityHeaders::new(&settings(), true, ""), "/", None);
+        assert_eq!(headers.get(header::STRICT_TRANSPORT_SECURITY).unwrap(), "max-age=3600; includeSubDomains");
+
+        let disabled: SecuritySettings = SecuritySettings { hsts_max_age: 0, ..settings() };
+        assert!(!applied(&SecurityHeaders::new(&disabled, true, ""), "/", None).contains_key(header::STRICT_TRANSPORT_SECURITY));
+    }
+
+    #[test]
+    fn csp_mode_picks_the_header() {
+        let enforced: HeaderMap = applied(&SecurityHeaders::new(&settings(), false, ""), "/", None);
+        assert!(enforced.contains_key(header::CONTENT_SECURITY_POLICY));
+        assert!(!enforced.contains_key(header::CONTENT_SECURITY_POLICY_REPORT_ONLY));
+
+        let report_only: HeaderMap = applied(&SecurityHeaders::new(&settings(), false, "").with_csp_mode(CspMode::ReportOnly), "/", None);
+        assert!(!report_only.contains_key(header::CONTENT_SECURITY_POLICY));
+        assert!(report_only.contains_key(header::CONTENT_SECURITY_POLICY_REPORT_ONLY));
+
+        let off: HeaderMap = applied(&SecurityHeaders::new(&settings(), false, "").with_csp_mode(CspMode::Off), "/", None);
+        assert!(!off.contains_key(header::CONTENT_SECURITY_POLICY) && !off.contains_key(header::CONTENT_SECURITY_POLICY_REPORT_ONLY));
+        assert_eq!(off.get(header::X_CONTENT_TYPE_OPTIONS).unwrap(), "nosniff");
+    }
+
+    #[test]
+    fn headers_set_by_the_handler_are_kept() {
+        let mut headers: HeaderMap = HeaderMap::new();
+        headers.insert(header::CONTENT_SECURITY_POLICY, HeaderValue::from_static("sandbox"));
+        headers.insert(header::REFERRER_POLICY, HeaderValue::from_static("origin"));
+        SecurityHeaders::new(&settings(), false, "").apply(&mut headers, "/", None);
+
+        assert_eq!(headers.get(header::CONTENT_SECURITY_POLICY).unwrap(), "sandbox");
+        assert_eq!(headers.get(header::REFERRER_POLICY).unwrap(), "origin");
+        assert_eq!(headers.get("permissions-policy").unwrap(), "camera=()");
+    }
+}
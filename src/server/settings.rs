@@ -0,0 +1,695 @@
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+
+use super::maintenance::MaintenanceNotice;
+use actix_svelte::secrets::{secret_var, Secret};
+
+/// #### Settings
+/// Runtime configuration read from the environment (the `.env` file is loaded in `main`).
+#[derive(Clone, Debug)]
+pub struct Settings {
+    pub application: ApplicationSettings,
+    pub listen: ListenSettings,
+    pub http: HttpSettings,
+    pub tls: TlsSettings,
+    pub frontend: FrontendSettings,
+    pub security: SecuritySettings,
+    pub health: HealthSettings,
+    pub logging: LoggingSettings,
+    pub metrics: MetricsSettings,
+    pub admin: AdminSettings,
+    pub maintenance: MaintenanceSettings,
+    pub runtime: RuntimeSettings,
+}
+
+#[derive(Clone, Debug)]
+pub struct ApplicationSettings {
+    pub host: String,
+    pub port: u16,
+    pub app_name: String,
+    /// Watched for changes, see `server::reload`
+    pub settings_file: PathBuf,
+    /// Sub-URL the app is mounted at, e.g. `/cleanroom`, empty when served from the root
+    pub base_path: String,
+}
+
+/// Listeners besides the primary `HOST:PORT` one
+#[derive(Clone, Debug, Default)]
+pub struct ListenSettings {
+    /// Extra public addresses, e.g. the IPv6 twin of the primary address
+    pub addresses: Vec<SocketAddr>,
+    /// Addresses that also serve the admin routes (pause, unpause, ...)
+    pub admin_addresses: Vec<SocketAddr>,
+    pub unix_socket_path: Option<PathBuf>,
+    /// Octal permissions applied to the unix socket file, e.g. `660`
+    pub unix_socket_mode: u32,
+}
+
+/// Tuning of the actix `HttpServer` and the request body limits
+#[derive(Clone, Debug)]
+pub struct HttpSettings {
+    pub workers: usize,
+    /// Pending connections queue size of every TCP listener
+    pub backlog: u32,
+    /// Maximum concurrent connections per worker
+    pub max_connections: usize,
+    /// `None` disables keep-alive
+    pub keep_alive: Option<Duration>,
+    /// Time a client has to send the request head, zero disables the timeout
+    pub client_request_timeout: Duration,
+    /// Time a client has to acknowledge a connection shutdown, zero disables the timeout
+    pub client_disconnect_timeout: Duration,
+    /// Seconds workers get to finish in-flight requests on shutdown
+    pub shutdown_timeout: u64,
+    /// Maximum JSON body size in bytes
+    pub json_limit: usize,
+    /// Maximum raw body size in bytes for `Bytes` and `String` extractors
+    pub payload_limit: usize,
+}
+
+/// How the embedded SvelteKit client is served
+#[derive(Clone, Debug, Default)]
+pub struct FrontendSettings {
+    /// Embedded page sent with 404 responses for missing assets, e.g. `404.html`
+    pub not_found_page: Option<String>,
+    /// Vite dev server that non-API requests are proxied to, needs the `dev_proxy` feature
+    pub dev_server_url: Option<String>,
+    /// Folder whose files are served before the embedded ones, for patching the UI without a rebuild
+    pub static_dir: Option<PathBuf>,
+    /// Flags handed to the client in the page bootstrap data
+    pub feature_flags: BTreeMap<String, bool>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CspMode {
+    /// Browsers block what the policy doesn't allow
+    Enforce,
+    /// Browsers only report violations to `/api/csp-report`, for trying out a policy
+    ReportOnly,
+    Off,
+}
+
+/// Security headers added to every response, see `server::security_headers`
+#[derive(Clone, Debug)]
+pub struct SecuritySettings {
+    pub csp_mode: CspMode,
+    /// Policy of the app, `{nonce}` is replaced with the per-request nonce of the page
+    pub csp_policy: String,
+    /// Swagger UI relies on inline scripts and styles
+    pub swagger_csp_policy: String,
+    /// Value of the `frame-ancestors` directive, `'none'` forbids framing the app
+    pub frame_ancestors: String,
+    /// Seconds browsers remember to only use HTTPS, only sent when TLS is on. Zero disables HSTS.
+    pub hsts_max_age: u64,
+    pub referrer_policy: String,
+    pub permissions_policy: String,
+}
+
+/// Thresholds of the readiness probe, see `server::health`
+#[derive(Clone, Debug)]
+pub struct HealthSettings {
+    /// Time the database checks get before they count as failed
+    pub db_timeout: Duration,
+    /// Free space required on the volumes of the database and the logs
+    pub min_free_disk_bytes: u64,
+}
+
+/// Access to `/metrics`, see `server::metrics`
+#[derive(Clone, Debug)]
+pub struct MetricsSettings {
+    /// Bearer token scrapers can send instead of going through an admin listener
+    pub token: Option<Secret<String>>,
+}
+
+/// Credentials for the admin routes besides the admin listeners, see `server::admin_auth`
+#[derive(Clone, Debug, Default)]
+pub struct AdminSettings {
+    /// Bearer token for scripts calling the admin routes
+    pub token: Option<Secret<String>>,
+    /// Users whose session grants access to the admin routes, these names can no longer be registered
+    pub users: Vec<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogFormat {
+    /// One line per event with the span context, the default
+    Full,
+    /// Multi-line and indented, easiest to read in a terminal
+    Pretty,
+    /// One line per event, span fields without span names
+    Compact,
+    /// Newline-delimited JSON with the current span and its parents, for log shippers
+    Json,
+}
+
+/// When log files start over, besides reaching `LOG_MAX_FILE_MB`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogInterval {
+    Minutely,
+    Hourly,
+    Daily,
+    Never,
+}
+
+/// A log file that only gets events matching its own filter, e.g. an access log
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LogFileTarget {
+    /// File name prefix, e.g. `access` gives `access.2026-10-19.log`
+    pub name: String,
+    /// `EnvFilter` directives, e.g. `actix_web::middleware::logger=info`
+    pub filter: String,
+}
+
+/// Read by `telemetry::setup_logging` before anything else, changes need a restart except for `RUST_LOG`
+#[derive(Clone, Debug)]
+pub struct LoggingSettings {
+    /// Relative paths are resolved against the working directory
+    pub directory: PathBuf,
+    /// Start of the main log's file names, empty gives `2026-10-19.log`
+    pub file_prefix: String,
+    pub rotation: LogInterval,
+    /// A file that would grow past this continues in `<period>.1.log`, `<period>.2.log`, ...
+    pub max_file_bytes: Option<u64>,
+    /// Files kept per log, the current one included, older ones are deleted
+    pub max_files: Option<usize>,
+    /// Rotated files last written longer ago are deleted
+    pub max_age: Option<Duration>,
+    /// Gzip rotated files
+    pub compress: bool,
+    pub console_format: LogFormat,
+    pub file_format: LogFormat,
+    /// Extra files next to the main log, which still gets everything `RUST_LOG` lets through
+    pub target_files: Vec<LogFileTarget>,
+    /// Latest log lines kept in memory for the admin log console
+    pub buffer_lines: usize,
+    /// Span export, off unless an OTLP endpoint is configured
+    pub otlp: Option<OtlpSettings>,
+}
+
+/// Export of the request spans to an OpenTelemetry collector, see `actix_svelte::otel`
+#[derive(Clone, Debug)]
+pub struct OtlpSettings {
+    /// Full traces URL, e.g. `http://localhost:4318/v1/traces`
+    pub endpoint: String,
+    /// `EnvFilter` directives for the exported spans, independent of `RUST_LOG`
+    pub filter: String,
+    /// The app name and version of `AppState`
+    pub service_name: String,
+    pub service_version: String,
+}
+
+/// Maintenance mode, see `server::maintenance`. Its message and end time are runtime settings.
+#[derive(Clone, Debug)]
+pub struct MaintenanceSettings {
+    /// `MAINTENANCE_MODE` at startup, reloads pause or resume when the value in the settings file changes
+    pub start_paused: bool,
+    /// Path prefixes relative to `BASE_PATH` that are still served during maintenance
+    pub allow_paths: Vec<String>,
+    /// Sent as `Retry-After` when the maintenance has no scheduled end
+    pub retry_after: Duration,
+}
+
+/// Settings that are re-applied while the server runs, everything else needs a restart
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RuntimeSettings {
+    /// `EnvFilter` directives, e.g. `info,sqlx=warn`
+    pub log_filter: String,
+    pub cors_origins: Vec<String>,
+    /// Shown on the maintenance page
+    pub maintenance_message: Option<String>,
+    /// Scheduled end of the maintenance
+    pub maintenance_until: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TlsMode {
+    /// Plain HTTP only
+    Disabled,
+    /// Certificate chain and private key loaded from PEM files, reloaded when they change
+    Pem,
+    /// Certificate generated at startup, for development only
+    SelfSigned,
+}
+
+#[derive(Clone, Debug)]
+pub struct TlsSettings {
+    pub mode: TlsMode,
+    pub cert_path: Option<PathBuf>,
+    pub key_path: Option<PathBuf>,
+    /// Plain HTTP port that redirects every request to the HTTPS listener
+    pub redirect_port: Option<u16>,
+}
+
+impl Settings {
+    pub fn from_env() -> Self {
+        Self::with_application(ApplicationSettings::from_env())
+    }
+
+    /// Reads every section from the environment except the listen address
+    pub fn with_application(application: ApplicationSettings) -> Self {
+        Self {
+            application,
+            listen: ListenSettings::from_env(),
+            http: HttpSettings::from_env(),
+            tls: TlsSettings::from_env(),
+            frontend: FrontendSettings::from_env(),
+            security: SecuritySettings::from_env(),
+            health: HealthSettings::from_env(),
+            logging: LoggingSettings::from_env(),
+            metrics: MetricsSettings::from_env(),
+            admin: AdminSettings::from_env(),
+            maintenance: MaintenanceSettings::from_env(),
+            runtime: RuntimeSettings::from_env(),
+        }
+    }
+}
+
+impl ApplicationSettings {
+    pub fn from_env() -> Self {
+        let host: String = dotenvy::var("HOST").expect("HOST must be set");
+        let port: u16 = dotenvy::var("ASPNETCORE_PORT")
+            .or_else(|_| dotenvy::var("PORT"))
+            .unwrap_or_else(|_| "5000".to_string()) // Default to 5000 if nothing is set
+            .parse::<u16>()
+            .expect("PORT must be a number");
+
+        Self::with_address(host, port)
+    }
+
+    /// Used by the Windows service, whose address is baked in at compile time
+    pub fn with_address(host: String, port: u16) -> Self {
+        let app_name: String = app_name();
+        let settings_file: PathBuf = env_or("SETTINGS_FILE", PathBuf::from(".env"));
+        let base_path: String = normalize_base_path(&env_or("BASE_PATH", String::new()));
+
+        Self { host, port, app_name, settings_file, base_path }
+    }
+}
+
+/// `cleanroom`, `/cleanroom/` and `/cleanroom` all become `/cleanroom`, `/` becomes empty.
+/// Same rules as SvelteKit's `paths.base`: a leading slash and no trailing slash.
+pub fn normalize_base_path(base_path: &str) -> String {
+    let trimmed: &str = base_path.trim().trim_matches('/');
+    if trimmed.is_empty() {
+        String::new()
+    } else {
+        format!("/{}", trimmed)
+    }
+}
+
+impl ListenSettings {
+    pub fn from_env() -> Self {
+        let unix_socket_mode: String = env_or("UNIX_SOCKET_MODE", "660".to_string());
+
+        Self {
+            addresses: socket_addresses("LISTEN_ADDRESSES"),
+            admin_addresses: socket_addresses("ADMIN_LISTEN_ADDRESSES"),
+            unix_socket_path: env_or::<Option<PathBuf>>("UNIX_SOCKET_PATH", None),
+            unix_socket_mode: u32::from_str_radix(&unix_socket_mode, 8)
+                .unwrap_or_else(|_| panic!("UNIX_SOCKET_MODE has an invalid value: '{}'", unix_socket_mode)),
+        }
+    }
+}
+
+/// Comma separated list of socket addresses, IPv6 addresses use brackets: `[::]:8041`
+fn socket_addresses(key: &str) -> Vec<SocketAddr> {
+    dotenvy::var(key)
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|address| !address.is_empty())
+        .map(|address| {
+            address
+                .parse::<SocketAddr>()
+                .unwrap_or_else(|_| panic!("{} has an invalid address: '{}'", key, address))
+        })
+        .collect()
+}
+
+impl HttpSettings {
+    pub fn from_env() -> Self {
+        // One worker per CPU, the old single worker let one slow request stall every other one
+        let cpus: usize = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+        let keep_alive_secs: u64 = env_or("HTTP_KEEP_ALIVE_SECS", 5);
+
+        Self {
+            workers: env_or("HTTP_WORKERS", cpus).max(1),
+            backlog: env_or("HTTP_BACKLOG", 2048),
+            max_connections: env_or("HTTP_MAX_CONNECTIONS", 25_000),
+            keep_alive: (keep_alive_secs > 0).then(|| Duration::from_secs(keep_alive_secs)),
+            client_request_timeout: Duration::from_millis(env_or("HTTP_CLIENT_REQUEST_TIMEOUT_MS", 5_000)),
+            client_disconnect_timeout: Duration::from_millis(env_or("HTTP_CLIENT_DISCONNECT_TIMEOUT_MS", 1_000)),
+            shutdown_timeout: env_or("HTTP_SHUTDOWN_TIMEOUT_SECS", 5),
+            json_limit: env_or("HTTP_JSON_LIMIT", 2 * 1024 * 1024),
+            payload_limit: env_or("HTTP_PAYLOAD_LIMIT", 256 * 1024),
+        }
+    }
+}
+
+impl HealthSettings {
+    pub fn from_env() -> Self {
+        Self {
+            db_timeout: Duration::from_millis(env_or("HEALTH_DB_TIMEOUT_MS", 2_000)),
+            min_free_disk_bytes: env_or("HEALTH_MIN_FREE_DISK_MB", 100u64) * 1024 * 1024,
+        }
+    }
+}
+
+impl MetricsSettings {
+    pub fn from_env() -> Self {
+        Self {
+            token: secret_var("METRICS_TOKEN").filter(|token| !token.expose().is_empty()),
+        }
+    }
+}
+
+impl AdminSettings {
+    pub fn from_env() -> Self {
+        Self {
+            token: secret_var("ADMIN_TOKEN").filter(|token| !token.expose().is_empty()),
+            users: dotenvy::var("ADMIN_USERS")
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|user| !user.is_empty())
+                .map(str::to_string)
+                .collect(),
+        }
+    }
+
+    /// `false` when the admin routes can only be reached through an admin listener
+    pub fn has_credentials(&self) -> bool {
+        self.token.is_some() || !self.users.is_empty()
+    }
+}
+
+impl LoggingSettings {
+    pub fn from_env() -> Self {
+        let rotation: LogInterval = match dotenvy::var("LOG_ROTATION").unwrap_or_default().trim().to_lowercase().as_str() {
+            "" | "daily" => LogInterval::Daily,
+            "hourly" => LogInterval::Hourly,
+            "minutely" => LogInterval::Minutely,
+            "never" | "size" => LogInterval::Never,
+            other => panic!("Unknown LOG_ROTATION '{}', expected daily, hourly, minutely or never", other),
+        };
+
+        Self {
+            directory: env_or("LOG_DIR", PathBuf::from("./logs")),
+            file_prefix: env_or("LOG_FILE_PREFIX", String::new()),
+            rotation,
+            max_file_bytes: env_or::<Option<u64>>("LOG_MAX_FILE_MB", None).filter(|mb| *mb > 0).map(|mb| mb * 1024 * 1024),
+            max_files: env_or::<Option<usize>>("LOG_MAX_FILES", None).filter(|files| *files > 0),
+            max_age: env_or::<Option<u64>>("LOG_MAX_AGE_DAYS", None).filter(|days| *days > 0).map(|days| Duration::from_secs(days * 24 * 60 * 60)),
+            compress: env_or("LOG_COMPRESS", false),
+            console_format: log_format("LOG_CONSOLE_FORMAT"),
+            file_format: log_format("LOG_FILE_FORMAT"),
+            target_files: log_file_targets("LOG_TARGET_FILES"),
+            buffer_lines: env_or("LOG_BUFFER_LINES", 1_000),
+            otlp: OtlpSettings::from_env(),
+        }
+    }
+}
+
+impl OtlpSettings {
+    /// `OTEL_EXPORTER_OTLP_TRACES_ENDPOINT` as is, or `OTEL_EXPORTER_OTLP_ENDPOINT` with `/v1/traces`
+    /// appended like the OpenTelemetry SDKs do. `OTEL_SDK_DISABLED=true` turns the export off.
+    pub fn from_env() -> Option<Self> {
+        if env_or("OTEL_SDK_DISABLED", false) {
+            return None;
+        }
+        let endpoint: String = match env_or::<Option<String>>("OTEL_EXPORTER_OTLP_TRACES_ENDPOINT", None) {
+            Some(endpoint) => endpoint,
+            None => format!("{}/v1/traces", env_or::<Option<String>>("OTEL_EXPORTER_OTLP_ENDPOINT", None)?.trim_end_matches('/')),
+        };
+
+        Some(Self {
+            endpoint,
+            filter: env_or("OTEL_TRACES_FILTER", "info".to_string()),
+            service_name: env_or("OTEL_SERVICE_NAME", app_name()),
+            service_version: env!("CARGO_PKG_VERSION").to_string(),
+        })
+    }
+}
+
+fn app_name() -> String {
+    dotenvy::var("APP_NAME").unwrap_or_else(|_| "App Template".to_string())
+}
+
+fn log_format(key: &str) -> LogFormat {
+    let value: String = dotenvy::var(key).unwrap_or_default();
+    LogFormat::from_env(&value)
+        .unwrap_or_else(|| panic!("Unknown {} '{}', expected full, pretty, compact or json", key, value.trim()))
+}
+
+/// `name:directives` pairs separated by `;`, e.g. `access:actix_web::middleware::logger=info;security:backend=warn,chaos=info`
+fn log_file_targets(key: &str) -> Vec<LogFileTarget> {
+    dotenvy::var(key)
+        .unwrap_or_default()
+        .split(';')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (name, filter) = entry
+                .split_once(':')
+                .map(|(name, filter)| (name.trim(), filter.trim()))
+                .filter(|(name, filter)| !filter.is_empty() && !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+                .unwrap_or_else(|| panic!("{} has an invalid entry '{}', expected name:directives", key, entry));
+            LogFileTarget { name: name.to_string(), filter: filter.to_string() }
+        })
+        .collect()
+}
+
+impl MaintenanceSettings {
+    /// Probes and admin routes, plus signing in so `ADMIN_USERS` can still get an admin session
+    pub const DEFAULT_ALLOW_PATHS: &'static str =
+        "/api/health,/api/version,/api/pause,/api/unpause,/api/admin,/metrics,/api/auth/login,/api/auth/protected";
+
+    pub fn from_env() -> Self {
+        let allow_paths: String = dotenvy::var("MAINTENANCE_ALLOW_PATHS").unwrap_or_else(|_| Self::DEFAULT_ALLOW_PATHS.to_string());
+
+        Self {
+            start_paused: RuntimeSettings::maintenance_mode(|key| dotenvy::var(key).ok()).unwrap_or_else(|e| panic!("{}", e)),
+            allow_paths: Self::parse_allow_paths(&allow_paths),
+            retry_after: Duration::from_secs(env_or("MAINTENANCE_RETRY_AFTER_SECS", 300)),
+        }
+    }
+
+    /// Comma separated prefixes, a trailing `/` is dropped
+    pub fn parse_allow_paths(allow_paths: &str) -> Vec<String> {
+        allow_paths
+            .split(',')
+            .map(|path| path.trim().trim_end_matches('/').to_string())
+            .filter(|path| !path.is_empty())
+            .collect()
+    }
+}
+
+impl TlsSettings {
+    pub fn from_env() -> Self {
+        let mode: TlsMode = match dotenvy::var("TLS_MODE").unwrap_or_default().to_lowercase().as_str() {
+            "" | "off" | "disabled" | "false" => TlsMode::Disabled,
+            "pem" | "on" | "true" => TlsMode::Pem,
+            "self-signed" | "self_signed" => TlsMode::SelfSigned,
+            other => panic!("Unknown TLS_MODE '{}', expected off, pem or self-signed", other),
+        };
+        let cert_path: Option<PathBuf> = dotenvy::var("TLS_CERT_PATH").ok().map(PathBuf::from);
+        let key_path: Option<PathBuf> = dotenvy::var("TLS_KEY_PATH").ok().map(PathBuf::from);
+        if mode == TlsMode::Pem && (cert_path.is_none() || key_path.is_none()) {
+            panic!("TLS_CERT_PATH and TLS_KEY_PATH must be set when TLS_MODE=pem");
+        }
+
+        Self {
+            mode,
+            cert_path,
+            key_path,
+            redirect_port: env_or::<Option<u16>>("TLS_REDIRECT_PORT", None),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.mode != TlsMode::Disabled
+    }
+}
+
+impl FrontendSettings {
+    pub fn from_env() -> Self {
+        Self {
+            not_found_page: env_or::<Option<String>>("NOT_FOUND_PAGE", None)
+                .map(|page| page.trim_start_matches('/').to_string()),
+            dev_server_url: env_or::<Option<String>>("FRONTEND_DEV_URL", None),
+            static_dir: env_or::<Option<PathBuf>>("STATIC_DIR", None),
+            feature_flags: feature_flags("FEATURE_FLAGS"),
+        }
+    }
+}
+
+/// Comma separated flags, a bare name is enabled: `new_dashboard,beta_charts=false`
+fn feature_flags(key: &str) -> BTreeMap<String, bool> {
+    dotenvy::var(key)
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|flag| !flag.is_empty())
+        .map(|flag| match flag.split_once('=') {
+            Some((name, value)) => {
+                let enabled: bool = bool::from_env(value.trim())
+                    .unwrap_or_else(|| panic!("{} has an invalid value for '{}': '{}'", key, name.trim(), value));
+                (name.trim().to_string(), enabled)
+            }
+            None => (flag.to_string(), true),
+        })
+        .collect()
+}
+
+impl SecuritySettings {
+    pub fn from_env() -> Self {
+        let csp_mode: CspMode = match dotenvy::var("CSP_MODE").unwrap_or_default().to_lowercase().as_str() {
+            "" | "enforce" | "on" => CspMode::Enforce,
+            "report-only" | "report_only" => CspMode::ReportOnly,
+            "off" | "disabled" => CspMode::Off,
+            other => panic!("Unknown CSP_MODE '{}', expected enforce, report-only or off", other),
+        };
+
+        Self {
+            csp_mode,
+            csp_policy: env_or(
+                "CSP_POLICY",
+                "default-src 'self'; script-src 'self' 'nonce-{nonce}'; style-src 'self' 'nonce-{nonce}'; \
+                 style-src-attr 'unsafe-inline'; img-src 'self' data:; font-src 'self' data:; connect-src 'self'; \
+                 object-src 'none'; base-uri 'self'; form-action 'self'"
+                    .to_string(),
+            ),
+            swagger_csp_policy: env_or(
+                "CSP_POLICY_SWAGGER",
+                "default-src 'self'; script-src 'self' 'unsafe-inline'; style-src 'self' 'unsafe-inline'; \
+                 img-src 'self' data:; connect-src 'self'; object-src 'none'; base-uri 'self'"
+                    .to_string(),
+            ),
+            frame_ancestors: env_or("CSP_FRAME_ANCESTORS", "'none'".to_string()),
+            hsts_max_age: env_or("HSTS_MAX_AGE", 31_536_000),
+            referrer_policy: env_or("REFERRER_POLICY", "strict-origin-when-cross-origin".to_string()),
+            permissions_policy: env_or("PERMISSIONS_POLICY", "camera=(), microphone=(), geolocation=()".to_string()),
+        }
+    }
+}
+
+impl RuntimeSettings {
+    /// Variables backing these settings, changes to any other variable are reported as "restart required"
+    pub const KEYS: [&'static str; 5] = ["RUST_LOG", "CORS_ALLOWED_ORIGINS", "MAINTENANCE_MODE", "MAINTENANCE_MESSAGE", "MAINTENANCE_UNTIL"];
+    /// The Vite dev server in debug builds, so `pnpm dev` against `cargo run` works without any setup
+    #[cfg(debug_assertions)]
+    pub const DEFAULT_CORS_ORIGINS: &'static str = "http://localhost:5173";
+    /// Release builds serve the frontend themselves, from the same origin
+    #[cfg(not(debug_assertions))]
+    pub const DEFAULT_CORS_ORIGINS: &'static str = "";
+
+    pub fn from_env() -> Self {
+        Self::from_source(|key| dotenvy::var(key).ok()).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Builds the settings from any key/value source, the reloader uses the freshly read settings file
+    pub fn from_source(get: impl Fn(&str) -> Option<String>) -> std::result::Result<Self, String> {
+        let log_filter: String = get("RUST_LOG")
+            .filter(|value| !value.trim().is_empty())
+            .unwrap_or_else(|| "info".to_string());
+        // Set but empty allows no other origin, also in debug builds
+        let cors_origins: Vec<String> = get("CORS_ALLOWED_ORIGINS")
+            .unwrap_or_else(|| Self::DEFAULT_CORS_ORIGINS.to_string())
+            .split(',')
+            .map(|origin| origin.trim().trim_end_matches('/').to_string())
+            .filter(|origin| !origin.is_empty())
+            .collect();
+        let maintenance_message: Option<String> = get("MAINTENANCE_MESSAGE").filter(|message| !message.trim().is_empty());
+        let maintenance_until: Option<DateTime<Utc>> = match get("MAINTENANCE_UNTIL") {
+            Some(value) if !value.trim().is_empty() => Some(
+                DateTime::parse_from_rfc3339(value.trim())
+                    .map_err(|_| format!("MAINTENANCE_UNTIL must be an RFC 3339 time, e.g. 2026-01-31T22:00:00Z, not '{}'", value))?
+                    .with_timezone(&Utc),
+            ),
+            _ => None,
+        };
+
+        Ok(Self { log_filter, cors_origins, maintenance_message, maintenance_until })
+    }
+
+    /// `MAINTENANCE_MODE`, not kept with the other settings since pausing through the API changes
+    /// whether maintenance is on without touching the variable
+    pub fn maintenance_mode(get: impl Fn(&str) -> Option<String>) -> std::result::Result<bool, String> {
+        match get("MAINTENANCE_MODE") {
+            Some(value) if !value.trim().is_empty() => {
+                bool::from_env(value.trim()).ok_or_else(|| format!("MAINTENANCE_MODE has an invalid value: '{}'", value))
+            }
+            _ => Ok(false),
+        }
+    }
+
+    pub fn maintenance_notice(&self) -> MaintenanceNotice {
+        MaintenanceNotice {
+            message: self.maintenance_message.clone(),
+            until: self.maintenance_until,
+        }
+    }
+}
+
+/// Parses an optional environment variable, falling back to `default` when it is not set.
+/// Panics with the variable name when the value can't be parsed, same as the required variables.
+pub fn env_or<T: FromEnv>(key: &str, default: T) -> T {
+    match dotenvy::var(key) {
+        Ok(value) if !value.trim().is_empty() => {
+            T::from_env(value.trim()).unwrap_or_else(|| panic!("{} has an invalid value: '{}'", key, value))
+        }
+        _ => default,
+    }
+}
+
+pub trait FromEnv: Sized {
+    fn from_env(value: &str) -> Option<Self>;
+}
+
+macro_rules! from_env_via_from_str {
+    ($($ty:ty),*) => {
+        $(
+            impl FromEnv for $ty {
+                fn from_env(value: &str) -> Option<Self> {
+                    <$ty>::from_str(value).ok()
+                }
+            }
+        )*
+    };
+}
+
+from_env_via_from_str!(u16, u32, u64, usize, i32, String, PathBuf);
+
+impl FromEnv for bool {
+    fn from_env(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "1" | "true" | "yes" | "on" => Some(true),
+            "0" | "false" | "no" | "off" => Some(false),
+            _ => None,
+        }
+    }
+}
+
+/// Unset or empty is the default `full`
+impl FromEnv for LogFormat {
+    fn from_env(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "" | "full" | "text" => Some(LogFormat::Full),
+            "pretty" => Some(LogFormat::Pretty),
+            "compact" => Some(LogFormat::Compact),
+            "json" => Some(LogFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+impl<T: FromEnv> FromEnv for Option<T> {
+    fn from_env(value: &str) -> Option<Self> {
+        T::from_env(value).map(Some)
+    }
+}
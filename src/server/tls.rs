@@ -0,0 +1,338 @@
+use actix_web::{http::header, web, App, HttpRequest, HttpResponse, HttpServer};
+use notify::{Event, RecursiveMode, Watcher};
+use rustls::{
+    crypto::CryptoProvider,
+    pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer},
+    server::{ClientHello, ResolvesServerCert},
+    sign::CertifiedKey,
+    ServerConfig,
+};
+use std::{
+    fs::File,
+    io::{BufReader, Error, Result},
+    net::TcpListener,
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc, RwLock},
+};
+
+use super::settings::{TlsMode, TlsSettings};
+
+/// #### Certificate Resolver
+/// Hands the current certificate to every handshake. The certificate can be swapped at runtime,
+/// so renewed certificates are picked up by new connections without restarting the service.
+#[derive(Debug)]
+pub struct CertificateResolver {
+    current: RwLock<Arc<CertifiedKey>>,
+}
+
+impl CertificateResolver {
+    pub fn new(certified_key: CertifiedKey) -> Self {
+        Self { current: RwLock::new(Arc::new(certified_key)) }
+    }
+
+    pub fn replace(&self, certified_key: CertifiedKey) {
+        *self.current.write().expect("certificate lock poisoned") = Arc::new(certified_key);
+    }
+}
+
+impl ResolvesServerCert for CertificateResolver {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.current.read().expect("certificate lock poisoned").clone())
+    }
+}
+
+fn crypto_provider() -> Arc<CryptoProvider> {
+    Arc::new(rustls::crypto::ring::default_provider())
+}
+
+/// Builds the rustls configuration for the configured TLS mode, returns `None` when TLS is disabled.
+/// In `pem` mode the certificate files are watched and reloaded when they change.
+pub fn server_config(settings: &TlsSettings, hostname: &str) -> Result<Option<ServerConfig>> {
+    let certified_key: CertifiedKey = match settings.mode {
+        TlsMode::Disabled => return Ok(None),
+        TlsMode::Pem => {
+            let cert_path: &PathBuf = settings.cert_path.as_ref().expect("TLS_CERT_PATH must be set");
+            let key_path: &PathBuf = settings.key_path.as_ref().expect("TLS_KEY_PATH must be set");
+            load_certified_key(cert_path, key_path)?
+        }
+        TlsMode::SelfSigned => {
+            tracing::event!(target: "backend", tracing::Level::WARN, "TLS is using a self-signed certificate, do not use this mode in production.");
+            self_signed_certified_key(hostname)?
+        }
+    };
+
+    let resolver: Arc<CertificateResolver> = Arc::new(CertificateResolver::new(certified_key));
+    if settings.mode == TlsMode::Pem {
+        watch_certificates(
+            resolver.clone(),
+            settings.cert_path.clone().unwrap_or_default(),
+            settings.key_path.clone().unwrap_or_default(),
+        )?;
+    }
+
+    let mut config: ServerConfig = ServerConfig::builder_with_provider(crypto_provider())
+        .with_safe_default_protocol_versions()
+        .map_err(Error::other)?
+        .with_no_client_auth()
+        .with_cert_resolver(resolver);
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+    Ok(Some(config))
+}
+
+/// Reads a PEM certificate chain and private key and checks that they belong together.
+pub fn load_certified_key(cert_path: &Path, key_path: &Path) -> Result<CertifiedKey> {
+    let mut cert_reader: BufReader<File> = BufReader::new(File::open(cert_path)?);
+    let cert_chain: Vec<CertificateDer<'static>> =
+        rustls_pemfile::certs(&mut cert_reader).collect::<Result<Vec<_>>>()?;
+    if cert_chain.is_empty() {
+        return Err(Error::other(format!("No certificates found in {}", cert_path.display())));
+    }
+
+    let mut key_reader: BufReader<File> = BufReader::new(File::open(key_path)?);
+    let private_key: PrivateKeyDer<'static> = rustls_pemfile::private_key(&mut key_reader)?
+        .ok_or_else(|| Error::other(format!("No private key found in {}", key_path.display())))?;
+
+    CertifiedKey::from_der(cert_chain, private_key, &crypto_provider()).map_err(Error::other)
+}
+
+/// Generates a throwaway certificate for the configured host, `localhost` and the loopback addresses.
+pub fn self_signed_certified_key(hostname: &str) -> Result<CertifiedKey> {
+    let mut names: Vec<String> = vec!["localhost".to_string(), "127.0.0.1".to_string(), "::1".to_string()];
+    if !names.iter().any(|name| name == hostname) {
+        names.push(hostname.to_string());
+    }
+    let generated: rcgen::CertifiedKey = rcgen::generate_simple_self_signed(names).map_err(Error::other)?;
+    let private_key: PrivateKeyDer<'static> =
+        PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(generated.key_pair.serialize_der()));
+
+    CertifiedKey::from_der(vec![generated.cert.der().clone()], private_key, &crypto_provider()).map_err(Error::other)
+}
+
+/// Watches the certificate and key files on a background thread and swaps the certificate in
+/// the resolver whenever they change. A half-written pair keeps the previous certificate in use.
+fn watch_certificates(resolver: Arc<CertificateResolver>, cert_path: PathBuf, key_path: PathBuf) -> Result<()> {
+    let (tx, rx) = mpsc::channel::<notify::Result<Event>>();
+    let mut watcher = notify::recommended_watcher(tx).map_err(Error::other)?;
+    // Watch the parent folders, certificate renewals usually replace the files instead of writing into them
+    for path in [&cert_path, &key_path] {
+        let folder: &Path = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        watcher.watch(folder, RecursiveMode::NonRecursive).map_err(Error::other)?;
+    }
+
+    std::thread::spawn(move || {
+        // The watcher stops when dropped, so it lives as long as this thread
+        let _watcher = watcher;
+        let cert_name = cert_path.file_name().map(|n| n.to_os_string());
+        let key_name = key_path.file_name().map(|n| n.to_os_string());
+        for event in rx {
+            let Ok(event) = event else { continue };
+            let touches_certificate: bool = event.paths.iter().any(|p| {
+                let name = p.file_name().map(|n| n.to_os_string());
+                name == cert_name || name == key_name
+            });
+            if !touches_certificate || event.kind.is_access() {
+                continue;
+            }
+
+            match load_certified_key(&cert_path, &key_path) {
+                Ok(certified_key) => {
+                    resolver.replace(certified_key);
+                    tracing::event!(target: "backend", tracing::Level::INFO, "TLS certificate reloaded from {}", cert_path.display());
+                }
+                Err(e) => {
+                    tracing::event!(target: "backend", tracing::Level::WARN, "TLS certificate changed but could not be loaded, keeping the previous one: {}", e);
+                }
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// Binds `TLS_REDIRECT_PORT` and builds the redirect server, `None` when TLS is off or no port is set.
+/// Used by the console app and the Windows service alike.
+pub fn redirect_server(settings: &TlsSettings, hostname: &str, https_port: u16) -> Result<Option<actix_web::dev::Server>> {
+    match settings.redirect_port {
+        Some(redirect_port) if settings.enabled() => {
+            let listener: TcpListener = TcpListener::bind(format!("{}:{}", hostname, redirect_port))?;
+            Ok(Some(build_redirect_server(listener, https_port)?))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// #### HTTP to HTTPS redirect
+/// Plain HTTP listener that answers every request with a permanent redirect to the HTTPS port.
+pub fn build_redirect_server(listener: TcpListener, https_port: u16) -> Result<actix_web::dev::Server> {
+    let server: actix_web::dev::Server = HttpServer::new(move || {
+        App::new().default_service(web::to(move |req: HttpRequest| redirect_to_https(req, https_port)))
+    })
+    .listen(listener)?
+    .workers(1)
+    .shutdown_timeout(5)
+    .run();
+
+    Ok(server)
+}
+
+async fn redirect_to_https(req: HttpRequest, https_port: u16) -> HttpResponse {
+    let host: String = req.connection_info().host().to_string();
+    let host: &str = strip_port(&host);
+    let path_and_query: &str = req.uri().path_and_query().map(|pq| pq.as_str()).unwrap_or("/");
+    let location: String = if https_port == 443 {
+        format!("https://{}{}", host, path_and_query)
+    } else {
+        format!("https://{}:{}{}", host, https_port, path_and_query)
+    };
+
+    HttpResponse::PermanentRedirect()
+        .insert_header((header::LOCATION, location))
+        .finish()
+}
+
+/// Removes the port from a `Host` header value, keeping IPv6 literals such as `[::1]` intact.
+fn strip_port(host: &str) -> &str {
+    match host.rsplit_once(':') {
+        Some((name, port)) if !name.is_empty() && !port.contains(']') && port.chars().all(|c| c.is_ascii_digit()) => name,
+        _ => host,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{http::StatusCode, test::TestRequest};
+    use std::time::{Duration, Instant};
+
+    /// Folder for certificate files, removed when dropped
+    struct CertDir(PathBuf);
+
+    impl CertDir {
+        fn new() -> Self {
+            let dir: PathBuf = std::env::temp_dir().join(format!("tls-test-{}", uuid::Uuid::new_v4().simple()));
+            std::fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+
+        fn write(&self, name: &str, contents: &str) -> PathBuf {
+            let path: PathBuf = self.0.join(name);
+            std::fs::write(&path, contents).unwrap();
+            path
+        }
+
+        /// Replaces the file the way certificate renewals do, by renaming a finished file over it
+        fn replace(&self, name: &str, contents: &str) {
+            let staged: PathBuf = self.write(&format!("{}.new", name), contents);
+            std::fs::rename(staged, self.0.join(name)).unwrap();
+        }
+    }
+
+    impl Drop for CertDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// Certificate and key PEM of a fresh self-signed certificate
+    fn generate(name: &str) -> (String, String) {
+        let generated: rcgen::CertifiedKey = rcgen::generate_simple_self_signed(vec![name.to_string()]).unwrap();
+        (generated.cert.pem(), generated.key_pair.serialize_pem())
+    }
+
+    fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+        haystack.windows(needle.len()).any(|window| window == needle)
+    }
+
+    #[test]
+    fn port_is_stripped_from_the_host() {
+        assert_eq!(strip_port("example.com:8080"), "example.com");
+        assert_eq!(strip_port("example.com"), "example.com");
+        assert_eq!(strip_port("127.0.0.1:80"), "127.0.0.1");
+        assert_eq!(strip_port("[::1]:8443"), "[::1]");
+        assert_eq!(strip_port("[::1]"), "[::1]");
+        assert_eq!(strip_port(":80"), ":80");
+        assert_eq!(strip_port("example.com:http"), "example.com:http");
+    }
+
+    #[actix_web::test]
+    async fn redirect_keeps_host_path_and_query() {
+        let req: HttpRequest = TestRequest::get().uri("/app/page?tab=2").insert_header((header::HOST, "example.com:8080")).to_http_request();
+        let res: HttpResponse = redirect_to_https(req.clone(), 8443).await;
+        assert_eq!(res.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(res.headers().get(header::LOCATION).unwrap(), "https://example.com:8443/app/page?tab=2");
+
+        let res: HttpResponse = redirect_to_https(req, 443).await;
+        assert_eq!(res.headers().get(header::LOCATION).unwrap(), "https://example.com/app/page?tab=2");
+
+        let req: HttpRequest = TestRequest::get().uri("/").insert_header((header::HOST, "[::1]:8080")).to_http_request();
+        let res: HttpResponse = redirect_to_https(req, 8443).await;
+        assert_eq!(res.headers().get(header::LOCATION).unwrap(), "https://[::1]:8443/");
+    }
+
+    #[test]
+    fn matching_pem_pair_is_loaded() {
+        let dir: CertDir = CertDir::new();
+        let (cert, key) = generate("example.test");
+        let certified_key: CertifiedKey = load_certified_key(&dir.write("cert.pem", &cert), &dir.write("key.pem", &key)).unwrap();
+        assert_eq!(certified_key.cert.len(), 1);
+    }
+
+    #[test]
+    fn empty_pem_files_are_rejected() {
+        let dir: CertDir = CertDir::new();
+        let (cert, key) = generate("example.test");
+        let empty: PathBuf = dir.write("empty.pem", "");
+
+        let error: Error = load_certified_key(&empty, &dir.write("key.pem", &key)).unwrap_err();
+        assert!(error.to_string().starts_with("No certificates found"), "{}", error);
+        let error: Error = load_certified_key(&dir.write("cert.pem", &cert), &empty).unwrap_err();
+        assert!(error.to_string().starts_with("No private key found"), "{}", error);
+    }
+
+    #[test]
+    fn mismatched_pem_pair_is_rejected() {
+        let dir: CertDir = CertDir::new();
+        let (cert, _) = generate("example.test");
+        let (_, other_key) = generate("example.test");
+        assert!(load_certified_key(&dir.write("cert.pem", &cert), &dir.write("key.pem", &other_key)).is_err());
+    }
+
+    #[test]
+    fn self_signed_certificate_covers_the_host() {
+        let certified_key: CertifiedKey = self_signed_certified_key("app.example.test").unwrap();
+        let der: &[u8] = certified_key.end_entity_cert().unwrap().as_ref();
+        assert!(contains(der, b"app.example.test"));
+        assert!(contains(der, b"localhost"));
+
+        // Already one of the default names, so it isn't listed twice
+        let certified_key: CertifiedKey = self_signed_certified_key("localhost").unwrap();
+        let der: &[u8] = certified_key.end_entity_cert().unwrap().as_ref();
+        assert_eq!(der.windows(b"localhost".len()).filter(|window| *window == b"localhost").count(), 1);
+    }
+
+    #[test]
+    fn renewed_certificate_is_picked_up() {
+        let dir: CertDir = CertDir::new();
+        let (cert, key) = generate("old.example.test");
+        let cert_path: PathBuf = dir.write("cert.pem", &cert);
+        let key_path: PathBuf = dir.write("key.pem", &key);
+        let resolver: Arc<CertificateResolver> = Arc::new(CertificateResolver::new(load_certified_key(&cert_path, &key_path).unwrap()));
+        watch_certificates(resolver.clone(), cert_path, key_path).unwrap();
+        let serves = |name: &[u8]| contains(resolver.current.read().unwrap().end_entity_cert().unwrap().as_ref(), name);
+
+        // A broken file keeps the previous certificate
+        dir.replace("cert.pem", "not a certificate");
+        std::thread::sleep(Duration::from_millis(200));
+        assert!(serves(b"old.example.test"));
+
+        let (cert, key) = generate("new.example.test");
+        dir.replace("key.pem", &key);
+        dir.replace("cert.pem", &cert);
+        let deadline: Instant = Instant::now() + Duration::from_secs(10);
+        while !serves(b"new.example.test") {
+            assert!(Instant::now() < deadline, "the renewed certificate was not loaded");
+            std::thread::sleep(Duration::from_millis(20));
+        }
+    }
+}
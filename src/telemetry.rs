@@ -1,81 +1,82 @@
-use std::path::PathBuf;
-use tracing_appender::{
-    non_blocking::WorkerGuard,
-    rolling::{RollingFileAppender, Rotation},
-};
+use actix_svelte::otel::{self, TracerGuard};
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use tracing_appender::non_blocking::WorkerGuard;
 use tracing_subscriber::{
-    EnvFilter,
-    fmt::{self, time::LocalTime},
+    EnvFilter, Layer, Registry,
+    fmt::{self, MakeWriter, time::{LocalTime, UtcTime}},
+    reload,
 };
 
-fn get_log_directory() -> PathBuf {
-    // if not in IIS context, use a logs folder int he current directory
-    let log_dir: PathBuf = PathBuf::from("./logs");
-    if !log_dir.exists() {
-        if let Err(e) = std::fs::create_dir_all(&log_dir) {
-            tracing::error!("Failed to create log directory: {}", e);
-        }
-    }
+use crate::log_rotation::RollingLogFile;
+use crate::server::{
+    log_buffer,
+    settings::{LogFormat, LoggingSettings},
+};
 
-    log_dir
+/// Keep alive until the process exits, dropping it flushes the log files and exports the last spans
+pub struct LogGuards {
+    _files: Vec<WorkerGuard>,
+    _tracer: Option<TracerGuard>,
 }
 
-pub fn setup_logging() -> Result<WorkerGuard, Box<dyn std::error::Error>> {
-    let log_dir: PathBuf = get_log_directory();
+pub fn setup_logging(settings: &LoggingSettings) -> Result<LogGuards, Box<dyn std::error::Error>> {
+    std::fs::create_dir_all(&settings.directory)
+        .map_err(|e| format!("Failed to create the log directory {}: {}", settings.directory.display(), e))?;
+    let mut guards: Vec<WorkerGuard> = Vec::new();
 
-    // Create file appender with daily reotation (generates files like: 'app-2025-05-02.log')
-    let file_appender: RollingFileAppender = RollingFileAppender::builder()
-        .rotation(Rotation::DAILY)
-        .filename_suffix("log")
-        .build(log_dir)
-        .expect("Failed to create file appender");
+    // Rotated by time and size (generates files like: '2025-05-02.log', '2025-05-02.1.log.gz')
+    let file_appender: RollingLogFile = RollingLogFile::new(settings, &settings.file_prefix)
+        .map_err(|e| format!("Failed to create the log file in {}: {}", settings.directory.display(), e))?;
 
     // Create a non-blocking writer to avoid performance issues
     let (non_blocking, guard) = tracing_appender::non_blocking(file_appender);
-
-    // Create a custom time format for the log messages
-    let time_format: Vec<time::format_description::BorrowedFormatItem<'_>> =
-        time::format_description::parse("[hour]:[minute]:[second]")
-            .expect("format string should be valid!");
-    let timer: LocalTime<Vec<time::format_description::BorrowedFormatItem<'_>>> =
-        LocalTime::new(time_format);
+    guards.push(guard);
 
     // Create environment filter
     let env_filter: EnvFilter =
         EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let directives: String = env_filter.to_string();
+    // Wrapped in a reload layer so the filter can change without a restart (see `server::log_level`)
+    let (env_filter, filter_handle) = reload::Layer::new(env_filter);
+    crate::server::log_level::install_log_filter(filter_handle, directives);
 
     // File layer - no ANSI colors
-    let file_layer = fmt::layer()
-        .with_writer(non_blocking)
-        .with_ansi(false)
-        .with_timer(timer.clone())
-        .with_level(true)
-        .with_target(true)
-        .with_file(true)
-        .with_line_number(true);
+    let mut layers: Vec<BoxedLayer> = vec![format_layer(settings.file_format, non_blocking, false)];
+    // What the admin log console shows, see `server::log_buffer`
+    layers.push(log_buffer::layer(settings.buffer_lines).boxed());
+
+    // Only add console layer in debug builds
+    #[cfg(debug_assertions)]
+    layers.push(format_layer(settings.console_format, std::io::stdout, true));
+
+    // `RUST_LOG` filters the console and the main file, the target files below have their own filters
+    let mut layers: Vec<BoxedLayer> = vec![layers.with_filter(env_filter).boxed()];
+    for target in &settings.target_files {
+        let target_filter: EnvFilter = EnvFilter::try_new(&target.filter)
+            .map_err(|e| format!("Invalid filter for the {} log file: {}", target.name, e))?;
+        let target_appender: RollingLogFile = RollingLogFile::new(settings, &target.name)?;
+        let (target_writer, guard) = tracing_appender::non_blocking(target_appender);
+        guards.push(guard);
+        layers.push(format_layer(settings.file_format, target_writer, false).with_filter(target_filter).boxed());
+    }
+
+    // Spans go to the OpenTelemetry collector with their own filter, `RUST_LOG` only concerns the logs
+    let mut tracer: Option<TracerGuard> = None;
+    if let Some(otlp) = &settings.otlp {
+        let otlp_filter: EnvFilter = EnvFilter::try_new(&otlp.filter)
+            .map_err(|e| format!("Invalid OTEL_TRACES_FILTER: {}", e))?;
+        let exporter = otel::otlp_exporter(&otlp.endpoint)
+            .map_err(|e| format!("Failed to create the OTLP exporter for {}: {}", otlp.endpoint, e))?;
+        let provider: SdkTracerProvider = otel::tracer_provider(exporter, otel::resource(&otlp.service_name, &otlp.service_version));
+        layers.push(otel::layer(&provider).with_filter(otlp_filter).boxed());
+        tracer = Some(TracerGuard::new(provider));
+    }
 
-    // Combine both layers with the filter
     use tracing_subscriber::layer::SubscriberExt;
     use tracing_subscriber::util::SubscriberInitExt;
 
-    // Create a registry and add the layers to it, only adding the file layer as we add the console later
-    let registry = tracing_subscriber::registry()
-        .with(env_filter)
-        .with(file_layer);
-
-    // Only add console layer in debug builds
-    #[cfg(debug_assertions)]
-    let registry = {
-        // Console layer - only in debug/development builds
-        let console_layer = fmt::layer()
-            .with_writer(std::io::stdout)
-            .with_ansi(true) // Enable colors for console
-            .with_timer(timer)
-            .with_level(true)
-            .with_target(true);
-
-        registry.with(console_layer)
-    };
+    // Create a registry and add the layers to it
+    let registry = tracing_subscriber::registry().with(layers);
 
     // Initialize the registry
     registry.init();
@@ -92,6 +93,114 @@ pub fn setup_logging() -> Result<WorkerGuard, Box<dyn std::error::Error>> {
     tracing::info!("This is an info message I want displayed in the log file.");
     tracing::warn!("This is a warning message I want displayed in the log file.");
     tracing::error!("This is an error message I want displayed in the log file.");
+    if let Some(otlp) = &settings.otlp {
+        tracing::info!("Exporting spans to {} as {}", otlp.endpoint, otlp.service_name);
+    }
+
+    Ok(LogGuards { _files: guards, _tracer: tracer })
+}
+
+type BoxedLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+/// A `fmt` layer in the configured format. JSON lines carry a full UTC timestamp and the fields of
+/// the current span and its parents, e.g. the `request_id` of `server::request_id`.
+fn format_layer<W>(format: LogFormat, writer: W, console: bool) -> BoxedLayer
+where
+    W: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+{
+    // Create a custom time format for the log messages
+    let time_format: Vec<time::format_description::BorrowedFormatItem<'_>> =
+        time::format_description::parse("[hour]:[minute]:[second]")
+            .expect("format string should be valid!");
+    let timer: LocalTime<Vec<time::format_description::BorrowedFormatItem<'_>>> =
+        LocalTime::new(time_format);
+
+    // Colors only on the console and never in JSON, where escape codes would end up inside the values
+    let layer = fmt::layer()
+        .with_writer(writer)
+        .with_ansi(console && format != LogFormat::Json)
+        .with_level(true)
+        .with_target(true);
+    // Files get the source location, the console stays short
+    let layer = layer.with_file(!console).with_line_number(!console);
+
+    // Files get the full UTC time, so the log search can filter their lines by time range
+    match (format, console) {
+        (LogFormat::Full, true) => layer.with_timer(timer).boxed(),
+        (LogFormat::Full, false) => layer.with_timer(UtcTime::rfc_3339()).boxed(),
+        (LogFormat::Pretty, true) => layer.pretty().with_timer(timer).boxed(),
+        (LogFormat::Pretty, false) => layer.pretty().with_timer(UtcTime::rfc_3339()).boxed(),
+        (LogFormat::Compact, true) => layer.compact().with_timer(timer).boxed(),
+        (LogFormat::Compact, false) => layer.compact().with_timer(UtcTime::rfc_3339()).boxed(),
+        (LogFormat::Json, _) => layer
+            .json()
+            .with_timer(UtcTime::rfc_3339())
+            .with_current_span(true)
+            .with_span_list(true)
+            .boxed(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::settings::FromEnv;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn log_format_is_parsed() {
+        assert_eq!(LogFormat::from_env(""), Some(LogFormat::Full));
+        assert_eq!(LogFormat::from_env("text"), Some(LogFormat::Full));
+        assert_eq!(LogFormat::from_env(" Pretty "), Some(LogFormat::Pretty));
+        assert_eq!(LogFormat::from_env("compact"), Some(LogFormat::Compact));
+        assert_eq!(LogFormat::from_env("JSON"), Some(LogFormat::Json));
+        assert_eq!(LogFormat::from_env("yaml"), None);
+    }
+
+    /// Everything the layer writes, shared with the test
+    #[derive(Clone, Default)]
+    struct Output(Arc<Mutex<Vec<u8>>>);
 
-    Ok(guard)
+    impl std::io::Write for Output {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn log_line(format: LogFormat, console: bool) -> String {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let output: Output = Output::default();
+        let writer: Output = output.clone();
+        let subscriber = tracing_subscriber::registry().with(format_layer(format, move || writer.clone(), console));
+        tracing::subscriber::with_default(subscriber, || tracing::info!(user = "jane", "Signed in"));
+
+        let bytes: Vec<u8> = output.0.lock().unwrap().clone();
+        String::from_utf8(bytes).unwrap()
+    }
+
+    #[test]
+    fn text_console_is_colored_and_files_are_not() {
+        assert!(log_line(LogFormat::Full, true).contains('\x1b'));
+        assert!(log_line(LogFormat::Compact, true).contains('\x1b'));
+        assert!(!log_line(LogFormat::Full, false).contains('\x1b'));
+        assert!(!log_line(LogFormat::Pretty, false).contains('\x1b'));
+    }
+
+    #[test]
+    fn json_is_never_colored() {
+        for console in [true, false] {
+            let line: String = log_line(LogFormat::Json, console);
+            assert!(!line.contains('\x1b'), "{}", line);
+            let event: serde_json::Value = serde_json::from_str(line.trim()).unwrap();
+            assert_eq!(event["fields"]["message"], "Signed in");
+            assert_eq!(event["fields"]["user"], "jane");
+            assert_eq!(event["level"], "INFO");
+        }
+    }
 }
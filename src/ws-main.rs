@@ -1,8 +1,14 @@
+mod log_rotation;
 mod server;
+mod telemetry;
 
-use std::net::TcpListener;
 use server::build_server_app;
+use server::listeners::Listeners;
+use server::maintenance::{self, MaintenanceNotice};
+use server::settings::{ApplicationSettings, LoggingSettings, Settings};
+use server::tls;
 use tokio::runtime::Runtime;
+use telemetry::LogGuards;
 use tokio_util::sync::CancellationToken;
 use std::sync::mpsc;
 use std::time::Duration;
@@ -13,17 +19,22 @@ use windows_service::{
   service::{
       ServiceControl, ServiceControlAccept, ServiceExitCode, ServiceState, ServiceStatus, ServiceType,
   },
-  service_control_handler::{self, ServiceControlHandlerResult},
+  service_control_handler::{self, ServiceControlHandlerResult, ServiceStatusHandle},
   service_dispatcher,
 };
 
 // Service name
 const SERVICE_NAME: &str = "actix_example";
-// Global flag for shutdown coordination
-pub static PAUSED: AtomicBool = AtomicBool::new(false);
 
 // Main service logic
-fn run_service_server(rx: mpsc::Receiver<ServiceControl>) -> Result<(), Box<dyn std::error::Error>> {
+fn run_service_server(rx: mpsc::Receiver<ServiceControl>, status_handle: ServiceStatusHandle) -> Result<(), Box<dyn std::error::Error>> {
+    // Services start in System32, relative paths like `LOG_DIR` and `.env` are meant next to the executable
+    if let Some(exe_dir) = std::env::current_exe()?.parent() {
+        std::env::set_current_dir(exe_dir)?;
+    }
+    // Before the first `dotenvy::var` loads .env, so reloads can tell its values from real ones
+    server::reload::capture_process_env();
+    let _log_guards: LogGuards = telemetry::setup_logging(&LoggingSettings::from_env())?;
     // Create a new tokio runtime
     let rt: Runtime = Runtime::new()?;
     
@@ -34,12 +45,17 @@ fn run_service_server(rx: mpsc::Receiver<ServiceControl>) -> Result<(), Box<dyn
     // Spawn the Actix web server
     rt.block_on(async move {
         let hostname: &str = env!("WINDOW_SERVICE_HOST");
-        let port: i32 = env!("WINDOW_SERVICE_PORT").parse().expect("WINDOW_SERVICE_PORT must be a number");
-        let listener: TcpListener = TcpListener::bind(format!("{}:{}", hostname, port)).expect("Failed to bind address");
+        let port: u16 = env!("WINDOW_SERVICE_PORT").parse().expect("WINDOW_SERVICE_PORT must be a number");
+        let settings: Settings = Settings::with_application(ApplicationSettings::with_address(hostname.to_string(), port));
+        let listeners: Listeners = Listeners::bind(&settings)?;
+        let https_port: u16 = listeners.port()?;
         let cancel_token: CancellationToken = CancellationToken::new();
-        let server_app: actix_web::dev::Server = build_server_app(listener, cancel_token.clone()).await?;
+        // Optional plain HTTP listener that only redirects to the HTTPS port, like the console app
+        let redirect_server: Option<actix_web::dev::Server> = tls::redirect_server(&settings.tls, hostname, https_port)?;
+        let server_app: actix_web::dev::Server = build_server_app(listeners, settings, cancel_token.clone()).await?;
 
         let srv: actix_web::dev::ServerHandle = server_app.handle();
+        let redirect_srv: Option<actix_web::dev::ServerHandle> = redirect_server.as_ref().map(|redirect_server| redirect_server.handle());
 
         // Spawn control message handler
         tokio::spawn(async move {
@@ -47,25 +63,33 @@ fn run_service_server(rx: mpsc::Receiver<ServiceControl>) -> Result<(), Box<dyn
               match control {
                   ServiceControl::Stop => {
                       running_clone.store(false, Ordering::SeqCst);
+                      if let Some(redirect_srv) = &redirect_srv {
+                          redirect_srv.stop(true).await;
+                      }
                       srv.stop(true).await;
                       break;
                   }
+                  // Connections are still accepted so visitors get the maintenance page instead of a timeout
                   ServiceControl::Pause => {
-                      PAUSED.store(true, Ordering::SeqCst);
-                      // You could also pause accepting new connections here
-                      srv.pause().await;
+                      maintenance::pause(MaintenanceNotice::default(), "the service control manager");
+                      set_service_state(&status_handle, ServiceState::Paused);
                   }
                   ServiceControl::Continue => {
-                      PAUSED.store(false, Ordering::SeqCst);
-                      // You could resume accepting connections here
-                      srv.resume().await;
+                      maintenance::resume("the service control manager");
+                      set_service_state(&status_handle, ServiceState::Running);
                   }
                   _ => {}
               }
           }
         });
 
-        server_app.await?;
+        match redirect_server {
+            Some(redirect_server) => {
+                let (served, _) = tokio::join!(server_app, redirect_server);
+                served?;
+            }
+            None => server_app.await?,
+        }
         Ok::<(), std::io::Error>(())
     })?;
 
@@ -103,18 +127,10 @@ fn service_main(_arguments: Vec<std::ffi::OsString>) {
     ).unwrap();
 
     // Tell the system that the service is running
-    status_handle.set_service_status(ServiceStatus {
-        service_type: ServiceType::OWN_PROCESS,
-        current_state: ServiceState::Running,
-        controls_accepted: ServiceControlAccept::STOP,
-        exit_code: ServiceExitCode::Win32(0),
-        checkpoint: 0,
-        wait_hint: Duration::default(),
-        process_id: None,
-    }).unwrap();
+    set_service_state(&status_handle, ServiceState::Running);
 
     // Run the server
-    if let Err(e) = run_service_server(control_rx) {
+    if let Err(e) = run_service_server(control_rx, status_handle) {
         // Log the error
         eprintln!("Service error: {}", e);
     }
@@ -131,6 +147,22 @@ fn service_main(_arguments: Vec<std::ffi::OsString>) {
     }).unwrap();
 }
 
+// Reports a running or paused service, both accept stop, pause and continue
+fn set_service_state(status_handle: &ServiceStatusHandle, state: ServiceState) {
+    let result = status_handle.set_service_status(ServiceStatus {
+        service_type: ServiceType::OWN_PROCESS,
+        current_state: state,
+        controls_accepted: ServiceControlAccept::STOP | ServiceControlAccept::PAUSE_CONTINUE,
+        exit_code: ServiceExitCode::Win32(0),
+        checkpoint: 0,
+        wait_hint: Duration::default(),
+        process_id: None,
+    });
+    if let Err(e) = result {
+        eprintln!("Failed to report the service state {:?}: {}", state, e);
+    }
+}
+
 #[cfg(all(windows, not(debug_assertions)))]
 fn main() -> Result<(), windows_service::Error> {
     // Start the service dispatcher
@@ -142,13 +174,14 @@ fn main() -> Result<(), windows_service::Error> {
 #[cfg(debug_assertions)]
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
+  server::reload::capture_process_env();
+  let _log_guards: LogGuards = telemetry::setup_logging(&LoggingSettings::from_env()).expect("Failed to set up logging");
   let hostname: String = dotenvy::var("WINDOW_SERVICE_HOST").expect("HOST must be set");
   let port: u16 = dotenvy::var("WINDOW_SERVICE_PORT")
     .unwrap_or_else(|_| "5000".to_string()) // Default to 5000 if nothing is set
     .parse::<u16>().expect("WINDOW_SERVICE_PORT must be a number");
-  let listener: TcpListener = TcpListener::bind(format!("{hostname}:{port}")).expect("Failed to bind to address");
-  let cancel_token: CancellationToken = CancellationToken::new();
-  let server_app: server::Application = server::Application::build(hostname, port, None).await?;
+  let settings: Settings = Settings::with_application(ApplicationSettings::with_address(hostname, port));
+  let server_app: server::Application = server::Application::build(settings, None).await?;
 
   server_app.run_until_stopped().await
 }
\ No newline at end of file
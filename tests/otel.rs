@@ -0,0 +1,80 @@
+use actix_svelte::otel;
+use actix_web::http::header::{HeaderMap, HeaderName, HeaderValue};
+use opentelemetry::{
+    trace::{SpanId, SpanKind, TraceId},
+    Key, KeyValue, Value,
+};
+use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider, SpanData};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::layer::SubscriberExt;
+
+const TRACEPARENT: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+/// Runs `traced` with the OpenTelemetry layer exporting to memory and returns the finished spans
+fn export_spans(traced: impl FnOnce()) -> Vec<SpanData> {
+    let exporter: InMemorySpanExporter = InMemorySpanExporter::default();
+    let provider: SdkTracerProvider = otel::tracer_provider(exporter.clone(), otel::resource("Test App", "1.2.3"));
+    let subscriber = tracing_subscriber::registry().with(otel::layer(&provider));
+    tracing::subscriber::with_default(subscriber, traced);
+    provider.force_flush().unwrap();
+
+    exporter.get_finished_spans().unwrap()
+}
+
+fn headers(traceparent: &str) -> HeaderMap {
+    let mut headers: HeaderMap = HeaderMap::new();
+    headers.insert(HeaderName::from_static("traceparent"), HeaderValue::from_str(traceparent).unwrap());
+    headers
+}
+
+fn span<'a>(spans: &'a [SpanData], name: &str) -> &'a SpanData {
+    spans.iter().find(|span| span.name == name).unwrap_or_else(|| panic!("no span named {}", name))
+}
+
+#[test]
+fn request_span_continues_the_incoming_trace() {
+    let spans: Vec<SpanData> = export_spans(|| {
+        let request = tracing::info_span!("request", otel.name = "POST /api/auth/login", otel.kind = "server");
+        request.set_parent(otel::parent_context(&headers(TRACEPARENT))).unwrap();
+        request.in_scope(|| {
+            otel::db_span("SELECT", "users").in_scope(|| {});
+            otel::password_hash_span("verify").in_scope(|| {});
+        });
+    });
+
+    let request: &SpanData = span(&spans, "POST /api/auth/login");
+    assert_eq!(request.span_kind, SpanKind::Server);
+    assert_eq!(request.span_context.trace_id(), TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736").unwrap());
+    assert_eq!(request.parent_span_id, SpanId::from_hex("00f067aa0ba902b7").unwrap());
+    assert!(request.parent_span_is_remote);
+
+    for child in [span(&spans, "SELECT users"), span(&spans, "argon2 verify")] {
+        assert_eq!(child.span_context.trace_id(), request.span_context.trace_id());
+        assert_eq!(child.parent_span_id, request.span_context.span_id());
+    }
+    let query: &SpanData = span(&spans, "SELECT users");
+    assert_eq!(query.span_kind, SpanKind::Client);
+    assert!(query.attributes.contains(&KeyValue::new("db.system.name", "sqlite")));
+    assert!(query.attributes.contains(&KeyValue::new("db.collection.name", "users")));
+}
+
+#[test]
+fn invalid_traceparent_starts_a_new_trace() {
+    let spans: Vec<SpanData> = export_spans(|| {
+        let request = tracing::info_span!("request", otel.name = "GET /api/health");
+        let _ = request.set_parent(otel::parent_context(&headers("00-not-a-trace-01")));
+        request.in_scope(|| {});
+    });
+
+    let request: &SpanData = span(&spans, "GET /api/health");
+    assert_ne!(request.span_context.trace_id(), TraceId::INVALID);
+    assert_eq!(request.parent_span_id, SpanId::INVALID);
+}
+
+#[test]
+fn resource_names_the_service() {
+    let resource = otel::resource("Test App", "1.2.3");
+
+    assert_eq!(resource.get(&Key::new("service.name")), Some(Value::from("Test App")));
+    assert_eq!(resource.get(&Key::new("service.version")), Some(Value::from("1.2.3")));
+}
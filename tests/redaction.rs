@@ -0,0 +1,112 @@
+use actix_svelte::{
+    secrets::{open_secrets, seal_secrets, secret_var, Secret},
+    CreateUser,
+};
+use std::{
+    io::Write,
+    sync::{Arc, Mutex},
+};
+use tracing_subscriber::fmt::MakeWriter;
+
+const SECRET_VALUE: &str = "0f1e2d3c4b5a69788796a5b4c3d2e1f0";
+
+/// Collects everything the fmt layer writes so the test can search it
+#[derive(Clone, Default)]
+struct CapturedLogs(Arc<Mutex<Vec<u8>>>);
+
+impl CapturedLogs {
+    fn contents(&self) -> String {
+        String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+    }
+}
+
+impl Write for CapturedLogs {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for CapturedLogs {
+    type Writer = CapturedLogs;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+#[test]
+fn secret_formatting_is_redacted() {
+    let secret: Secret<String> = Secret::new(SECRET_VALUE.to_string());
+
+    assert_eq!(format!("{}", secret), "[REDACTED]");
+    assert_eq!(format!("{:?}", secret), "[REDACTED]");
+    assert_eq!(secret.expose(), SECRET_VALUE);
+}
+
+#[test]
+fn serialized_secret_is_redacted() {
+    let user: CreateUser = serde_json::from_str(&format!(r#"{{"username":"operator","password":"{}"}}"#, SECRET_VALUE)).unwrap();
+    let body: String = serde_json::to_string(&serde_json::json!({ "password": user.password })).unwrap();
+
+    assert_eq!(user.password.expose(), SECRET_VALUE);
+    assert!(!body.contains(SECRET_VALUE), "response body leaked the secret: {}", body);
+}
+
+#[test]
+fn tracing_output_never_contains_secrets() {
+    let logs: CapturedLogs = CapturedLogs::default();
+    let subscriber = tracing_subscriber::fmt()
+        .with_writer(logs.clone())
+        .with_max_level(tracing::Level::TRACE)
+        .json()
+        .finish();
+
+    tracing::subscriber::with_default(subscriber, || {
+        let secret: Secret<String> = Secret::new(SECRET_VALUE.to_string());
+        let user: CreateUser = CreateUser {
+            username: "operator".to_string(),
+            password: Secret::new(SECRET_VALUE.to_string()),
+        };
+        tracing::info!(secret = %secret, "display field");
+        tracing::info!(secret = ?secret, "debug field");
+        tracing::debug!("user payload {:?}", user);
+        let span = tracing::info_span!("request", token = ?secret);
+        let _entered = span.enter();
+        tracing::warn!("inside span");
+    });
+
+    let output: String = logs.contents();
+    assert!(output.contains("[REDACTED]"));
+    assert!(!output.contains(SECRET_VALUE), "tracing output leaked the secret: {}", output);
+}
+
+#[test]
+fn file_variant_takes_precedence() {
+    let path = std::env::temp_dir().join(format!("actix-svelte-secret-{}", std::process::id()));
+    std::fs::write(&path, format!("{}\n", SECRET_VALUE)).unwrap();
+    std::env::set_var("REDACTION_TEST_KEY", "plain value");
+    std::env::set_var("REDACTION_TEST_KEY_FILE", &path);
+
+    let secret: Secret<String> = secret_var("REDACTION_TEST_KEY").unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(secret.expose(), SECRET_VALUE);
+}
+
+#[test]
+fn sealed_secrets_round_trip() {
+    let key: Secret<String> = Secret::new("11".repeat(32));
+    let plaintext: String = format!("SECRET_KEY={}\n", SECRET_VALUE);
+
+    let sealed: Vec<u8> = seal_secrets(plaintext.as_bytes(), &key).unwrap();
+    assert!(!String::from_utf8_lossy(&sealed).contains(SECRET_VALUE));
+    assert_eq!(open_secrets(&sealed, &key).unwrap(), plaintext.as_bytes());
+
+    let wrong_key: Secret<String> = Secret::new("22".repeat(32));
+    assert!(open_secrets(&sealed, &wrong_key).is_err());
+}